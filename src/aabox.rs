@@ -0,0 +1,70 @@
+use glam::{Mat4, Vec3};
+use crate::Shape;
+use crate::medium::{gpu_medium_idx, NO_MEDIUM};
+
+// an axis aligned box given by its min and max corners
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct AABox {
+    pub box_min: Vec3,
+    material_idx: u32,
+    pub box_max: Vec3,
+    medium_idx: u32,
+}
+
+unsafe impl bytemuck::Pod for AABox {}
+unsafe impl bytemuck::Zeroable for AABox {}
+
+impl AABox {
+    pub fn new(a: Vec3, b: Vec3, material_idx: u32) -> Self {
+        Self { box_min: a.min(b), material_idx, box_max: a.max(b), medium_idx: NO_MEDIUM }
+    }
+
+    // fill the box with one of the scene's media, turning it into an invisible boundary
+    pub fn with_medium(mut self, medium_idx: u32) -> Self {
+        self.medium_idx = gpu_medium_idx(medium_idx);
+        self
+    }
+
+    pub fn material_idx(&self) -> u32 {
+        self.material_idx
+    }
+
+    // the box around the box's corners once moved by transform, which is the box itself
+    // unless the transform turns it off the axes
+    pub(crate) fn transformed(&self, transform: &Mat4) -> Self {
+        let mut box_min = Vec3::INFINITY;
+        let mut box_max = Vec3::NEG_INFINITY;
+        for i in 0..8 {
+            let corner = Vec3::new(
+                if i & 1 == 0 { self.box_min.x } else { self.box_max.x },
+                if i & 2 == 0 { self.box_min.y } else { self.box_max.y },
+                if i & 4 == 0 { self.box_min.z } else { self.box_max.z });
+            let world_corner = transform.transform_point3(corner);
+            box_min = box_min.min(world_corner);
+            box_max = box_max.max(world_corner);
+        }
+        Self { box_min, box_max, ..*self }
+    }
+
+    // the box moved by transform as a shape, for boxes turned off the axes
+    pub(crate) fn turned(&self, transform: &Mat4) -> Shape {
+        Shape::cuboid(self.box_min, self.box_max, *transform, self.material_idx)
+            .with_gpu_medium_idx(self.medium_idx)
+    }
+
+    pub fn get_aabb(&self) -> (Vec3, Vec3) {
+        (self.box_min, self.box_max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::assert_layout;
+
+    #[test]
+    fn layout_matches_wgsl() {
+        assert_layout!(AABox, "AABox", box_min, material_idx, box_max, medium_idx);
+    }
+}
@@ -0,0 +1,165 @@
+use std::f32::consts::PI;
+use glam::Vec3;
+use crate::Camera;
+
+// a camera pose at a moment of an animation, with time in seconds
+#[derive(Copy, Clone, Debug)]
+pub struct CameraKeyframe {
+    pub time: f32,
+    pub position: Vec3,
+    pub look_at: Vec3,
+    // in degrees; a camera with a physical lens keeps its own field of view
+    pub vfov: f32,
+}
+
+impl CameraKeyframe {
+    pub fn new(time: f32, position: Vec3, look_at: Vec3, vfov: f32) -> Self {
+        Self { time, position, look_at, vfov }
+    }
+}
+
+// how the camera moves over time; everything else about it stays as it was set up
+#[derive(Clone, Debug)]
+pub enum CameraAnimation {
+    // a Catmull-Rom spline through the keyframes, which passes through each one and holds
+    // still before the first and after the last
+    Path(Vec<CameraKeyframe>),
+    // a circle around center at the given radius and height above it, looking at center,
+    // once every period seconds
+    Turntable { center: Vec3, radius: f32, height: f32, vfov: f32, period: f32 },
+}
+
+impl CameraAnimation {
+    // the keyframes are sorted by time
+    pub fn path(mut keyframes: Vec<CameraKeyframe>) -> Self {
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self::Path(keyframes)
+    }
+
+    pub fn turntable(center: Vec3, radius: f32, height: f32, vfov: f32, period: f32) -> Self {
+        Self::Turntable { center, radius, height, vfov, period }
+    }
+
+    // how long the animation takes to play through once, in seconds
+    pub fn duration(&self) -> f32 {
+        match self {
+            Self::Path(keyframes) => keyframes.last().map_or(0.0, |last| last.time),
+            Self::Turntable { period, .. } => *period,
+        }
+    }
+
+    // the pose at time t
+    pub fn keyframe_at(&self, t: f32) -> Option<CameraKeyframe> {
+        match self {
+            Self::Path(keyframes) => interpolate(keyframes, t),
+            Self::Turntable { center, radius, height, vfov, period } => {
+                let angle = 2.0 * PI * t / period.max(f32::MIN_POSITIVE);
+                let offset = Vec3::new(radius * angle.sin(), *height, radius * angle.cos());
+                Some(CameraKeyframe::new(t, *center + offset, *center, *vfov))
+            }
+        }
+    }
+
+    // moves the camera to where it is at time t
+    pub fn apply(&self, camera: &mut Camera, t: f32) {
+        if let Some(keyframe) = self.keyframe_at(t) {
+            camera.look_at(keyframe.position, keyframe.look_at);
+            camera.vfov = keyframe.vfov;
+        }
+    }
+}
+
+fn interpolate(keyframes: &[CameraKeyframe], t: f32) -> Option<CameraKeyframe> {
+    let first = keyframes.first()?;
+    let last = keyframes.last()?;
+    if t <= first.time {
+        return Some(CameraKeyframe { time: t, ..*first });
+    }
+    if t >= last.time {
+        return Some(CameraKeyframe { time: t, ..*last });
+    }
+
+    // the span [k0, k1] that t falls in, and its neighbours on either side
+    let i = keyframes.partition_point(|keyframe| keyframe.time <= t) - 1;
+    let k0 = &keyframes[i];
+    let k1 = &keyframes[i + 1];
+    let before = &keyframes[i.saturating_sub(1)];
+    let after = &keyframes[(i + 2).min(keyframes.len() - 1)];
+
+    let span = k1.time - k0.time;
+    if span <= 0.0 {
+        return Some(CameraKeyframe { time: t, ..*k1 });
+    }
+    let s = (t - k0.time) / span;
+
+    // cubic Hermite with Catmull-Rom tangents, scaled for uneven keyframe spacing
+    let (h00, h10, h01, h11) = (
+        2.0 * s * s * s - 3.0 * s * s + 1.0,
+        s * s * s - 2.0 * s * s + s,
+        -2.0 * s * s * s + 3.0 * s * s,
+        s * s * s - s * s,
+    );
+    let tangent = |a: &CameraKeyframe, b: &CameraKeyframe, value: fn(&CameraKeyframe) -> Vec3| {
+        let dt = b.time - a.time;
+        if dt > 0.0 { (value(b) - value(a)) * (span / dt) } else { Vec3::ZERO }
+    };
+    let spline = |value: fn(&CameraKeyframe) -> Vec3| {
+        h00 * value(k0) + h10 * tangent(before, k1, value) +
+            h01 * value(k1) + h11 * tangent(k0, after, value)
+    };
+
+    Some(CameraKeyframe {
+        time: t,
+        position: spline(|keyframe| keyframe.position),
+        look_at: spline(|keyframe| keyframe.look_at),
+        vfov: spline(|keyframe| Vec3::splat(keyframe.vfov)).x,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyframes() -> Vec<CameraKeyframe> {
+        vec![
+            CameraKeyframe::new(0.0, Vec3::new(0.0, 1.0, 5.0), Vec3::ZERO, 40.0),
+            CameraKeyframe::new(1.0, Vec3::new(4.0, 2.0, 3.0), Vec3::X, 30.0),
+            CameraKeyframe::new(3.0, Vec3::new(2.0, 0.5, -4.0), Vec3::Y, 50.0),
+            CameraKeyframe::new(4.0, Vec3::new(-3.0, 1.0, 0.0), Vec3::Z, 20.0),
+        ]
+    }
+
+    fn assert_pose(keyframe: CameraKeyframe, expected: &CameraKeyframe) {
+        assert!(keyframe.position.abs_diff_eq(expected.position, 1e-5), "{keyframe:?}");
+        assert!(keyframe.look_at.abs_diff_eq(expected.look_at, 1e-5), "{keyframe:?}");
+        assert!((keyframe.vfov - expected.vfov).abs() < 1e-4, "{keyframe:?}");
+    }
+
+    #[test]
+    fn path_passes_through_its_keyframes() {
+        let path = CameraAnimation::path(keyframes());
+        for keyframe in keyframes() {
+            assert_pose(path.keyframe_at(keyframe.time).unwrap(), &keyframe);
+        }
+        // and moves between them
+        let halfway = path.keyframe_at(2.0).unwrap();
+        assert!(!halfway.position.abs_diff_eq(keyframes()[1].position, 1e-3));
+    }
+
+    #[test]
+    fn path_holds_still_outside_its_keyframes() {
+        let path = CameraAnimation::path(keyframes());
+        let (first, last) = (keyframes()[0], keyframes()[3]);
+        assert_pose(path.keyframe_at(-2.0).unwrap(), &first);
+        assert_pose(path.keyframe_at(10.0).unwrap(), &last);
+        assert!(CameraAnimation::path(vec![]).keyframe_at(0.0).is_none());
+    }
+
+    #[test]
+    fn turntable_of_no_radius_gives_a_finite_camera() {
+        let turntable = CameraAnimation::turntable(Vec3::ZERO, 0.0, 3.0, 40.0, 8.0);
+        let mut camera = Camera::default();
+        turntable.apply(&mut camera, 1.0);
+        assert!(camera.forwards.is_finite() && camera.right.is_finite() && camera.up.is_finite());
+    }
+}
@@ -4,10 +4,14 @@ use winit::event::{ElementState, KeyEvent, WindowEvent};
 use winit::event_loop::{ActiveEventLoop};
 use winit::keyboard::{KeyCode, PhysicalKey};
 use winit::window::{Window, WindowId};
-use crate::{Camera, RayTracer};
+use crate::{Camera, PixelFilter, RayTracer, Sampler, SceneGenerator};
 use crate::gpu_timing::QueryResults;
 use crate::scene::Scene;
+use crate::raytracer::{required_limits, KERNEL_DEFINES};
+use crate::shader_preprocessor::preprocess;
+use crate::shader_watcher::{ShaderWatcher, SHADER_DIRECTORY};
 use crate::bvh::BVHTree;
+use crate::light_bvh::LightBVH;
 
 
 pub struct App<'a> {
@@ -18,15 +22,25 @@ pub struct App<'a> {
     render_parameters: RenderParameters,
     cursor_position: winit::dpi::PhysicalPosition<f64>,
     bvh_tree: BVHTree,
+    light_bvh: LightBVH,
+    // set in the shader dev mode
+    shader_watcher: Option<ShaderWatcher>,
 }
 
 impl Default for App<'_> {
     fn default() -> Self {
-        let mut scene = Scene::book_one_final();
-        // scene = Scene::new();
-        let camera = Camera::default();
-        let mut bvh_tree = BVHTree::new(scene.spheres.len());
-        bvh_tree.build_bvh_tree(&mut scene.spheres);
+        let generator = SceneGenerator::BookOneFinal;
+        Self::with_scene(generator.generate(0), generator.camera())
+    }
+}
+
+impl App<'_> {
+    pub fn with_scene(mut scene: Scene, camera: Camera) -> Self {
+        scene.update_lights();
+        let mut bvh_tree = BVHTree::new(scene.primitives());
+        bvh_tree.build_bvh_tree();
+        let mut light_bvh = LightBVH::new(&scene);
+        light_bvh.build_light_bvh();
         let render_parameters = RenderParameters {
             camera,
             sampling_parameters: SamplingParameters::default(),
@@ -38,7 +52,51 @@ impl Default for App<'_> {
             scene,
             render_parameters,
             cursor_position: winit::dpi::PhysicalPosition::default(),
-            bvh_tree
+            bvh_tree,
+            light_bvh,
+            shader_watcher: None,
+        }
+    }
+
+    // the seed the kernel samples with
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.render_parameters.sampling_parameters.seed = seed;
+        self
+    }
+
+    // the shader dev mode: the pipelines are rebuilt whenever a shader in src/shaders is saved,
+    // and a shader that doesn't compile leaves the last good ones running
+    pub fn with_shader_reloading(mut self) -> Self {
+        self.shader_watcher = Some(ShaderWatcher::new(SHADER_DIRECTORY));
+        self
+    }
+
+    fn reload_shaders(&mut self) {
+        let (Some(watcher), Some(renderer), Some(state)) =
+            (self.shader_watcher.as_ref(), self.renderer.as_mut(), self.wgpu_state.as_ref())
+        else {
+            return;
+        };
+        // the shaders are read from disk, includes and all
+        let sources = preprocess("raytracer_kernel.wgsl", KERNEL_DEFINES, |name| watcher.read(name))
+            .and_then(|kernel| {
+                Ok((kernel, preprocess("screen_shader.wgsl", &[], |name| watcher.read(name))?))
+            });
+        let (kernel, display) = match sources {
+            Ok(sources) => sources,
+            Err(err) => {
+                eprintln!("couldn't read the shaders: {err}");
+                return;
+            }
+        };
+        match renderer.reload_shaders(&state.device, &kernel, &display) {
+            Ok(()) => {
+                println!("reloaded the shaders");
+                if let Some(window) = &self.window {
+                    window.request_redraw();
+                }
+            }
+            Err(err) => eprintln!("the shaders failed to compile; keeping the last ones\n{err}"),
         }
     }
 }
@@ -78,19 +136,22 @@ impl ApplicationHandler for App<'_> {
                 self.renderer = RayTracer::new(
                     &state.device,
                     &state.queue,
-                    &state.surface_config,
+                    state.surface_config.format,
                     &self.render_parameters,
                     &self.scene,
                     self.render_parameters.viewport,
                     &self.bvh_tree,
+                    &self.light_bvh,
                 );
             }
         }
     }
 
-    // fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
-    //     self.window.as_ref().unwrap().request_redraw();
-    // }
+    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
+        if self.shader_watcher.as_mut().is_some_and(|watcher| watcher.poll()) {
+            self.reload_shaders();
+        }
+    }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, 
                     window_id: WindowId, event: WindowEvent) {
@@ -128,9 +189,18 @@ impl ApplicationHandler for App<'_> {
                     self.cursor_position = position;
                 }
                 WindowEvent::MouseInput { state, ..
-                } => {
-                    if state.is_pressed() {
-                        println!("cursor position {:?}", self.cursor_position);
+                } if state.is_pressed() => {
+                    println!("cursor position {:?}", self.cursor_position);
+                    // autofocus on whatever was clicked
+                    if let (Some(renderer), Some(state)) =
+                        (self.renderer.as_ref(), self.wgpu_state.as_ref()) {
+                        let pixel = (self.cursor_position.x as u32, self.cursor_position.y as u32);
+                        if let Some(distance) =
+                            renderer.pick_focus_distance(&state.device, &state.queue, pixel) {
+                            self.render_parameters.camera.focus_distance = distance;
+                            renderer.update_camera(&state.queue, &self.render_parameters);
+                            window.request_redraw();
+                        }
                     }
                 }
 
@@ -210,10 +280,7 @@ impl<'a> WgpuState<'a> {
         let (device, queue) = adapter.request_device(
             &wgpu::DeviceDescriptor {
                 required_features: features, // wgpu::Features::empty(),
-                required_limits: wgpu::Limits {
-                    max_storage_buffer_binding_size: 512_u32 << 20,
-                    ..Default::default()
-                },
+                required_limits: required_limits(),
                 label: None,
                 memory_hints: Default::default(),
             },
@@ -249,17 +316,55 @@ impl<'a> WgpuState<'a> {
     }
 }
 
+// how next event estimation picks the light to sample
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LightSampling {
+    Uniform = 0,
+    // in proportion to power, from an alias table
+    Power = 1,
+    // down the light BVH, favouring bright lights close to the point being shaded
+    LightBVH = 2,
+}
+
+#[derive(Clone, Debug)]
 pub struct SamplingParameters {
     pub samples_per_pixel: u32,
     pub num_bounces: u32,
+    pub light_sampling: LightSampling,
+    pub sampler: Sampler,
+    // how samples are spread over and around a pixel and weighted
+    pub filter: PixelFilter,
+    // paths that have bounced this many times may be ended early by Russian roulette,
+    // with a chance that grows as their throughput drops
+    pub russian_roulette_depth: u32,
+    // the brightest a single sample may be, to keep fireflies out of the image at the cost
+    // of some energy; None leaves samples alone
+    pub max_sample_radiance: Option<f32>,
+    // every random number the kernel draws comes from this seed, so two renders with the same
+    // seed on the same adapter are identical; the scene generators take a seed of their own
+    pub seed: u64,
+    // the frame of a sequence being rendered, which hashes into the seed so every frame draws
+    // different numbers
+    pub frame: u32,
 }
 
 impl Default for SamplingParameters {
     fn default() -> Self {
-        Self { samples_per_pixel: 100_u32, num_bounces: 50_u32 }
+        Self {
+            samples_per_pixel: 100_u32,
+            num_bounces: 50_u32,
+            light_sampling: LightSampling::LightBVH,
+            sampler: Sampler::Sobol,
+            filter: PixelFilter::default(),
+            russian_roulette_depth: 3_u32,
+            max_sample_radiance: None,
+            seed: 0,
+            frame: 0,
+        }
     }
 }
 
+#[derive(Clone, Debug)]
 pub struct RenderParameters {
     pub camera: Camera,
     pub sampling_parameters: SamplingParameters,
@@ -1,8 +1,13 @@
-use crate::sphere::Sphere;
+use crate::primitive::Primitive;
 use glam::{Vec3};
 
 const BINS: usize = 4096;
 
+// how deep a leaf may sit below the root; the kernel keeps a node for each level it has passed
+// on its traversal stack, STACKSIZE in bvh.wgsl, which must be as large, so splitting stops
+// here and leaves a bigger leaf rather than nodes the kernel can't reach
+pub(crate) const MAX_DEPTH: usize = 64;
+
 pub struct Bin {
     aabb_min: Vec3,
     aabb_max: Vec3,
@@ -55,22 +60,22 @@ impl BVHNode {
         self.prim_count as f32 * area
     }
 
-    pub fn update_node_bounds(&mut self, spheres: &[Sphere]) {
+    pub fn update_node_bounds(&mut self, primitives: &[Primitive]) {
         let mut aabb_min = Vec3::INFINITY;
         let mut aabb_max = Vec3::NEG_INFINITY;
         //expand the aabb
         for i in 0 ..self.prim_count as usize { 
-            let (sph_min, sph_max) =
-                spheres[self.left_first as usize + i].get_aabb();
-            aabb_min = aabb_min.min(sph_min);
-            aabb_max = aabb_max.max(sph_max);
+            let (prim_min, prim_max) =
+                primitives[self.left_first as usize + i].get_aabb();
+            aabb_min = aabb_min.min(prim_min);
+            aabb_max = aabb_max.max(prim_max);
         }
         self.aabb_min = aabb_min;
         self.aabb_max = aabb_max;
     }
 
     // this function will return a tuple with (splitCost, bestAxis, planeValue)
-    pub fn find_best_split_plane(&self, spheres: &[Sphere])
+    pub fn find_best_split_plane(&self, primitives: &[Primitive])
                                  -> (f32, usize, f32) {
 
         let extent = self.aabb_max - self.aabb_min;
@@ -94,9 +99,9 @@ impl BVHNode {
             // for each axis, populate the bins
             for i in 0..self.prim_count as usize {
                 let bin_idx = (BINS - 1).min(
-                    ((spheres[i + start_idx].center[axes] - min_bound) * scale) as usize);
+                    ((primitives[i + start_idx].centroid()[axes] - min_bound) * scale) as usize);
                 let (aabb_min, aabb_max) =
-                    spheres[i + start_idx].get_aabb();
+                    primitives[i + start_idx].get_aabb();
                 bins[bin_idx].expand_bin(aabb_min, aabb_max);
             }
 
@@ -142,31 +147,62 @@ impl BVHNode {
 
 pub struct BVHTree {
     pub nodes: Vec<BVHNode>,
+    pub primitives: Vec<Primitive>,
 }
 
 impl BVHTree {
-    pub fn new(num_primitives: usize) -> Self {
-        Self { nodes: Vec::<BVHNode>::with_capacity(2 * num_primitives) }
+    pub fn new(primitives: Vec<Primitive>) -> Self {
+        Self {
+            nodes: Vec::<BVHNode>::with_capacity(2 * primitives.len()),
+            primitives
+        }
+    }
+
+    pub fn build_bvh_tree(&mut self) {
+        self.build_to_depth(MAX_DEPTH);
     }
 
-    pub fn build_bvh_tree(&mut self, spheres: &mut [Sphere]) {
-        let prim_count = spheres.len() as u32;
-        let mut node = BVHNode::default();
-        node.left_first = 0;
-        node.prim_count = prim_count;
-        node.update_node_bounds(spheres);
+    fn build_to_depth(&mut self, max_depth: usize) {
+        let prim_count = self.primitives.len() as u32;
+        let mut node = BVHNode {
+            left_first: 0,
+            prim_count,
+            ..Default::default()
+        };
+        node.update_node_bounds(&self.primitives);
         self.nodes.push(node);
 
         // push an empty node at index 1 as a placeholder that will never be used
         self.nodes.push(BVHNode::default());
 
-        self.subdivide(0, spheres);
+        // an empty scene leaves the root as a node with no primitives and no children,
+        // which the kernel recognizes (left_first == 0) and skips
+        if prim_count > 0 {
+            self.subdivide(0, max_depth);
+        }
         println!("finished bvh_tree");
     }
 
-    fn subdivide(&mut self, index: usize, spheres: &mut [Sphere]) {
+    // the number of levels below the root down to the deepest leaf
+    pub fn depth(&self) -> usize {
+        fn below(nodes: &[BVHNode], index: usize) -> usize {
+            let node = &nodes[index];
+            if node.prim_count > 0 || node.left_first == 0 {
+                return 0;
+            }
+            let left = node.left_first as usize;
+            1 + below(nodes, left).max(below(nodes, left + 1))
+        }
+        below(&self.nodes, 0)
+    }
+
+    // levels_left is how many more levels may be split below the node
+    fn subdivide(&mut self, index: usize, levels_left: usize) {
+        if levels_left == 0 {
+            return;
+        }
         let (split_cost, best_axis, plane_val) =
-            self.nodes[index].find_best_split_plane(spheres);
+            self.nodes[index].find_best_split_plane(&self.primitives);
         let cost = self.nodes[index].find_node_cost();
 
         if cost <= split_cost {
@@ -174,14 +210,14 @@ impl BVHTree {
         }
 
         let mut i = self.nodes[index].left_first as usize;
-        let mut j = i + self.nodes[index].prim_count as usize - 1;
+        let mut j = i + self.nodes[index].prim_count as usize;
 
-        while i <= j {
-            if spheres[i].center[best_axis] < plane_val {
+        while i < j {
+            if self.primitives[i].centroid()[best_axis] < plane_val {
                 i += 1;
             } else {
-                spheres.swap(i,j);
                 j -= 1;
+                self.primitives.swap(i,j);
             }
         }
         let left_count = i as u32 - self.nodes[index].left_first;
@@ -190,15 +226,19 @@ impl BVHTree {
         }
 
         let node_idx = self.nodes.len();
-        let mut left_node = BVHNode::default();
-        left_node.left_first = self.nodes[index].left_first;
-        left_node.prim_count = left_count;
-        left_node.update_node_bounds(spheres);
-
-        let mut right_node = BVHNode::default();
-        right_node.left_first = i as u32;
-        right_node.prim_count = self.nodes[index].prim_count - left_count;
-        right_node.update_node_bounds(spheres);
+        let mut left_node = BVHNode {
+            left_first: self.nodes[index].left_first,
+            prim_count: left_count,
+            ..Default::default()
+        };
+        left_node.update_node_bounds(&self.primitives);
+
+        let mut right_node = BVHNode {
+            left_first: i as u32,
+            prim_count: self.nodes[index].prim_count - left_count,
+            ..Default::default()
+        };
+        right_node.update_node_bounds(&self.primitives);
 
         self.nodes[index].left_first = node_idx as u32;
         self.nodes[index].prim_count = 0;
@@ -206,7 +246,130 @@ impl BVHTree {
         self.nodes.push(left_node);
         self.nodes.push(right_node);
 
-        self.subdivide(node_idx, spheres);
-        self.subdivide(node_idx + 1, spheres);
+        self.subdivide(node_idx, levels_left - 1);
+        self.subdivide(node_idx + 1, levels_left - 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::assert_layout;
+    use crate::primitive::PrimitiveType;
+    use crate::SceneGenerator;
+
+    #[test]
+    fn layout_matches_wgsl() {
+        assert_layout!(BVHNode, "BVHNode", aabb_min, left_first, aabb_max, prim_count);
+    }
+
+    fn spheres_along_x(centers: &[f32]) -> Vec<Primitive> {
+        centers.iter().enumerate()
+            .map(|(idx, &x)| {
+                let center = Vec3::new(x, 0.0, 0.0);
+                Primitive::new(PrimitiveType::Sphere, idx, (center - 0.5, center + 0.5))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn split_with_nothing_on_the_left_leaves_a_leaf() {
+        // the best split plane rounds onto the first sphere's center: binning counts that
+        // sphere on the left, but the partition sends it right along with the others, which
+        // used to underflow the partition's end index as the node starts at primitive 0
+        let mut bvh_tree = BVHTree::new(spheres_along_x(&[-3.3000002, -2.2, -1.1]));
+        bvh_tree.build_bvh_tree();
+
+        let mut prim_idxs: Vec<u32> = bvh_tree.nodes.iter()
+            .filter(|node| node.prim_count > 0)
+            .flat_map(|node| node.left_first..node.left_first + node.prim_count)
+            .collect();
+        prim_idxs.sort();
+        assert_eq!(prim_idxs, [0, 1, 2]);
+    }
+
+    // the slab test of hit_bvh_node in bvh.wgsl
+    fn hit_node(node: &BVHNode, origin: Vec3, inv_direction: Vec3, nearest_hit: f32) -> f32 {
+        let t0 = (node.aabb_min - origin) * inv_direction;
+        let t1 = (node.aabb_max - origin) * inv_direction;
+        let tmin = t0.min(t1).max_element();
+        let tmax = t0.max(t1).min_element();
+        if tmin > tmax || tmax <= 0.0 || tmin > nearest_hit { 1e30 } else { tmin }
+    }
+
+    // walks the tree in the order traverseScene does, for a ray that hits no primitive, which
+    // is where the most nodes are left pending, and gives the most it had on its stack at once
+    fn most_pending(tree: &BVHTree, origin: Vec3, direction: Vec3) -> usize {
+        let inv_direction = direction.recip();
+        let nearest_hit = 1e30;
+        let mut stack = vec![];
+        let mut most = 0;
+        let mut node = tree.nodes[0];
+        loop {
+            let mut next = None;
+            if node.prim_count == 0 {
+                let (mut left, mut right) = (node.left_first as usize,
+                                             node.left_first as usize + 1);
+                let mut t_left = hit_node(&tree.nodes[left], origin, inv_direction, nearest_hit);
+                let mut t_right = hit_node(&tree.nodes[right], origin, inv_direction, nearest_hit);
+                if t_left > t_right {
+                    (t_left, t_right) = (t_right, t_left);
+                    (left, right) = (right, left);
+                }
+                if t_left <= nearest_hit {
+                    next = Some(left);
+                    if t_right < nearest_hit {
+                        stack.push(right);
+                        most = most.max(stack.len());
+                    }
+                }
+            }
+            match next.or_else(|| stack.pop()) {
+                Some(idx) => node = tree.nodes[idx],
+                None => return most,
+            }
+        }
+    }
+
+    #[test]
+    fn a_ray_along_a_deep_tree_stays_within_the_kernel_stack() {
+        // a row of spheres seen end on, by a ray that passes their corners: it enters both
+        // children at every level, so it leaves a node pending for each level it goes down,
+        // more than the ten the stack used to hold
+        let centers: Vec<f32> = (0..4096).map(|x| x as f32).collect();
+        let mut tree = BVHTree::new(spheres_along_x(&centers));
+        tree.build_bvh_tree();
+
+        let most = most_pending(&tree, Vec3::new(-2.0, 0.45, 0.45), Vec3::X);
+        let stack_size = crate::layout::wgsl_u32_constant("STACKSIZE") as usize;
+        assert!(most > 10, "the ray only left {most} nodes pending");
+        assert!(most <= stack_size, "the ray left {most} nodes pending, but the kernel's stack \
+                                     holds {stack_size}");
+    }
+
+    #[test]
+    fn kernel_stack_holds_the_deepest_tree() {
+        let stack_size = crate::layout::wgsl_u32_constant("STACKSIZE") as usize;
+        assert!(stack_size >= MAX_DEPTH,
+                "STACKSIZE is {stack_size} in bvh.wgsl but trees are built {MAX_DEPTH} deep");
+    }
+
+    #[test]
+    fn building_stops_at_the_depth_limit() {
+        let scene = SceneGenerator::from_name("many_spheres:2000").unwrap().generate(1);
+        let mut full = BVHTree::new(scene.primitives());
+        full.build_bvh_tree();
+        let mut capped = BVHTree::new(scene.primitives());
+        capped.build_to_depth(4);
+        assert!(full.depth() > 4, "the full tree is only {} deep", full.depth());
+        assert_eq!(capped.depth(), 4);
+
+        // every primitive still ends up in exactly one leaf
+        let mut prim_idxs: Vec<u32> = capped.nodes.iter()
+            .filter(|node| node.prim_count > 0)
+            .flat_map(|node| node.left_first..node.left_first + node.prim_count)
+            .collect();
+        prim_idxs.sort();
+        assert!(prim_idxs.iter().copied().eq(0..capped.primitives.len() as u32));
     }
 }
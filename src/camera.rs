@@ -1,5 +1,99 @@
-use glam::{Vec3};
+use glam::{Vec2, Vec3};
 
+// f/8 at 1/125 s and ISO 100 leaves the rendered radiance as it is; other settings
+// brighten or darken the image in proportion to the light they would let in
+const EXPOSURE_CALIBRATION: f32 = 8.0 * 8.0 * 125.0;
+
+// the shape of the lens opening, which out of focus highlights take on
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Aperture {
+    Circular,
+    // a regular polygon inscribed in the circle, as formed by the blades of an iris;
+    // rotation is in degrees
+    Polygonal { blades: u32, rotation: f32 },
+}
+
+// how directions from the camera are laid out over the image
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    // the pinhole or thin lens camera, with vfov or the lens deciding the field of view
+    Perspective,
+    // parallel rays along forwards from a view this many scene units high
+    Orthographic { height: f32 },
+    // equidistant: the angle from forwards grows with the distance from the center of the
+    // image, out to fov / 2 at the top and bottom edges; fov is in degrees and may exceed 180
+    Fisheye { fov: f32 },
+    // a 360 panorama, longitude across the image and latitude down it, centered on
+    // forwards; a 2:1 image covers the sphere without stretching
+    Equirectangular,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+// how the two eyes are put together in one image
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StereoLayout {
+    // left eye on the left
+    SideBySide,
+    // left eye on top
+    OverUnder,
+}
+
+// a pair of eyes either side of the camera position; every ray from one eye meets its twin
+// from the other at the convergence distance, so that is where things appear at the depth of
+// the screen. an equirectangular camera turns the eyes with each longitude for 360 stereo
+#[derive(Copy, Clone, Debug)]
+pub struct Stereo {
+    // the distance between the eyes, in scene units
+    pub interocular_distance: f32,
+    // infinite for parallel eyes
+    pub convergence_distance: f32,
+    pub layout: StereoLayout,
+}
+
+impl Default for Stereo {
+    fn default() -> Self {
+        Self {
+            interocular_distance: 0.064,
+            convergence_distance: f32::INFINITY,
+            layout: StereoLayout::SideBySide,
+        }
+    }
+}
+
+// a camera set up the way a photographer would set one up; scene units are taken to be
+// metres. when a camera has one, it decides the field of view, depth of field and exposure
+// in place of vfov and defocus_angle
+#[derive(Copy, Clone, Debug)]
+pub struct PhysicalLens {
+    // in millimetres
+    pub focal_length: f32,
+    // width and height in millimetres; the image is fit to the sensor's height
+    pub sensor_size: Vec2,
+    pub f_stop: f32,
+    // in seconds; motion blur still follows shutter_open and shutter_close
+    pub shutter_speed: f32,
+    pub iso: f32,
+}
+
+impl Default for PhysicalLens {
+    fn default() -> Self {
+        // a 50mm lens on a full frame sensor
+        Self {
+            focal_length: 50.0,
+            sensor_size: Vec2::new(36.0, 24.0),
+            f_stop: 8.0,
+            shutter_speed: 1.0 / 125.0,
+            iso: 100.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Camera {
     pub position: Vec3,
     pub forwards: Vec3,
@@ -8,15 +102,25 @@ pub struct Camera {
     pub vfov: f32,
     pub defocus_angle: f32,
     pub focus_distance: f32,
+    // rays are spread uniformly over [shutter_open, shutter_close]; moving objects
+    // are at their start position at time 0 and their end position at time 1
+    pub shutter_open: f32,
+    pub shutter_close: f32,
+    pub lens: Option<PhysicalLens>,
+    pub aperture: Aperture,
+    // only perspective cameras have depth of field
+    pub projection: Projection,
+    // renders both eyes into one image when set
+    pub stereo: Option<Stereo>,
 }
 
 impl Default for Camera {
     fn default() -> Self {
-        let lookAt = Vec3::new(0.0, 0.0, 0.0);
-        let lookFrom = Vec3::new(13.0, 2.0, 3.0);
-        // let lookAt = Vec3::new(0.0, 0.0, -1.0);
-        // let lookFrom = Vec3::new(-2.0, 2.0, 1.0);
-        let forwards = (lookAt - lookFrom).normalize();
+        let look_at = Vec3::new(0.0, 0.0, 0.0);
+        let look_from = Vec3::new(13.0, 2.0, 3.0);
+        // let look_at = Vec3::new(0.0, 0.0, -1.0);
+        // let look_from = Vec3::new(-2.0, 2.0, 1.0);
+        let forwards = (look_at - look_from).normalize();
         let right = forwards.cross(Vec3::new(0.0, 1.0, 0.0)).normalize();
         let up = right.cross(forwards);
         let vfov = 20.0f32;
@@ -26,13 +130,86 @@ impl Default for Camera {
         // let focus_distance = 3.4_f32;
 
         Self {
-            position: lookFrom,
+            position: look_from,
             forwards,
             right,
             up,
             vfov,
             defocus_angle,
-            focus_distance
+            focus_distance,
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+            lens: None,
+            aperture: Aperture::Circular,
+            projection: Projection::Perspective,
+            stereo: None,
+        }
+    }
+}
+
+impl Camera {
+    // points the camera at target from position, keeping it level. looking straight up or down
+    // there is no level, so the top of the image faces +z instead; a target at the camera's
+    // own position leaves it facing the way it was
+    pub fn look_at(&mut self, position: Vec3, target: Vec3) {
+        self.position = position;
+        self.forwards = (target - position).try_normalize().unwrap_or(self.forwards);
+        let world_up = if self.forwards.y.abs() > 0.999 { Vec3::Z } else { Vec3::Y };
+        self.right = self.forwards.cross(world_up).normalize();
+        self.up = self.right.cross(self.forwards);
+    }
+
+    pub fn with_lens(mut self, lens: PhysicalLens) -> Self {
+        self.lens = Some(lens);
+        self
+    }
+
+    pub fn with_aperture(mut self, aperture: Aperture) -> Self {
+        self.aperture = aperture;
+        self
+    }
+
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    pub fn with_stereo(mut self, stereo: Stereo) -> Self {
+        self.stereo = Some(stereo);
+        self
+    }
+
+    // in degrees
+    pub fn vertical_fov(&self) -> f32 {
+        match self.lens {
+            Some(lens) => {
+                // a thin lens focused at focus_distance sits this far from the sensor, so
+                // focusing closer narrows the view a little
+                let focal_length = 0.001 * lens.focal_length;
+                let focus_distance = self.focus_distance.max(1.001 * focal_length);
+                let image_distance = focal_length * focus_distance / (focus_distance - focal_length);
+                2.0 * (0.0005 * lens.sensor_size.y / image_distance).atan().to_degrees()
+            }
+            None => self.vfov,
+        }
+    }
+
+    // the radius of the lens opening that rays start from, in scene units
+    pub fn defocus_radius(&self) -> f32 {
+        match self.lens {
+            Some(lens) => 0.0005 * lens.focal_length / lens.f_stop.max(0.5),
+            None => self.focus_distance * (0.5 * self.defocus_angle).to_radians().tan(),
+        }
+    }
+
+    // what the rendered radiance is scaled by
+    pub fn exposure(&self) -> f32 {
+        match self.lens {
+            Some(lens) => {
+                let f_stop = lens.f_stop.max(0.5);
+                EXPOSURE_CALIBRATION * lens.shutter_speed * (lens.iso / 100.0) / (f_stop * f_stop)
+            }
+            None => 1.0,
         }
     }
 }
@@ -45,4 +222,43 @@ impl Default for Camera {
 //     pub fn get_camera() -> Camera {
 //
 //     }
-// }
\ No newline at end of file
+// }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_orthonormal(camera: &Camera) {
+        for axis in [camera.forwards, camera.right, camera.up] {
+            assert!(axis.is_finite() && (axis.length() - 1.0).abs() < 1e-5, "{camera:?}");
+        }
+        assert!(camera.forwards.dot(camera.right).abs() < 1e-5, "{camera:?}");
+        assert!(camera.forwards.dot(camera.up).abs() < 1e-5, "{camera:?}");
+    }
+
+    #[test]
+    fn look_at_keeps_the_camera_level() {
+        let mut camera = Camera::default();
+        camera.look_at(Vec3::new(3.0, 2.0, 1.0), Vec3::ZERO);
+        assert_orthonormal(&camera);
+        assert!(camera.right.y.abs() < 1e-6);
+        assert!(camera.up.y > 0.0);
+    }
+
+    #[test]
+    fn look_at_straight_up_or_down_or_at_itself_stays_finite() {
+        let mut camera = Camera::default();
+        camera.look_at(Vec3::new(0.0, 5.0, 0.0), Vec3::ZERO);
+        assert_orthonormal(&camera);
+        assert_eq!(camera.forwards, Vec3::NEG_Y);
+
+        camera.look_at(Vec3::ZERO, Vec3::new(0.0, 5.0, 0.0));
+        assert_orthonormal(&camera);
+        assert_eq!(camera.forwards, Vec3::Y);
+
+        camera.look_at(Vec3::ONE, Vec3::ONE);
+        assert_orthonormal(&camera);
+        assert_eq!(camera.forwards, Vec3::Y);
+        assert_eq!(camera.position, Vec3::ONE);
+    }
+}
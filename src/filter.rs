@@ -0,0 +1,109 @@
+use std::f32::consts::PI;
+
+// the kernel reconstructs pixels by filter importance sampling: each sample lands at an offset
+// from the pixel center drawn in proportion to |f| and carries the weight f / pdf, and a pixel
+// is the weighted average of its samples
+//
+// all the filters are separable, so x and y are drawn the same way from a table of the
+// one-dimensional |f| over [0, radius]; the kernel evaluates f itself to get the weight
+
+// filter_type will be indexed as follows:
+// 0 Box; 1 Tent; 2 Gaussian; 3 BlackmanHarris; 4 Mitchell
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FilterType {
+    Box = 0,
+    Tent = 1,
+    // with a standard deviation of a third of the radius, shifted down to reach zero there
+    Gaussian = 2,
+    BlackmanHarris = 3,
+    // B = C = 1/3; its negative lobes give some samples negative weights
+    Mitchell = 4,
+}
+
+// the number of bins in the sampling table
+pub const FILTER_TABLE_SIZE: usize = 64;
+
+#[derive(Copy, Clone, Debug)]
+pub struct PixelFilter {
+    pub filter_type: FilterType,
+    // in pixels
+    pub radius: f32,
+}
+
+impl Default for PixelFilter {
+    fn default() -> Self {
+        Self {
+            filter_type: FilterType::Box,
+            radius: 0.5,
+        }
+    }
+}
+
+impl PixelFilter {
+    pub fn new(filter_type: FilterType, radius: f32) -> Self {
+        Self { filter_type, radius: radius.max(1e-3) }
+    }
+
+    // the filter along one axis, x in pixels from the center
+    pub fn evaluate(&self, x: f32) -> f32 {
+        let r = self.radius;
+        let x = x.abs();
+        if x > r {
+            return 0.0;
+        }
+        match self.filter_type {
+            FilterType::Box => 1.0,
+            FilterType::Tent => r - x,
+            FilterType::Gaussian => {
+                let sigma = r / 3.0;
+                let gaussian = |x: f32| (-x * x / (2.0 * sigma * sigma)).exp();
+                (gaussian(x) - gaussian(r)).max(0.0)
+            }
+            FilterType::BlackmanHarris => {
+                let n = 0.5 + 0.5 * x / r;
+                0.35875 - 0.48829 * (2.0 * PI * n).cos() + 0.14128 * (4.0 * PI * n).cos()
+                    - 0.01168 * (6.0 * PI * n).cos()
+            }
+            FilterType::Mitchell => mitchell(2.0 * x / r),
+        }
+    }
+
+    // the cumulative distribution of |f| over [0, radius], FILTER_TABLE_SIZE + 1 entries
+    // from 0 to 1, each bin taken as constant
+    pub fn sampling_table(&self) -> Vec<f32> {
+        let bin_width = self.radius / FILTER_TABLE_SIZE as f32;
+        // the average of |f| over each bin, from a few points inside it
+        let bins: Vec<f32> = (0..FILTER_TABLE_SIZE)
+            .map(|bin| {
+                (0..8)
+                    .map(|k| self.evaluate((bin as f32 + (k as f32 + 0.5) / 8.0) * bin_width).abs())
+                    .sum::<f32>() / 8.0
+            })
+            .collect();
+        let total: f32 = bins.iter().sum();
+
+        let mut table = Vec::<f32>::with_capacity(FILTER_TABLE_SIZE + 1);
+        table.push(0.0);
+        let mut cumulative = 0.0;
+        for bin in bins {
+            cumulative += if total > 0.0 { bin / total } else { 1.0 / FILTER_TABLE_SIZE as f32 };
+            table.push(cumulative);
+        }
+        table[FILTER_TABLE_SIZE] = 1.0;
+        table
+    }
+}
+
+// the Mitchell-Netravali cubic with B = C = 1/3 over [0, 2]
+fn mitchell(x: f32) -> f32 {
+    let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+    if x < 1.0 {
+        ((12.0 - 9.0 * b - 6.0 * c) * x * x * x + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+            + (6.0 - 2.0 * b)) / 6.0
+    } else if x <= 2.0 {
+        ((-b - 6.0 * c) * x * x * x + (6.0 * b + 30.0 * c) * x * x
+            + (-12.0 * b - 48.0 * c) * x + (8.0 * b + 24.0 * c)) / 6.0
+    } else {
+        0.0
+    }
+}
@@ -0,0 +1,401 @@
+use std::collections::HashMap;
+use std::path::Path;
+use glam::{Mat4, Quat, Vec2, Vec3};
+use gltf::khr_lights_punctual::Kind;
+use gltf::mesh::Mode;
+use gltf::material::AlphaMode;
+use image::RgbaImage;
+use crate::{Camera, Light, Material, Projection, Scene, SceneGraph, SceneObject, Texture,
+            Transform, Triangle};
+use crate::scene_graph::NodeId;
+
+// imports a glTF or GLB file into a scene: the meshes of the default scene become triangles,
+// placed by the node hierarchy and tinted by their vertex colors, its metallic-roughness
+// materials become principled ones, and KHR_lights_punctual lights become point, spot and
+// sun lights. light intensities are taken as they are, in the renderer's units
+//
+// whatever has no counterpart here is left out or approximated, with a warning for each:
+// normal maps, alpha blending and masking, texture transforms, secondary uv sets, skins and
+// morph targets among others. occlusion maps are dropped silently, since the path tracer
+// finds occlusion by itself
+pub struct GltfImport {
+    // the node hierarchy, already flattened into the scene; nodes keep the file's names
+    pub graph: SceneGraph,
+    // the file's cameras, placed by their nodes, in the order they appear in the hierarchy
+    pub cameras: Vec<Camera>,
+    pub warnings: Vec<String>,
+}
+
+// the extensions the importer reads; those it only approximates warn where they are used,
+// and anything else the file uses gets a warning of its own
+const KNOWN_EXTENSIONS: [&str; 9] = [
+    "KHR_lights_punctual",
+    "KHR_materials_emissive_strength",
+    "KHR_materials_ior",
+    "KHR_materials_transmission",
+    "KHR_materials_specular",
+    "KHR_materials_unlit",
+    "KHR_materials_pbrSpecularGlossiness",
+    "KHR_materials_volume",
+    "KHR_texture_transform",
+];
+
+pub fn import_gltf(path: &Path, scene: &mut Scene) -> gltf::Result<GltfImport> {
+    let (document, buffers, images) = gltf::import(path)?;
+    let mut importer = Importer {
+        buffers: &buffers,
+        images: &images,
+        scene,
+        graph: SceneGraph::new(),
+        cameras: vec![],
+        warnings: vec![],
+        first_material: 0,
+        default_material: None,
+        image_layers: HashMap::new(),
+        textures: HashMap::new(),
+    };
+
+    for extension in document.extensions_used() {
+        if !KNOWN_EXTENSIONS.contains(&extension) {
+            importer.warn(format!("extension {extension} is not supported and was ignored"));
+        }
+    }
+
+    importer.first_material = importer.scene.materials.len() as u32;
+    for material in document.materials() {
+        let material = importer.material(&material);
+        importer.scene.materials.push(material);
+    }
+
+    match document.default_scene().or_else(|| document.scenes().next()) {
+        Some(gltf_scene) => {
+            let root = importer.graph.root();
+            for node in gltf_scene.nodes() {
+                importer.node(&node, root);
+            }
+        }
+        None => importer.warn("the file has no scene".to_string()),
+    }
+
+    let Importer { graph, cameras, warnings, .. } = importer;
+    graph.flatten(scene);
+    Ok(GltfImport { graph, cameras, warnings })
+}
+
+struct Importer<'a> {
+    buffers: &'a [gltf::buffer::Data],
+    images: &'a [gltf::image::Data],
+    scene: &'a mut Scene,
+    graph: SceneGraph,
+    cameras: Vec<Camera>,
+    warnings: Vec<String>,
+    // where the file's materials start in the scene's
+    first_material: u32,
+    // the glTF default material, added the first time a primitive has none
+    default_material: Option<u32>,
+    // from glTF image index to image array layer, and from (image, srgb) to scene texture
+    image_layers: HashMap<usize, Option<u32>>,
+    textures: HashMap<(usize, bool), Option<u32>>,
+}
+
+impl Importer<'_> {
+    fn warn(&mut self, warning: String) {
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+    }
+
+    fn node(&mut self, node: &gltf::Node, parent: NodeId) {
+        let (translation, rotation, scale) = node.transform().decomposed();
+        let transform = Transform {
+            translation: Vec3::from(translation),
+            rotation: Quat::from_array(rotation),
+            scale: Vec3::from(scale),
+        };
+        let name = node.name().map(str::to_string)
+            .unwrap_or_else(|| format!("node {}", node.index()));
+        let id = self.graph.add_node(parent, &name, transform);
+
+        if let Some(mesh) = node.mesh() {
+            if node.skin().is_some() {
+                self.warn(format!("node {name} is skinned; its mesh is imported in its bind pose"));
+            }
+            let triangles = self.mesh(&mesh);
+            self.graph.node_mut(id).objects
+                .extend(triangles.into_iter().map(SceneObject::Triangle));
+        }
+        let world_transform = self.graph.world_transform(id);
+        if let Some(camera) = node.camera() {
+            self.cameras.push(camera_at(&camera, &world_transform));
+        }
+        if let Some(light) = node.light() {
+            let light = self.light(&light, &world_transform);
+            self.scene.lights.push(light);
+        }
+
+        for child in node.children() {
+            self.node(&child, id);
+        }
+    }
+
+    fn mesh(&mut self, mesh: &gltf::Mesh) -> Vec<Triangle> {
+        let mesh_name = mesh.name().map(str::to_string)
+            .unwrap_or_else(|| format!("{}", mesh.index()));
+        let mut triangles = vec![];
+        for primitive in mesh.primitives() {
+            if primitive.morph_targets().len() > 0 {
+                self.warn(format!("mesh {mesh_name} has morph targets, which were ignored"));
+            }
+
+            let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+            let Some(positions) = reader.read_positions() else {
+                self.warn(format!("a primitive of mesh {mesh_name} has no positions"));
+                continue;
+            };
+            let positions: Vec<Vec3> = positions.map(Vec3::from).collect();
+            let normals: Option<Vec<Vec3>> = reader.read_normals()
+                .map(|normals| normals.map(Vec3::from).collect());
+            let colors: Option<Vec<Vec3>> = reader.read_colors(0)
+                .map(|colors| colors.into_rgb_f32().map(Vec3::from).collect());
+            // glTF puts v = 0 at the top of an image, the texture sampler at the bottom
+            let uvs: Option<Vec<Vec2>> = reader.read_tex_coords(0)
+                .map(|uvs| uvs.into_f32().map(|[u, v]| Vec2::new(u, 1.0 - v)).collect());
+            let indices: Vec<u32> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..positions.len() as u32).collect(),
+            };
+            let Some(corners) = triangle_corners(primitive.mode(), &indices) else {
+                self.warn(format!("mesh {mesh_name} has points or lines, which were ignored"));
+                continue;
+            };
+
+            let material_idx = match primitive.material().index() {
+                Some(idx) => self.first_material + idx as u32,
+                None => self.default_material(),
+            };
+            for [i0, i1, i2] in corners {
+                let [i0, i1, i2] = [i0 as usize, i1 as usize, i2 as usize];
+                if [i0, i1, i2].iter().any(|&i| i >= positions.len()) {
+                    continue;
+                }
+                let mut triangle = Triangle::new(positions[i0], positions[i1], positions[i2],
+                                                 material_idx);
+                if let Some(normals) = &normals {
+                    triangle = triangle.with_normals(normals[i0], normals[i1], normals[i2]);
+                }
+                if let Some(uvs) = &uvs {
+                    triangle = triangle.with_uvs(uvs[i0], uvs[i1], uvs[i2]);
+                }
+                if let Some(colors) = &colors {
+                    triangle = triangle.with_colors(colors[i0], colors[i1], colors[i2]);
+                }
+                triangles.push(triangle);
+            }
+        }
+        triangles
+    }
+
+    fn default_material(&mut self) -> u32 {
+        *self.default_material.get_or_insert_with(|| {
+            self.scene.materials.push(Material::principled(Vec3::ONE, 1.0, 1.0));
+            (self.scene.materials.len() - 1) as u32
+        })
+    }
+
+    fn material(&mut self, material: &gltf::Material) -> Material {
+        let name = material.name().map(str::to_string)
+            .unwrap_or_else(|| format!("{}", material.index().unwrap_or(0)));
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, _] = pbr.base_color_factor();
+        let base_color = Vec3::new(r, g, b);
+        let emission = Vec3::from(material.emissive_factor()) *
+            material.emissive_strength().unwrap_or(1.0);
+
+        // an unlit material shows its base color whatever the lighting, which is closest to
+        // a surface giving off that color
+        if material.unlit() {
+            self.warn(format!("material {name} is unlit and was made emissive"));
+            return Material::emissive(base_color);
+        }
+        if material.pbr_specular_glossiness().is_some() {
+            self.warn(format!("material {name} uses specular-glossiness; its metallic-roughness \
+                               fallback was used"));
+        }
+
+        let mut imported = Material::principled(base_color, pbr.metallic_factor(),
+                                                pbr.roughness_factor())
+            .with_emission(emission);
+        if let Some(info) = pbr.base_color_texture() {
+            if let Some(texture) = self.texture(&info, true, &name) {
+                imported = imported.with_albedo_texture(texture);
+            }
+        }
+        if let Some(info) = pbr.metallic_roughness_texture() {
+            if let Some(texture) = self.texture(&info, false, &name) {
+                imported = imported.with_roughness_texture(texture);
+            }
+        }
+        if let Some(info) = material.emissive_texture() {
+            if let Some(texture) = self.texture(&info, true, &name) {
+                imported = imported.with_emission_texture(texture);
+            }
+        }
+
+        let ior = material.ior().unwrap_or(1.5);
+        if let Some(transmission) = material.transmission() {
+            if transmission.transmission_texture().is_some() {
+                self.warn(format!("material {name} has a transmission texture, which was ignored"));
+            }
+            imported = imported.with_transmission(transmission.transmission_factor(), ior);
+        } else {
+            imported = imported.with_transmission(0.0, ior);
+        }
+        // specular 0.5 is the reflectance of ior 1.5, and it scales linearly with reflectance
+        let reflectance = ((ior - 1.0) / (ior + 1.0)).powi(2);
+        let specular_factor = material.specular()
+            .map_or(1.0, |specular| specular.specular_factor());
+        imported = imported.with_specular(specular_factor * reflectance / 0.08);
+
+        if material.normal_texture().is_some() {
+            self.warn(format!("material {name} has a normal map, which was ignored"));
+        }
+        if material.volume().is_some() {
+            self.warn(format!("material {name} has a volume, which was ignored"));
+        }
+        match material.alpha_mode() {
+            AlphaMode::Opaque => {}
+            AlphaMode::Mask | AlphaMode::Blend => {
+                self.warn(format!("material {name} uses alpha {:?}; it was made opaque",
+                                  material.alpha_mode()));
+            }
+        }
+        imported
+    }
+
+    // the scene texture sampling the image behind info, or None if the image can't be used
+    fn texture(&mut self, info: &gltf::texture::Info, srgb: bool,
+               material_name: &str) -> Option<u32> {
+        if info.tex_coord() != 0 {
+            self.warn(format!("material {material_name} uses a second uv set; the first was used"));
+        }
+        if info.texture_transform().is_some() {
+            self.warn(format!("material {material_name} transforms a texture, which was ignored"));
+        }
+        let image_idx = info.texture().source().index();
+        if let Some(texture) = self.textures.get(&(image_idx, srgb)) {
+            return *texture;
+        }
+        let texture = self.image_layer(image_idx).map(|layer| {
+            self.scene.textures.push(Texture::image(layer, srgb));
+            (self.scene.textures.len() - 1) as u32
+        });
+        self.textures.insert((image_idx, srgb), texture);
+        texture
+    }
+
+    fn image_layer(&mut self, image_idx: usize) -> Option<u32> {
+        if let Some(layer) = self.image_layers.get(&image_idx) {
+            return *layer;
+        }
+        let data = &self.images[image_idx];
+        let layer = match to_rgba(data) {
+            Some(image) => {
+                self.scene.images.push(image);
+                Some((self.scene.images.len() - 1) as u32)
+            }
+            None => {
+                self.warn(format!("image {image_idx} has pixel format {:?}, which is not \
+                                   supported; it was left out", data.format));
+                None
+            }
+        };
+        self.image_layers.insert(image_idx, layer);
+        layer
+    }
+
+    fn light(&mut self, light: &gltf::khr_lights_punctual::Light, world_transform: &Mat4) -> Light {
+        if light.range().is_some() {
+            self.warn("light ranges are not supported; lights fall off with the square of \
+                       distance only".to_string());
+        }
+        let intensity = Vec3::from(light.color()) * light.intensity();
+        // lights shine down their node's -z axis
+        let position = world_transform.transform_point3(Vec3::ZERO);
+        let direction = world_transform.transform_vector3(Vec3::NEG_Z).normalize();
+        match light.kind() {
+            Kind::Directional => Light::sun(direction, intensity, 0.0),
+            Kind::Point => Light::point(position, intensity),
+            Kind::Spot { inner_cone_angle, outer_cone_angle } =>
+                Light::spot(position, direction, intensity, inner_cone_angle.to_degrees(),
+                            outer_cone_angle.to_degrees()),
+        }
+    }
+}
+
+// the corners of each triangle in a primitive, or None for points and lines
+fn triangle_corners(mode: Mode, indices: &[u32]) -> Option<Vec<[u32; 3]>> {
+    let corners = match mode {
+        Mode::Triangles => indices.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect(),
+        // every other triangle of a strip is wound the other way round
+        Mode::TriangleStrip => indices.windows(3).enumerate()
+            .map(|(i, c)| if i % 2 == 0 { [c[0], c[1], c[2]] } else { [c[1], c[0], c[2]] })
+            .collect(),
+        Mode::TriangleFan => indices.windows(2).skip(1)
+            .map(|c| [indices[0], c[0], c[1]])
+            .collect(),
+        Mode::Points | Mode::Lines | Mode::LineLoop | Mode::LineStrip => return None,
+    };
+    Some(corners)
+}
+
+// glTF cameras look down their node's -z axis with +y up
+fn camera_at(camera: &gltf::Camera, world_transform: &Mat4) -> Camera {
+    let mut imported = Camera {
+        defocus_angle: 0.0,
+        ..Camera::default()
+    };
+    imported.position = world_transform.transform_point3(Vec3::ZERO);
+    imported.forwards = world_transform.transform_vector3(Vec3::NEG_Z).normalize();
+    let up = world_transform.transform_vector3(Vec3::Y);
+    imported.right = imported.forwards.cross(up).normalize();
+    imported.up = imported.right.cross(imported.forwards);
+    match camera.projection() {
+        gltf::camera::Projection::Perspective(perspective) => {
+            imported.vfov = perspective.yfov().to_degrees();
+        }
+        gltf::camera::Projection::Orthographic(orthographic) => {
+            imported.projection = Projection::Orthographic { height: 2.0 * orthographic.ymag() };
+        }
+    }
+    imported
+}
+
+// 8 bit images as they are, deeper ones brought down to 8 bits; one and two channel images
+// are grey, with alpha in the second channel, as decoded from PNGs
+fn to_rgba(data: &gltf::image::Data) -> Option<RgbaImage> {
+    use gltf::image::Format;
+    let (channels, bytes) = match data.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        _ => return None,
+    };
+    let mut rgba = Vec::with_capacity((data.width * data.height * 4) as usize);
+    for pixel in data.pixels.chunks_exact(channels * bytes) {
+        // the most significant byte of each little-endian channel
+        let channel = |c: usize| pixel[c * bytes + bytes - 1];
+        let texel = match channels {
+            1 => [channel(0), channel(0), channel(0), 255],
+            2 => [channel(0), channel(0), channel(0), channel(1)],
+            3 => [channel(0), channel(1), channel(2), 255],
+            _ => [channel(0), channel(1), channel(2), channel(3)],
+        };
+        rgba.extend_from_slice(&texel);
+    }
+    RgbaImage::from_raw(data.width, data.height, rgba)
+}
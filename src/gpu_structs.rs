@@ -1,6 +1,8 @@
-use glam::{Vec3, Vec4};
+use std::f32::consts::PI;
+use glam::{Vec2, Vec3, Vec4};
 use crate::app::SamplingParameters;
-use crate::Camera;
+use crate::{Aperture, Background, Camera, Eye, Projection};
+use crate::sampler::frame_seed;
 
 
 #[repr(C)]
@@ -10,32 +12,101 @@ pub struct GPUCamera {
     camera_forwards: Vec4,
     camera_right: Vec4,
     camera_up: Vec4,
+    // the center of the first pixel and the steps to the next one across and down: in the
+    // world for perspective and orthographic cameras, and as angles for fisheye and panorama
     pixel_00: Vec4,
     du: Vec4,
     dv: Vec4,
     defocus_radius: f32,
-    _buffer: [u32; 3]
+    shutter_open: f32,
+    shutter_close: f32,
+    exposure: f32,
+    // zero for a circular aperture
+    aperture_blades: u32,
+    // in radians
+    aperture_rotation: f32,
+    projection: u32,
+    // the fisheye's largest angle from forwards, in radians
+    max_angle: f32,
+    // how far along the eye axis this eye is from the camera position, negative to the left
+    eye_offset: f32,
+    // zero for parallel eyes
+    convergence_distance: f32,
+    _buffer: [u32; 2],
 }
 unsafe impl bytemuck::Pod for GPUCamera {}
 unsafe impl bytemuck::Zeroable for GPUCamera {}
 
 impl GPUCamera {
     pub fn new(camera: &Camera, image_size: (u32, u32)) -> GPUCamera {
-        let defocus_radius = camera.focus_distance * (0.5 * camera.defocus_angle).to_radians().tan();
-        let theta = camera.vfov.to_radians();
-        let h = (theta / 2.0).tan();
-        let viewport_height: f32 = 2.0 * h * camera.focus_distance;
-        let viewport_width: f32 = viewport_height * (image_size.0 as f32 / image_size.1 as f32);
+        Self::for_eye(camera, image_size, None)
+    }
+
+    // one eye of a stereo camera, or the camera itself for None
+    pub fn for_eye(camera: &Camera, image_size: (u32, u32), eye: Option<Eye>) -> GPUCamera {
+        let (eye_offset, convergence_distance) = match (camera.stereo, eye) {
+            (Some(stereo), Some(eye)) => {
+                let half = 0.5 * stereo.interocular_distance;
+                let convergence = if stereo.convergence_distance.is_finite() {
+                    stereo.convergence_distance.max(0.0)
+                } else {
+                    0.0
+                };
+                (if eye == Eye::Left { -half } else { half }, convergence)
+            }
+            _ => (0.0, 0.0),
+        };
+        let defocus_radius = camera.defocus_radius();
+        let (width, height) = (image_size.0 as f32, image_size.1 as f32);
+        let (projection, max_angle) = match camera.projection {
+            Projection::Perspective => (0, 0.0),
+            Projection::Orthographic { .. } => (1, 0.0),
+            Projection::Fisheye { fov } => (2, 0.5 * fov.to_radians()),
+            Projection::Equirectangular => (3, 0.0),
+        };
+
+        let (pixel_00, du, dv) = match camera.projection {
+            Projection::Perspective | Projection::Orthographic { .. } => {
+                // the viewport lies across the focus plane for a perspective camera and
+                // through the camera for an orthographic one
+                let (viewport_height, center) = match camera.projection {
+                    Projection::Orthographic { height } => (height, camera.position),
+                    _ => {
+                        let theta = camera.vertical_fov().to_radians();
+                        let h = (theta / 2.0).tan();
+                        (2.0 * h * camera.focus_distance,
+                         camera.position + camera.focus_distance * camera.forwards)
+                    }
+                };
+                let viewport_width: f32 = viewport_height * (width / height);
 
-        let viewport_u = viewport_width * camera.right;
-        let viewport_v = -viewport_height * camera.up;
+                let viewport_u = viewport_width * camera.right;
+                let viewport_v = -viewport_height * camera.up;
 
-        let du = viewport_u / image_size.0 as f32;
-        let dv = viewport_v / image_size.1 as f32;
+                let du = viewport_u / width;
+                let dv = viewport_v / height;
 
-        let upper_left = camera.position + camera.focus_distance * camera.forwards -
-            0.5 * (viewport_u + viewport_v);
-        let pixel_00 = upper_left + 0.5 * (du + dv);
+                let upper_left = center - 0.5 * (viewport_u + viewport_v);
+                (upper_left + 0.5 * (du + dv), du, dv)
+            }
+            Projection::Fisheye { .. } | Projection::Equirectangular => {
+                // x grows to the right and y upwards, both in radians: the offset from the
+                // image center for a fisheye, longitude and latitude for a panorama
+                let step = match camera.projection {
+                    Projection::Fisheye { .. } => Vec2::splat(2.0 * max_angle / height),
+                    _ => Vec2::new(2.0 * PI / width, PI / height),
+                };
+                let upper_left = Vec2::new(-0.5 * width, 0.5 * height) * step;
+                let du = Vec3::new(step.x, 0.0, 0.0);
+                let dv = Vec3::new(0.0, -step.y, 0.0);
+                (upper_left.extend(0.0) + 0.5 * (du + dv), du, dv)
+            }
+        };
+
+        let (aperture_blades, aperture_rotation) = match camera.aperture {
+            Aperture::Circular => (0, 0.0),
+            Aperture::Polygonal { blades, rotation } => (blades.max(3), rotation),
+        };
 
         GPUCamera {
             camera_position: camera.position.extend(0.0),
@@ -46,7 +117,16 @@ impl GPUCamera {
             du: du.extend(0.0),
             dv: dv.extend(0.0),
             defocus_radius,
-            _buffer: [0u32; 3]
+            shutter_open: camera.shutter_open,
+            shutter_close: camera.shutter_close,
+            exposure: camera.exposure(),
+            aperture_blades,
+            aperture_rotation: aperture_rotation.to_radians(),
+            projection,
+            max_angle,
+            eye_offset,
+            convergence_distance,
+            _buffer: [0u32; 2],
         }
     }
 }
@@ -56,17 +136,80 @@ impl GPUCamera {
 pub struct GPUSamplingParameters {
     samples_per_pixel: u32,
     num_bounces: u32,
-    _buffer: [u32; 6],
+    light_sampling: u32,
+    russian_roulette_depth: u32,
+    // zero turns clamping off
+    max_sample_radiance: f32,
+    sampler: u32,
+    filter_type: u32,
+    filter_radius: f32,
+    // the render seed hashed with the frame
+    seed: u32,
 }
 
-// right now this is silly, but later when we add fields to this struct,
-// we may have to do some padding for GPU
+// the sampling parameters as the kernel reads them; every field is four bytes, so none need
+// padding, and sampling_parameters_layout_matches_wgsl holds them to the WGSL struct
 pub fn get_gpu_sampling_params(sampling_parameters: &SamplingParameters)
                            -> GPUSamplingParameters
 {
     GPUSamplingParameters {
         samples_per_pixel: sampling_parameters.samples_per_pixel,
         num_bounces: sampling_parameters.num_bounces,
-        _buffer: [0u32; 6]
+        light_sampling: sampling_parameters.light_sampling as u32,
+        russian_roulette_depth: sampling_parameters.russian_roulette_depth,
+        max_sample_radiance: sampling_parameters.max_sample_radiance
+            .map_or(0.0, |radiance| radiance.max(f32::MIN_POSITIVE)),
+        sampler: sampling_parameters.sampler as u32,
+        filter_type: sampling_parameters.filter.filter_type as u32,
+        filter_radius: sampling_parameters.filter.radius,
+        seed: frame_seed(sampling_parameters.seed, sampling_parameters.frame),
     }
-}
\ No newline at end of file
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct GPUBackground {
+    color: Vec4,
+    // nonzero for the sky gradient, which ignores color
+    sky: u32,
+    _buffer: [u32; 3],
+}
+
+unsafe impl bytemuck::Pod for GPUBackground {}
+unsafe impl bytemuck::Zeroable for GPUBackground {}
+
+impl GPUBackground {
+    pub fn new(background: Background) -> Self {
+        let (color, sky) = match background {
+            Background::Sky => (Vec3::ZERO, 1),
+            Background::Color(color) => (color, 0),
+        };
+        Self { color: color.extend(1.0), sky, _buffer: [0u32; 3] }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::assert_layout;
+
+    #[test]
+    fn camera_layout_matches_wgsl() {
+        assert_layout!(GPUCamera, "CameraData", camera_position, camera_forwards, camera_right,
+                       camera_up, pixel_00, du, dv, defocus_radius, shutter_open, shutter_close,
+                       exposure, aperture_blades, aperture_rotation, projection, max_angle,
+                       eye_offset, convergence_distance);
+    }
+
+    #[test]
+    fn sampling_parameters_layout_matches_wgsl() {
+        assert_layout!(GPUSamplingParameters, "SamplingParameters", samples_per_pixel, num_bounces,
+                       light_sampling, russian_roulette_depth, max_sample_radiance, sampler,
+                       filter_type, filter_radius, seed);
+    }
+
+    #[test]
+    fn background_layout_matches_wgsl() {
+        assert_layout!(GPUBackground, "Background", color, sky);
+    }
+}
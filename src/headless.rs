@@ -0,0 +1,156 @@
+use std::path::{Path, PathBuf};
+use image::{imageops, ImageResult, RgbaImage};
+use crate::app::RenderParameters;
+use crate::animation::CameraAnimation;
+use crate::bvh::BVHTree;
+use crate::light_bvh::LightBVH;
+use crate::raytracer::required_limits;
+use crate::{Eye, RayTracer, Scene, StereoLayout};
+
+// the frames of an animation to render, the first at time zero
+#[derive(Copy, Clone, Debug)]
+pub struct ImageSequence {
+    pub frame_count: u32,
+    pub frames_per_second: f32,
+    // replaces the render parameters' samples per pixel for every frame
+    pub samples_per_pixel: u32,
+}
+
+impl ImageSequence {
+    pub fn new(frame_count: u32, frames_per_second: f32, samples_per_pixel: u32) -> Self {
+        Self { frame_count, frames_per_second, samples_per_pixel }
+    }
+
+    // enough frames to play the animation through once; a turntable leaves out the frame
+    // that would repeat the first, so the sequence loops
+    pub fn for_animation(animation: &CameraAnimation, frames_per_second: f32,
+                         samples_per_pixel: u32) -> Self {
+        let frames = (animation.duration() * frames_per_second).round() as u32;
+        let frame_count = match animation {
+            CameraAnimation::Path(_) => frames + 1,
+            CameraAnimation::Turntable { .. } => frames.max(1),
+        };
+        Self::new(frame_count, frames_per_second, samples_per_pixel)
+    }
+
+    pub fn frame_time(&self, frame: u32) -> f32 {
+        frame as f32 / self.frames_per_second
+    }
+}
+
+// renders a scene without a window, for writing images to disk
+pub struct HeadlessRenderer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    renderer: RayTracer,
+}
+
+// a device on any adapter, as there is no window to present to; None if there is no adapter
+pub(crate) async fn request_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(
+        wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        }
+    );
+    let adapter = instance.request_adapter(
+        &wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface: None,
+            force_fallback_adapter: false,
+        }
+    ).await?;
+    // software and GL adapters may offer smaller storage buffers than the window asks for,
+    // which is fine for any scene that fits in them
+    let limits = required_limits();
+    let limits = wgpu::Limits {
+        max_storage_buffer_binding_size: limits.max_storage_buffer_binding_size
+            .min(adapter.limits().max_storage_buffer_binding_size),
+        ..limits
+    };
+    adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            required_features: wgpu::Features::empty(),
+            required_limits: limits,
+            label: None,
+            memory_hints: Default::default(),
+        },
+        None,
+    ).await.ok()
+}
+
+impl HeadlessRenderer {
+    // None if there is no adapter to render with
+    pub fn new(scene: &Scene, render_parameters: &RenderParameters) -> Option<Self> {
+        pollster::block_on(Self::new_async(scene, render_parameters))
+    }
+
+    async fn new_async(scene: &Scene, render_parameters: &RenderParameters) -> Option<Self> {
+        let (device, queue) = request_device().await?;
+
+        let mut bvh_tree = BVHTree::new(scene.primitives());
+        bvh_tree.build_bvh_tree();
+        let mut light_bvh = LightBVH::new(scene);
+        light_bvh.build_light_bvh();
+
+        let renderer = RayTracer::new(
+            &device,
+            &queue,
+            wgpu::TextureFormat::Rgba8Unorm,
+            render_parameters,
+            scene,
+            render_parameters.viewport,
+            &bvh_tree,
+            &light_bvh,
+        )?;
+
+        Some(Self { device, queue, renderer })
+    }
+
+    // the image from the camera, at the size of the viewport; a stereo camera renders each eye
+    // at that size and puts them together, so the image is twice as wide or twice as high
+    pub fn render(&self, render_parameters: &RenderParameters) -> RgbaImage {
+        let size = render_parameters.viewport;
+        self.renderer.update_sampling_parameters(&self.queue, render_parameters);
+        let Some(stereo) = render_parameters.camera.stereo else {
+            self.renderer.update_camera(&self.queue, render_parameters);
+            return self.renderer.render_image(&self.device, &self.queue, size);
+        };
+
+        let (width, height) = match stereo.layout {
+            StereoLayout::SideBySide => (2 * size.0, size.1),
+            StereoLayout::OverUnder => (size.0, 2 * size.1),
+        };
+        let mut image = RgbaImage::new(width, height);
+        for (i, eye) in [Eye::Left, Eye::Right].into_iter().enumerate() {
+            self.renderer.update_eye_camera(&self.queue, render_parameters, Some(eye));
+            let eye_image = self.renderer.render_image(&self.device, &self.queue, size);
+            let (x, y) = match stereo.layout {
+                StereoLayout::SideBySide => (i as u32 * size.0, 0),
+                StereoLayout::OverUnder => (0, i as u32 * size.1),
+            };
+            imageops::replace(&mut image, &eye_image, x as i64, y as i64);
+        }
+        image
+    }
+
+    // renders every frame of the sequence with the camera following the animation, and writes
+    // them to the directory as frame_00000.png, frame_00001.png and so on; each frame hashes its
+    // number into the seed, so the noise changes from frame to frame; returns their paths
+    pub fn render_sequence(&self, render_parameters: &RenderParameters, animation: &CameraAnimation,
+                           sequence: &ImageSequence, directory: &Path) -> ImageResult<Vec<PathBuf>> {
+        std::fs::create_dir_all(directory)?;
+        let mut frame_parameters = render_parameters.clone();
+        frame_parameters.sampling_parameters.samples_per_pixel = sequence.samples_per_pixel;
+
+        let mut paths = Vec::with_capacity(sequence.frame_count as usize);
+        for frame in 0..sequence.frame_count {
+            animation.apply(&mut frame_parameters.camera, sequence.frame_time(frame));
+            frame_parameters.sampling_parameters.frame = frame;
+            let path = directory.join(format!("frame_{frame:05}.png"));
+            self.render(&frame_parameters).save(&path)?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
@@ -0,0 +1,68 @@
+// the structs the kernel reads are laid out by hand to match their WGSL counterparts; these
+// checks hold them to the offsets and sizes naga gives the kernel's structs, so a mismatch fails
+// a test rather than putting garbage on screen
+use std::sync::OnceLock;
+use naga::{Expression, Literal, Module, TypeInner};
+use crate::raytracer::KERNEL_DEFINES;
+use crate::shader_preprocessor::{embedded_shader, preprocess};
+
+fn kernel() -> &'static Module {
+    static MODULE: OnceLock<Module> = OnceLock::new();
+    MODULE.get_or_init(|| {
+        let kernel = preprocess("raytracer_kernel.wgsl", KERNEL_DEFINES, embedded_shader).unwrap();
+        naga::front::wgsl::parse_str(&kernel.source).unwrap_or_else(|err| {
+            panic!("{}", kernel.map_error_lines(&err.emit_to_string(&kernel.source)))
+        })
+    })
+}
+
+// fields are the Rust struct's names and offsets in order, leaving out the _buffer padding,
+// which WGSL does without; they must sit where the WGSL struct's members do, and the structs
+// must be the same size, so arrays of them have the same stride
+pub(crate) fn assert_matches_wgsl(wgsl_name: &str, size: usize, fields: &[(&str, usize)]) {
+    let (members, span) = kernel().types.iter()
+        .find_map(|(_, ty)| match &ty.inner {
+            TypeInner::Struct { members, span } if ty.name.as_deref() == Some(wgsl_name) => {
+                Some((members, *span))
+            }
+            _ => None,
+        })
+        .unwrap_or_else(|| panic!("the kernel has no struct {wgsl_name}"));
+    let wgsl_fields: Vec<(&str, usize)> = members.iter()
+        .map(|member| (member.name.as_deref().unwrap_or("?"), member.offset as usize))
+        .collect();
+
+    assert_eq!(fields.len(), wgsl_fields.len(),
+               "{wgsl_name} has fields {fields:?} in Rust but {wgsl_fields:?} in WGSL");
+    for (&(field, offset), &(member, wgsl_offset)) in fields.iter().zip(&wgsl_fields) {
+        assert_eq!(offset, wgsl_offset,
+                   "{wgsl_name}.{field} is at byte {offset} in Rust but {member} is at byte \
+                    {wgsl_offset} in WGSL");
+    }
+    assert_eq!(size, span as usize,
+               "{wgsl_name} is {size} bytes in Rust but {span} bytes in WGSL");
+}
+
+// the value of one of the kernel's u32 constants, for the limits Rust has to agree with
+pub(crate) fn wgsl_u32_constant(name: &str) -> u32 {
+    let module = kernel();
+    let constant = module.constants.iter()
+        .find_map(|(_, constant)| (constant.name.as_deref() == Some(name)).then_some(constant))
+        .unwrap_or_else(|| panic!("the kernel has no constant {name}"));
+    match module.global_expressions[constant.init] {
+        Expression::Literal(Literal::U32(value)) => value,
+        ref init => panic!("the kernel's {name} is {init:?}, not a u32"),
+    }
+}
+
+// assert_layout!(RustType, "WgslStruct", field, ...) with the fields in declaration order
+macro_rules! assert_layout {
+    ($rust:ty, $wgsl:literal, $($field:ident),+ $(,)?) => {
+        crate::layout::assert_matches_wgsl(
+            $wgsl,
+            std::mem::size_of::<$rust>(),
+            &[$((stringify!($field), std::mem::offset_of!($rust, $field))),+],
+        )
+    };
+}
+pub(crate) use assert_layout;
@@ -1,17 +1,56 @@
 mod app;
 mod scene;
 mod sphere;
+mod quad;
+mod plane;
+mod aabox;
+mod shape;
+mod triangle;
+mod primitive;
 mod camera;
 mod util_funcs;
 mod raytracer;
 mod material;
+mod texture;
+mod light;
+mod medium;
 mod gpu_structs;
 mod gpu_timing;
 mod bvh;
+mod light_bvh;
+mod filter;
+mod sampler;
+mod headless;
+mod animation;
+mod scene_graph;
+mod scene_generator;
+mod gltf_import;
+mod mesh_import;
+mod shader_watcher;
+mod shader_preprocessor;
+#[cfg(test)]
+mod layout;
 
-pub use app::App;
+pub use app::{App, LightSampling, RenderParameters, SamplingParameters};
 pub use sphere::Sphere;
-pub use camera::Camera;
-pub use scene::Scene;
+pub use quad::Quad;
+pub use plane::Plane;
+pub use aabox::AABox;
+pub use shape::Shape;
+pub use triangle::Triangle;
+pub use camera::{Aperture, Camera, Eye, PhysicalLens, Projection, Stereo, StereoLayout};
+pub use scene::{Background, Scene};
+pub use gltf_import::{import_gltf, GltfImport};
+pub use mesh_import::{load_ply, load_stl, NormalSmoothing};
+pub use scene_generator::SceneGenerator;
+pub use scene_graph::{NodeId, SceneGraph, SceneNode, SceneObject, Transform};
+pub use material::Material;
+pub use texture::Texture;
+pub use light::Light;
+pub use medium::Medium;
+pub use sampler::{blue_noise_mask, frame_seed, BlueNoiseMask, PixelSampler, Sampler};
+pub use filter::{FilterType, PixelFilter};
 pub use raytracer::RayTracer;
+pub use headless::{HeadlessRenderer, ImageSequence};
+pub use animation::{CameraAnimation, CameraKeyframe};
 
@@ -0,0 +1,210 @@
+use std::f32::consts::PI;
+use glam::{Vec3, Vec4, Vec4Swizzles};
+use crate::{Material, Quad, Shape, Sphere, Triangle};
+
+// light_type will be indexed as follows:
+// 0 Sphere; 1 Quad; 2 Disk; 3 Triangle; 4 Point; 5 Spot; 6 Sun
+//
+// the first four are area lights that refer back to an emissive primitive; spheres are
+// sampled by the cone they subtend and read their center from the sphere buffer (they may
+// be moving), while quads, disks and triangles carry the geometry needed to pick a point uniformly on
+// their surface:
+// - quad: position is the corner, u and v the edges
+// - disk: position is the center, u and v the two radii of the (possibly sheared) ellipse
+// - triangle: position is the first corner, u and v the edges to the other two
+//
+// the rest are punctual lights with no geometry, so rays never hit them and they only
+// reach the scene through shadow rays; they carry their own intensity:
+// - point: position, intensity in every direction
+// - spot: position, u the direction it shines in, intensity on its axis falling off
+//   between the inner and outer cone
+// - sun: u the direction the light travels, intensity is the irradiance it delivers to a
+//   surface facing it; cos_outer is the cosine of its angular radius
+
+enum LightType {
+    Sphere = 0,
+    Quad = 1,
+    Disk = 2,
+    Triangle = 3,
+    Point = 4,
+    Spot = 5,
+    Sun = 6,
+}
+
+// marks primitives that are not in the light list, and the placeholder of an empty list
+pub const NO_LIGHT: u32 = u32::MAX;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct Light {
+    position: Vec4,
+    u: Vec4,
+    v: Vec4,
+    intensity: Vec4,
+    light_type: u32,
+    prim_idx: u32,
+    material_idx: u32,
+    area: f32,
+    cos_inner: f32,
+    cos_outer: f32,
+    _buffer: [u32; 2],
+}
+
+unsafe impl bytemuck::Pod for Light {}
+unsafe impl bytemuck::Zeroable for Light {}
+
+impl Light {
+    pub fn sphere(sphere: &Sphere, sphere_idx: usize) -> Self {
+        let area = 4.0 * PI * sphere.radius() * sphere.radius();
+        Self::new(LightType::Sphere, sphere_idx, Vec3::ZERO, Vec3::ZERO, Vec3::ZERO, area,
+                  sphere.material_idx())
+    }
+
+    pub fn quad(quad: &Quad, quad_idx: usize) -> Self {
+        let u = quad.u.truncate();
+        let v = quad.v.truncate();
+        Self::new(LightType::Quad, quad_idx, quad.q.truncate(), u, v, u.cross(v).length(),
+                  quad.material_idx())
+    }
+
+    // None if the shape is not a disk; other shapes are only found by BSDF sampling
+    pub fn disk(shape: &Shape, shape_idx: usize) -> Option<Self> {
+        let (center, u, v) = shape.disk_frame()?;
+        let area = PI * u.cross(v).length();
+        Some(Self::new(LightType::Disk, shape_idx, center, u, v, area, shape.material_idx()))
+    }
+
+    pub fn triangle(triangle: &Triangle, triangle_idx: usize) -> Self {
+        let [p0, p1, p2] = triangle.vertices();
+        let u = p1 - p0;
+        let v = p2 - p0;
+        Self::new(LightType::Triangle, triangle_idx, p0, u, v, 0.5 * u.cross(v).length(),
+                  triangle.material_idx())
+    }
+
+    pub fn point(position: Vec3, intensity: Vec3) -> Self {
+        Self::punctual(LightType::Point, position, Vec3::ZERO, intensity)
+    }
+
+    // the cone angles are in degrees, measured from the axis
+    pub fn spot(position: Vec3, direction: Vec3, intensity: Vec3,
+                inner_angle: f32, outer_angle: f32) -> Self {
+        let outer_angle = outer_angle.clamp(0.0, 90.0);
+        let inner_angle = inner_angle.clamp(0.0, outer_angle);
+        Self {
+            cos_inner: inner_angle.to_radians().cos(),
+            cos_outer: outer_angle.to_radians().cos(),
+            ..Self::punctual(LightType::Spot, position, direction.normalize(), intensity)
+        }
+    }
+
+    // angular_diameter is in degrees; the real sun is about half a degree across
+    pub fn sun(direction: Vec3, irradiance: Vec3, angular_diameter: f32) -> Self {
+        Self {
+            cos_outer: (0.5 * angular_diameter.clamp(0.0, 180.0)).to_radians().cos(),
+            ..Self::punctual(LightType::Sun, Vec3::ZERO, direction.normalize(), irradiance)
+        }
+    }
+
+    // the placeholder uploaded when a scene has no lights, so the GPU buffer is never empty
+    pub fn none() -> Self {
+        Self {
+            light_type: NO_LIGHT,
+            ..Self::new(LightType::Sphere, 0, Vec3::ZERO, Vec3::ZERO, Vec3::ZERO, 0.0, 0)
+        }
+    }
+
+    pub fn is_punctual(&self) -> bool {
+        self.light_type >= LightType::Point as u32 && self.light_type != NO_LIGHT
+    }
+
+    // lights infinitely far away, which have no position to bound
+    pub fn is_infinite(&self) -> bool {
+        self.light_type == LightType::Sun as u32
+    }
+
+    // the box the light emits from, or None for the sun
+    pub(crate) fn bounds(&self, spheres: &[Sphere]) -> Option<(Vec3, Vec3)> {
+        let position = self.position.xyz();
+        let u = self.u.xyz();
+        let v = self.v.xyz();
+        match self.light_type {
+            0 => Some(spheres[self.prim_idx as usize].get_aabb()),
+            1 => {
+                let corners = [position, position + u, position + v, position + u + v];
+                let aabb_min = corners.iter().fold(Vec3::INFINITY, |acc, c| acc.min(*c));
+                let aabb_max = corners.iter().fold(Vec3::NEG_INFINITY, |acc, c| acc.max(*c));
+                Some((aabb_min, aabb_max))
+            }
+            2 => {
+                // the extent of an ellipse along each axis
+                let half_extent = (u * u + v * v).map(f32::sqrt);
+                Some((position - half_extent, position + half_extent))
+            }
+            3 => {
+                let corners = [position, position + u, position + v];
+                let aabb_min = corners.iter().fold(Vec3::INFINITY, |acc, c| acc.min(*c));
+                let aabb_max = corners.iter().fold(Vec3::NEG_INFINITY, |acc, c| acc.max(*c));
+                Some((aabb_min, aabb_max))
+            }
+            6 => None,
+            _ => Some((position, position)),
+        }
+    }
+
+    // the total power the light gives off, as a luminance; the sun is taken to fall on a
+    // disk the size of the scene
+    pub(crate) fn power(&self, materials: &[Material], scene_radius: f32) -> f32 {
+        let intensity = luminance(self.intensity.xyz());
+        let emission = || luminance(materials[self.material_idx as usize].emission());
+        match self.light_type {
+            0 => PI * self.area * emission(),
+            // quads, disks and triangles emit from both sides
+            1..=3 => 2.0 * PI * self.area * emission(),
+            4 => 4.0 * PI * intensity,
+            5 => 2.0 * PI * (1.0 - 0.5 * (self.cos_inner + self.cos_outer)) * intensity,
+            6 => PI * scene_radius * scene_radius * intensity,
+            _ => 0.0,
+        }
+    }
+
+    fn punctual(light_type: LightType, position: Vec3, direction: Vec3, intensity: Vec3) -> Self {
+        Self {
+            intensity: intensity.extend(0.0),
+            ..Self::new(light_type, 0, position, direction, Vec3::ZERO, 0.0, 0)
+        }
+    }
+
+    fn new(light_type: LightType, prim_idx: usize, position: Vec3, u: Vec3, v: Vec3, area: f32,
+           material_idx: u32) -> Self {
+        Self {
+            position: position.extend(0.0),
+            u: u.extend(0.0),
+            v: v.extend(0.0),
+            intensity: Vec4::ZERO,
+            light_type: light_type as u32,
+            prim_idx: prim_idx as u32,
+            material_idx,
+            area,
+            cos_inner: 1.0,
+            cos_outer: 1.0,
+            _buffer: [0u32; 2],
+        }
+    }
+}
+
+fn luminance(color: Vec3) -> f32 {
+    color.dot(Vec3::new(0.2126, 0.7152, 0.0722))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::assert_layout;
+
+    #[test]
+    fn layout_matches_wgsl() {
+        assert_layout!(Light, "Light", position, u, v, intensity, light_type, prim_idx,
+                       material_idx, area, cos_inner, cos_outer);
+    }
+}
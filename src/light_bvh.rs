@@ -0,0 +1,199 @@
+use glam::Vec3;
+use crate::Scene;
+
+// the per light data for picking lights in proportion to their power:
+// - alias_probability and alias_idx form a Vose alias table: slot i keeps light i with
+//   alias_probability and hands over to light alias_idx otherwise
+// - pmf is the chance the alias table picks the light, which MIS needs when a light is hit
+// - bit_trail is the path from the light BVH root down to the light's leaf, where bit k
+//   set means the right child was taken at depth k
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct LightSelection {
+    alias_probability: f32,
+    alias_idx: u32,
+    pmf: f32,
+    bit_trail: u32,
+}
+
+unsafe impl bytemuck::Pod for LightSelection {}
+unsafe impl bytemuck::Zeroable for LightSelection {}
+
+// a leaf holds a single light (light_count == 1, left_first is the light's index);
+// otherwise the children sit at left_first and left_first + 1
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LightBVHNode {
+    pub aabb_min: Vec3,
+    pub left_first: u32,
+    pub aabb_max: Vec3,
+    pub light_count: u32,
+    pub power: f32,
+    _buffer: [u32; 3],
+}
+
+unsafe impl bytemuck::Pod for LightBVHNode {}
+unsafe impl bytemuck::Zeroable for LightBVHNode {}
+
+// the tree is built over every light that has a position; suns sit at the end of the
+// light list and are picked separately by the kernel
+pub struct LightBVH {
+    pub nodes: Vec<LightBVHNode>,
+    pub selection: Vec<LightSelection>,
+    bounds: Vec<Option<(Vec3, Vec3)>>,
+    powers: Vec<f32>,
+}
+
+impl LightBVH {
+    pub fn new(scene: &Scene) -> Self {
+        // suns are spread over a disk that covers everything in the scene
+        let (scene_min, scene_max) = scene.primitives().iter()
+            .map(|primitive| primitive.get_aabb())
+            .fold((Vec3::INFINITY, Vec3::NEG_INFINITY),
+                  |(acc_min, acc_max), (aabb_min, aabb_max)|
+                      (acc_min.min(aabb_min), acc_max.max(aabb_max)));
+        let scene_radius = if scene_min.is_finite() {
+            0.5 * (scene_max - scene_min).length()
+        } else {
+            1.0
+        };
+
+        let light_count = scene.lights.len();
+        Self {
+            nodes: Vec::<LightBVHNode>::with_capacity(2 * light_count),
+            selection: Vec::<LightSelection>::with_capacity(light_count),
+            bounds: scene.lights.iter().map(|light| light.bounds(&scene.spheres)).collect(),
+            powers: scene.lights.iter()
+                .map(|light| light.power(&scene.materials, scene_radius).max(0.0))
+                .collect(),
+        }
+    }
+
+    pub fn build_light_bvh(&mut self) {
+        self.build_alias_table();
+
+        // an empty tree is a root with no lights, which the kernel recognizes
+        self.nodes.push(LightBVHNode::default());
+        let mut lights: Vec<u32> = (0..self.bounds.len() as u32)
+            .filter(|&idx| self.bounds[idx as usize].is_some())
+            .collect();
+        if !lights.is_empty() {
+            self.subdivide(0, &mut lights, 0, 0);
+        }
+    }
+
+    fn build_alias_table(&mut self) {
+        let count = self.powers.len();
+        let total_power: f32 = self.powers.iter().sum();
+        // without any measurable power, fall back to picking lights uniformly
+        let pmfs: Vec<f32> = if total_power > 0.0 {
+            self.powers.iter().map(|power| power / total_power).collect()
+        } else {
+            vec![1.0 / count as f32; count]
+        };
+
+        self.selection = pmfs.iter().enumerate()
+            .map(|(idx, &pmf)| LightSelection {
+                alias_probability: 1.0,
+                alias_idx: idx as u32,
+                pmf,
+                bit_trail: 0,
+            })
+            .collect();
+
+        // Vose's method: pair every slot that is under the average with one that is over it
+        let mut scaled: Vec<f32> = pmfs.iter().map(|pmf| pmf * count as f32).collect();
+        let mut small: Vec<usize> = (0..count).filter(|&idx| scaled[idx] < 1.0).collect();
+        let mut large: Vec<usize> = (0..count).filter(|&idx| scaled[idx] >= 1.0).collect();
+        while let (Some(&under), Some(&over)) = (small.last(), large.last()) {
+            small.pop();
+            self.selection[under].alias_probability = scaled[under];
+            self.selection[under].alias_idx = over as u32;
+            scaled[over] -= 1.0 - scaled[under];
+            if scaled[over] < 1.0 {
+                large.pop();
+                small.push(over);
+            }
+        }
+        // whatever is left is (up to rounding) exactly average and keeps its own slot
+    }
+
+    fn subdivide(&mut self, index: usize, lights: &mut [u32], bit_trail: u32, depth: u32) {
+        let mut aabb_min = Vec3::INFINITY;
+        let mut aabb_max = Vec3::NEG_INFINITY;
+        let mut centroid_min = Vec3::INFINITY;
+        let mut centroid_max = Vec3::NEG_INFINITY;
+        let mut power = 0.0;
+        for &light in lights.iter() {
+            let (light_min, light_max) = self.light_bounds(light);
+            aabb_min = aabb_min.min(light_min);
+            aabb_max = aabb_max.max(light_max);
+            centroid_min = centroid_min.min(0.5 * (light_min + light_max));
+            centroid_max = centroid_max.max(0.5 * (light_min + light_max));
+            power += self.powers[light as usize];
+        }
+
+        let mut node = LightBVHNode {
+            aabb_min,
+            aabb_max,
+            light_count: lights.len() as u32,
+            power,
+            ..Default::default()
+        };
+
+        if lights.len() == 1 {
+            node.left_first = lights[0];
+            self.nodes[index] = node;
+            self.selection[lights[0] as usize].bit_trail = bit_trail;
+            return;
+        }
+
+        // split at the median along the widest spread of centroids; this keeps the tree
+        // balanced, so a bit trail never runs past 32 levels
+        let extent = centroid_max - centroid_min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+        lights.sort_by(|&a, &b| {
+            let (a_min, a_max) = self.light_bounds(a);
+            let (b_min, b_max) = self.light_bounds(b);
+            (a_min + a_max)[axis].total_cmp(&(b_min + b_max)[axis])
+        });
+
+        let node_idx = self.nodes.len();
+        node.left_first = node_idx as u32;
+        self.nodes[index] = node;
+        self.nodes.push(LightBVHNode::default());
+        self.nodes.push(LightBVHNode::default());
+
+        let (left, right) = lights.split_at_mut(lights.len() / 2);
+        self.subdivide(node_idx, left, bit_trail, depth + 1);
+        self.subdivide(node_idx + 1, right, bit_trail | (1 << depth), depth + 1);
+    }
+
+    fn light_bounds(&self, light: u32) -> (Vec3, Vec3) {
+        self.bounds[light as usize].unwrap_or((Vec3::ZERO, Vec3::ZERO))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::assert_layout;
+
+    #[test]
+    fn light_selection_layout_matches_wgsl() {
+        assert_layout!(LightSelection, "LightSelection", alias_probability, alias_idx, pmf,
+                       bit_trail);
+    }
+
+    #[test]
+    fn light_bvh_node_layout_matches_wgsl() {
+        assert_layout!(LightBVHNode, "LightBVHNode", aabb_min, left_first, aabb_max, light_count,
+                       power);
+    }
+}
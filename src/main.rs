@@ -1,12 +1,37 @@
 use winit::error::EventLoopError;
 use winit::event_loop::{ControlFlow, EventLoop};
-use wiw::App;
+use wiw::{App, SceneGenerator};
 
+// wiw [--watch-shaders] [scene] [seed]: renders one of the built-in scenes, book_one by default;
+// the seed, 0 unless given, both generates the scene and drives the kernel's sampling.
+// --watch-shaders rebuilds the pipelines whenever a shader in src/shaders is saved
 fn main() -> Result<(), EventLoopError> {
     env_logger::init();
-    
+
+    let (flags, positional): (Vec<String>, Vec<String>) = std::env::args().skip(1)
+        .partition(|arg| arg.starts_with("--"));
+    let watch_shaders = flags.iter().any(|flag| flag == "--watch-shaders");
+    if let Some(flag) = flags.iter().find(|flag| *flag != "--watch-shaders") {
+        eprintln!("unknown option {flag}");
+        std::process::exit(1);
+    }
+
+    let mut args = positional.into_iter();
+    let app = match args.next() {
+        Some(name) => {
+            let Some(generator) = SceneGenerator::from_name(&name) else {
+                eprintln!("unknown scene {name}; the scenes are {}",
+                          SceneGenerator::NAMES.join(", "));
+                std::process::exit(1);
+            };
+            let seed = args.next().and_then(|seed| seed.parse().ok()).unwrap_or(0);
+            App::with_scene(generator.generate(seed), generator.camera()).with_seed(seed)
+        }
+        None => App::default(),
+    };
+    let mut app = if watch_shaders { app.with_shader_reloading() } else { app };
+
     let event_loop = EventLoop::new()?;
     event_loop.set_control_flow(ControlFlow::Poll);
-    let mut app = App::default();
     event_loop.run_app(&mut app)
-}
\ No newline at end of file
+}
@@ -1,37 +1,157 @@
 use glam::{Vec3, Vec4};
+use crate::texture::NO_TEXTURE;
 
 // material_type will be indexed as follows:
-// 0 Lambertian; 1 Metal; 2 Dielectric
+// 0 Lambertian; 1 Metal; 2 Dielectric; 3 Principled
+//
+// Principled is a metallic-roughness BSDF following the glTF PBR model, with optional
+// clearcoat, sheen and transmission layers; it uses refract_index as its ior
 
 enum MaterialType {
     Lambertian = 0,
     Metal = 1,
     Dielectric = 2,
+    Principled = 3,
 }
 
+// the texture slots hold indices into the scene's textures; a texture multiplies the
+// constant it belongs to (albedo, fuzz as roughness, emission); for principled materials
+// the roughness texture is a glTF metallic-roughness map (roughness in g, metallic in b)
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 pub struct Material {
     albedo: Vec4,
+    emission: Vec4,
     fuzz: f32,
     refract_index: f32,
     material_type: u32,
-    _buffer: u32,
+    albedo_texture: u32,
+    roughness_texture: u32,
+    emission_texture: u32,
+    metallic: f32,
+    roughness: f32,
+    specular: f32,
+    clearcoat: f32,
+    clearcoat_roughness: f32,
+    sheen: f32,
+    sheen_tint: f32,
+    transmission: f32,
+    _buffer: [u32; 2],
 }
 
 unsafe impl bytemuck::Pod for Material {}
 unsafe impl bytemuck::Zeroable for Material {}
 
 impl Material {
-    pub fn Lambertian(albedo: Vec3) -> Self {
-        Self { albedo: albedo.extend(1.0), fuzz:0.0, refract_index:0.0, material_type: 0, _buffer: 0 }
+    pub fn lambertian(albedo: Vec3) -> Self {
+        Self::new(albedo.extend(1.0), 0.0, 0.0, MaterialType::Lambertian)
     }
 
-    pub fn Metal(albedo: Vec3, fuzz: f32) -> Self {
-        Self { albedo: albedo.extend(1.0), fuzz: fuzz.clamp(0.0, 1.0), refract_index:0.0, material_type: 1, _buffer: 0 }
+    pub fn metal(albedo: Vec3, fuzz: f32) -> Self {
+        Self::new(albedo.extend(1.0), fuzz.clamp(0.0, 1.0), 0.0, MaterialType::Metal)
     }
 
-    pub fn Dielectric(refract_index: f32) -> Self {
-        Self { albedo: Vec4::ONE, fuzz:0.0, refract_index, material_type: 2, _buffer: 0 }
+    pub fn dielectric(refract_index: f32) -> Self {
+        Self::new(Vec4::ONE, 0.0, refract_index, MaterialType::Dielectric)
     }
-}
\ No newline at end of file
+
+    pub fn principled(base_color: Vec3, metallic: f32, roughness: f32) -> Self {
+        let mut material = Self::new(base_color.extend(1.0), 0.0, 1.5, MaterialType::Principled);
+        material.metallic = metallic.clamp(0.0, 1.0);
+        material.roughness = roughness.clamp(0.0, 1.0);
+        material
+    }
+
+    // a surface that only gives off light
+    pub fn emissive(emission: Vec3) -> Self {
+        Self::lambertian(Vec3::ZERO).with_emission(emission)
+    }
+
+    fn new(albedo: Vec4, fuzz: f32, refract_index: f32, material_type: MaterialType) -> Self {
+        Self {
+            albedo,
+            emission: Vec4::ZERO,
+            fuzz,
+            refract_index,
+            material_type: material_type as u32,
+            albedo_texture: NO_TEXTURE,
+            roughness_texture: NO_TEXTURE,
+            emission_texture: NO_TEXTURE,
+            metallic: 0.0,
+            roughness: 0.5,
+            specular: 0.5,
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.03,
+            sheen: 0.0,
+            sheen_tint: 0.5,
+            transmission: 0.0,
+            _buffer: [0u32; 2],
+        }
+    }
+
+    pub fn emission(&self) -> Vec3 {
+        self.emission.truncate()
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        self.emission() != Vec3::ZERO
+    }
+
+    pub fn with_emission(mut self, emission: Vec3) -> Self {
+        self.emission = emission.extend(1.0);
+        self
+    }
+
+    pub fn with_albedo_texture(mut self, texture_idx: u32) -> Self {
+        self.albedo_texture = texture_idx;
+        self
+    }
+
+    pub fn with_roughness_texture(mut self, texture_idx: u32) -> Self {
+        self.roughness_texture = texture_idx;
+        self
+    }
+
+    pub fn with_emission_texture(mut self, texture_idx: u32) -> Self {
+        self.emission_texture = texture_idx;
+        self
+    }
+
+    // the following only affect principled materials
+
+    pub fn with_specular(mut self, specular: f32) -> Self {
+        self.specular = specular.max(0.0);
+        self
+    }
+
+    pub fn with_clearcoat(mut self, clearcoat: f32, clearcoat_roughness: f32) -> Self {
+        self.clearcoat = clearcoat.clamp(0.0, 1.0);
+        self.clearcoat_roughness = clearcoat_roughness.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_sheen(mut self, sheen: f32, sheen_tint: f32) -> Self {
+        self.sheen = sheen.max(0.0);
+        self.sheen_tint = sheen_tint.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_transmission(mut self, transmission: f32, ior: f32) -> Self {
+        self.transmission = transmission.clamp(0.0, 1.0);
+        self.refract_index = ior;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::assert_layout;
+
+    #[test]
+    fn layout_matches_wgsl() {
+        assert_layout!(Material, "Material", albedo, emission, fuzz, refract_index, material_type,
+                       albedo_texture, roughness_texture, emission_texture, metallic, roughness,
+                       specular, clearcoat, clearcoat_roughness, sheen, sheen_tint, transmission);
+    }
+}
@@ -0,0 +1,63 @@
+use glam::{Vec3, Vec4};
+
+// a homogeneous participating medium, such as smoke, fog or murky water
+//
+// a medium fills a closed sphere, box or shape; such a primitive becomes an invisible
+// boundary that rays pass through and its material is not used. the scene may also be
+// filled with a global fog, which the GPU keeps at the start of its media list, so
+// scene.media[i] is found there at i + 1
+
+pub const NO_MEDIUM: u32 = u32::MAX;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct Medium {
+    // per unit length, for each color channel
+    absorption: Vec4,
+    scattering: Vec4,
+    // the Henyey-Greenstein asymmetry: negative scatters back, positive forward
+    g: f32,
+    _buffer: [u32; 3],
+}
+
+unsafe impl bytemuck::Pod for Medium {}
+unsafe impl bytemuck::Zeroable for Medium {}
+
+impl Medium {
+    pub fn new(absorption: Vec3, scattering: Vec3, g: f32) -> Self {
+        Self {
+            absorption: absorption.max(Vec3::ZERO).extend(0.0),
+            scattering: scattering.max(Vec3::ZERO).extend(0.0),
+            g: g.clamp(-0.99, 0.99),
+            _buffer: [0u32; 3],
+        }
+    }
+
+    // the isotropic constant density medium of book two, where albedo is the chance
+    // a collision scatters rather than absorbs
+    pub fn constant_density(density: f32, albedo: Vec3) -> Self {
+        let albedo = albedo.clamp(Vec3::ZERO, Vec3::ONE);
+        Self::new(density * (Vec3::ONE - albedo), density * albedo, 0.0)
+    }
+
+    // stands in for the global fog when the scene has none
+    pub(crate) fn vacuum() -> Self {
+        Self::new(Vec3::ZERO, Vec3::ZERO, 0.0)
+    }
+}
+
+// where a scene medium sits in the GPU media list
+pub(crate) fn gpu_medium_idx(medium_idx: u32) -> u32 {
+    medium_idx + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::assert_layout;
+
+    #[test]
+    fn layout_matches_wgsl() {
+        assert_layout!(Medium, "Medium", absorption, scattering, g);
+    }
+}
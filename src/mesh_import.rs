@@ -0,0 +1,554 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use glam::{Vec2, Vec3};
+use crate::Triangle;
+
+// loaders for PLY and STL meshes, giving triangles with the material passed in, to push onto
+// a scene's triangles or hang from a scene graph node. PLY vertex colors tint the material's
+// albedo, so a scan is best given a white material; STL has only face normals, and leaves
+// it to the caller how they are smoothed
+
+// how the corner normals of a mesh without vertex normals are made
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NormalSmoothing {
+    // every triangle shades with its own face normal
+    Flat,
+    // corners average the normals of the triangles that meet there, weighted by area, but
+    // only from those turned less than crease_angle (degrees) from the triangle's own, so
+    // sharp edges stay sharp; 180 smooths everything
+    Smooth { crease_angle: f32 },
+}
+
+pub fn load_stl(path: &Path, material_idx: u32, smoothing: NormalSmoothing)
+    -> io::Result<Vec<Triangle>> {
+    parse_stl(&fs::read(path)?, material_idx, smoothing)
+}
+
+fn parse_stl(data: &[u8], material_idx: u32, smoothing: NormalSmoothing)
+    -> io::Result<Vec<Triangle>> {
+    let faces = if is_binary_stl(data) {
+        binary_stl_faces(data)
+    } else {
+        ascii_stl_faces(data)?
+    };
+    // the geometric normal is taken from the winding, which the format makes counter-clockwise;
+    // stored normals are often missing or wrong
+    let mut triangles: Vec<Triangle> = faces.into_iter()
+        .map(|[p0, p1, p2]| Triangle::new(p0, p1, p2, material_idx))
+        .collect();
+    if let NormalSmoothing::Smooth { crease_angle } = smoothing {
+        smooth_normals(&mut triangles, crease_angle);
+    }
+    Ok(triangles)
+}
+
+// a binary STL starts with an 80 byte header and a triangle count, and is exactly as long as
+// that count says; ASCII ones start with "solid", but so do some binary headers
+fn is_binary_stl(data: &[u8]) -> bool {
+    if data.len() < 84 {
+        return false;
+    }
+    let count = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+    data.len() == 84 + 50 * count
+}
+
+fn binary_stl_faces(data: &[u8]) -> Vec<[Vec3; 3]> {
+    let read_vec3 = |bytes: &[u8]| {
+        let f = |i: usize| f32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        Vec3::new(f(0), f(4), f(8))
+    };
+    // each record is a normal, three corners and a two byte attribute
+    data[84..].chunks_exact(50)
+        .map(|record| {
+            [read_vec3(&record[12..]), read_vec3(&record[24..]), read_vec3(&record[36..])]
+        })
+        .collect()
+}
+
+fn ascii_stl_faces(data: &[u8]) -> io::Result<Vec<[Vec3; 3]>> {
+    let text = String::from_utf8_lossy(data);
+    let mut tokens = text.split_ascii_whitespace();
+    let mut faces = vec![];
+    let mut corners = Vec::with_capacity(3);
+    while let Some(token) = tokens.next() {
+        if token == "vertex" {
+            let mut coordinate = || -> io::Result<f32> {
+                let token = tokens.next().ok_or_else(|| invalid("STL vertex is cut short"))?;
+                token.parse().map_err(|_| invalid(&format!("bad STL coordinate {token}")))
+            };
+            corners.push(Vec3::new(coordinate()?, coordinate()?, coordinate()?));
+        } else if token == "endfacet" {
+            if let [p0, p1, p2] = corners[..] {
+                faces.push([p0, p1, p2]);
+            }
+            corners.clear();
+        }
+    }
+    Ok(faces)
+}
+
+// PLY in ASCII or binary of either byte order: vertex positions with optional normals,
+// colors (bytes or 0..1 floats) and texture coordinates, and polygonal faces, split into
+// fans of triangles. other elements are skipped
+pub fn load_ply(path: &Path, material_idx: u32) -> io::Result<Vec<Triangle>> {
+    parse_ply(&fs::read(path)?, material_idx)
+}
+
+fn parse_ply(data: &[u8], material_idx: u32) -> io::Result<Vec<Triangle>> {
+    let (header, body_start) = PlyHeader::parse(data)?;
+    let text = match header.format {
+        PlyFormat::Ascii => String::from_utf8_lossy(&data[body_start..]),
+        _ => Default::default(),
+    };
+    let mut body = match header.format {
+        PlyFormat::Ascii => PlyBody::Ascii(text.split_ascii_whitespace()),
+        PlyFormat::BinaryLittleEndian => PlyBody::Binary { data: &data[body_start..], pos: 0,
+                                                           big_endian: false },
+        PlyFormat::BinaryBigEndian => PlyBody::Binary { data: &data[body_start..], pos: 0,
+                                                        big_endian: true },
+    };
+
+    let mut vertices: Vec<PlyVertex> = vec![];
+    let mut faces: Vec<Vec<usize>> = vec![];
+    for element in &header.elements {
+        for _ in 0..element.count {
+            let mut vertex = PlyVertex::default();
+            for property in &element.properties {
+                match property.list_count {
+                    Some(count_type) => {
+                        let count = body.read(count_type)? as usize;
+                        let mut values = Vec::with_capacity(count);
+                        for _ in 0..count {
+                            values.push(body.read(property.scalar)?);
+                        }
+                        if element.name == "face" && matches!(property.name.as_str(),
+                                                              "vertex_indices" | "vertex_index") {
+                            faces.push(values.into_iter().map(|v| v as usize).collect());
+                        }
+                    }
+                    None => {
+                        let value = body.read(property.scalar)?;
+                        if element.name == "vertex" {
+                            vertex.set(&property.name, property.scalar, value as f32);
+                        }
+                    }
+                }
+            }
+            if element.name == "vertex" {
+                vertices.push(vertex);
+            }
+        }
+    }
+
+    let mut triangles = vec![];
+    for face in faces {
+        if face.iter().any(|&i| i >= vertices.len()) {
+            return Err(invalid("PLY face refers to a missing vertex"));
+        }
+        for i in 1..face.len().saturating_sub(1) {
+            let [v0, v1, v2] = [&vertices[face[0]], &vertices[face[i]], &vertices[face[i + 1]]];
+            let mut triangle = Triangle::new(v0.position, v1.position, v2.position, material_idx);
+            if let (Some(n0), Some(n1), Some(n2)) = (v0.normal(), v1.normal(), v2.normal()) {
+                triangle = triangle.with_normals(n0, n1, n2);
+            }
+            if let (Some(c0), Some(c1), Some(c2)) = (v0.color(), v1.color(), v2.color()) {
+                triangle = triangle.with_colors(c0, c1, c2);
+            }
+            if let (Some(uv0), Some(uv1), Some(uv2)) = (v0.uv, v1.uv, v2.uv) {
+                triangle = triangle.with_uvs(uv0, uv1, uv2);
+            }
+            triangles.push(triangle);
+        }
+    }
+    Ok(triangles)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum PlyScalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl PlyScalar {
+    fn parse(name: &str) -> io::Result<Self> {
+        Ok(match name {
+            "char" | "int8" => Self::I8,
+            "uchar" | "uint8" => Self::U8,
+            "short" | "int16" => Self::I16,
+            "ushort" | "uint16" => Self::U16,
+            "int" | "int32" => Self::I32,
+            "uint" | "uint32" => Self::U32,
+            "float" | "float32" => Self::F32,
+            "double" | "float64" => Self::F64,
+            _ => return Err(invalid(&format!("unknown PLY type {name}"))),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+}
+
+struct PlyProperty {
+    name: String,
+    scalar: PlyScalar,
+    // the type of the length in front of a list property
+    list_count: Option<PlyScalar>,
+}
+
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<PlyProperty>,
+}
+
+struct PlyHeader {
+    format: PlyFormat,
+    elements: Vec<PlyElement>,
+}
+
+impl PlyHeader {
+    // the header, and where the body after it starts
+    fn parse(data: &[u8]) -> io::Result<(Self, usize)> {
+        let mut format = None;
+        let mut elements: Vec<PlyElement> = vec![];
+        let mut pos = 0;
+        let mut first_line = true;
+        loop {
+            let end = data[pos..].iter().position(|&b| b == b'\n')
+                .ok_or_else(|| invalid("PLY header has no end_header"))?;
+            let line = String::from_utf8_lossy(&data[pos..pos + end]).trim().to_string();
+            pos += end + 1;
+            let words: Vec<&str> = line.split_ascii_whitespace().collect();
+            if first_line {
+                if words.first() != Some(&"ply") {
+                    return Err(invalid("not a PLY file"));
+                }
+                first_line = false;
+                continue;
+            }
+            match words.as_slice() {
+                ["format", name, _version] => {
+                    format = Some(match *name {
+                        "ascii" => PlyFormat::Ascii,
+                        "binary_little_endian" => PlyFormat::BinaryLittleEndian,
+                        "binary_big_endian" => PlyFormat::BinaryBigEndian,
+                        _ => return Err(invalid(&format!("unknown PLY format {name}"))),
+                    });
+                }
+                ["element", name, count] => {
+                    let count = count.parse()
+                        .map_err(|_| invalid(&format!("bad PLY element count {count}")))?;
+                    elements.push(PlyElement { name: name.to_string(), count, properties: vec![] });
+                }
+                ["property", "list", count_type, scalar, name] => {
+                    let element = elements.last_mut()
+                        .ok_or_else(|| invalid("PLY property before any element"))?;
+                    element.properties.push(PlyProperty {
+                        name: name.to_string(),
+                        scalar: PlyScalar::parse(scalar)?,
+                        list_count: Some(PlyScalar::parse(count_type)?),
+                    });
+                }
+                ["property", scalar, name] => {
+                    let element = elements.last_mut()
+                        .ok_or_else(|| invalid("PLY property before any element"))?;
+                    element.properties.push(PlyProperty {
+                        name: name.to_string(),
+                        scalar: PlyScalar::parse(scalar)?,
+                        list_count: None,
+                    });
+                }
+                ["end_header"] => break,
+                _ => {} // comments and obj_info
+            }
+        }
+        let format = format.ok_or_else(|| invalid("PLY header has no format"))?;
+        Ok((Self { format, elements }, pos))
+    }
+}
+
+enum PlyBody<'a> {
+    Ascii(std::str::SplitAsciiWhitespace<'a>),
+    Binary { data: &'a [u8], pos: usize, big_endian: bool },
+}
+
+impl PlyBody<'_> {
+    fn read(&mut self, scalar: PlyScalar) -> io::Result<f64> {
+        match self {
+            PlyBody::Ascii(tokens) => {
+                let token = tokens.next().ok_or_else(|| invalid("PLY data is cut short"))?;
+                token.parse().map_err(|_| invalid(&format!("bad PLY value {token}")))
+            }
+            PlyBody::Binary { data, pos, big_endian } => {
+                let size = scalar.size();
+                let mut bytes = [0u8; 8];
+                bytes[..size].copy_from_slice(data.get(*pos..*pos + size)
+                    .ok_or_else(|| invalid("PLY data is cut short"))?);
+                *pos += size;
+                if *big_endian {
+                    bytes[..size].reverse();
+                }
+                let [b0, b1, b2, b3, ..] = bytes;
+                Ok(match scalar {
+                    PlyScalar::I8 => b0 as i8 as f64,
+                    PlyScalar::U8 => b0 as f64,
+                    PlyScalar::I16 => i16::from_le_bytes([b0, b1]) as f64,
+                    PlyScalar::U16 => u16::from_le_bytes([b0, b1]) as f64,
+                    PlyScalar::I32 => i32::from_le_bytes([b0, b1, b2, b3]) as f64,
+                    PlyScalar::U32 => u32::from_le_bytes([b0, b1, b2, b3]) as f64,
+                    PlyScalar::F32 => f32::from_le_bytes([b0, b1, b2, b3]) as f64,
+                    PlyScalar::F64 => f64::from_le_bytes(bytes),
+                })
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct PlyVertex {
+    position: Vec3,
+    normal: [Option<f32>; 3],
+    color: [Option<f32>; 3],
+    uv: Option<Vec2>,
+}
+
+impl PlyVertex {
+    fn set(&mut self, name: &str, scalar: PlyScalar, value: f32) {
+        // integer colors run to the largest value of their type, float ones to 1
+        let color = match scalar {
+            PlyScalar::U8 => value / 255.0,
+            PlyScalar::U16 => value / 65535.0,
+            _ => value,
+        };
+        match name {
+            "x" => self.position.x = value,
+            "y" => self.position.y = value,
+            "z" => self.position.z = value,
+            "nx" => self.normal[0] = Some(value),
+            "ny" => self.normal[1] = Some(value),
+            "nz" => self.normal[2] = Some(value),
+            "red" | "r" | "diffuse_red" => self.color[0] = Some(color),
+            "green" | "g" | "diffuse_green" => self.color[1] = Some(color),
+            "blue" | "b" | "diffuse_blue" => self.color[2] = Some(color),
+            "u" | "s" | "texture_u" | "texture_s" => self.uv.get_or_insert(Vec2::ZERO).x = value,
+            "v" | "t" | "texture_v" | "texture_t" => self.uv.get_or_insert(Vec2::ZERO).y = value,
+            _ => {}
+        }
+    }
+
+    fn normal(&self) -> Option<Vec3> {
+        let [Some(x), Some(y), Some(z)] = self.normal else { return None };
+        Some(Vec3::new(x, y, z))
+    }
+
+    // PLY colors are sRGB
+    fn color(&self) -> Option<Vec3> {
+        let [Some(r), Some(g), Some(b)] = self.color else { return None };
+        Some(Vec3::new(r, g, b).map(srgb_to_linear))
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+// gives each corner the area weighted average of the face normals around it that are within
+// crease_angle degrees of its own face's; corners are matched by exact position
+pub(crate) fn smooth_normals(triangles: &mut [Triangle], crease_angle: f32) {
+    let cos_crease = crease_angle.clamp(0.0, 180.0).to_radians().cos();
+    // twice the area times the unit normal
+    let face_normals: Vec<Vec3> = triangles.iter()
+        .map(|triangle| {
+            let [p0, p1, p2] = triangle.vertices();
+            (p1 - p0).cross(p2 - p0)
+        })
+        .collect();
+    let key = |p: Vec3| [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()];
+    let mut faces_at: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+    for (idx, triangle) in triangles.iter().enumerate() {
+        for p in triangle.vertices() {
+            faces_at.entry(key(p)).or_default().push(idx);
+        }
+    }
+
+    for (idx, triangle) in triangles.iter_mut().enumerate() {
+        let own = face_normals[idx].normalize_or_zero();
+        let corner_normal = |p: Vec3| {
+            faces_at[&key(p)].iter()
+                .map(|&other| face_normals[other])
+                .filter(|n| n.normalize_or_zero().dot(own) >= cos_crease - 1e-6)
+                .sum::<Vec3>()
+        };
+        let [p0, p1, p2] = triangle.vertices();
+        *triangle = triangle.with_normals(corner_normal(p0), corner_normal(p1), corner_normal(p2));
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_same(actual: &Triangle, expected: &Triangle) {
+        assert!(bytemuck::bytes_of(actual) == bytemuck::bytes_of(expected),
+                "got {actual:?}, expected {expected:?}");
+    }
+
+    #[test]
+    fn ascii_ply_with_colors_splits_quads_into_fans() {
+        let ply = b"ply
+format ascii 1.0
+comment a unit square
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+1 1 0 0 0 255
+0 1 0 255 255 255
+4 0 1 2 3
+";
+        let triangles = parse_ply(ply, 7).unwrap();
+        let [p0, p1, p2, p3] = [Vec3::ZERO, Vec3::X, Vec3::new(1.0, 1.0, 0.0), Vec3::Y];
+        assert_eq!(triangles.len(), 2);
+        assert_same(&triangles[0], &Triangle::new(p0, p1, p2, 7)
+            .with_colors(Vec3::X, Vec3::Y, Vec3::Z));
+        assert_same(&triangles[1], &Triangle::new(p0, p2, p3, 7)
+            .with_colors(Vec3::X, Vec3::Z, Vec3::ONE));
+    }
+
+    #[test]
+    fn big_endian_ply() {
+        let mut ply = b"ply
+format binary_big_endian 1.0
+element vertex 3
+property float x
+property float y
+property float z
+element face 1
+property list uchar int vertex_indices
+end_header
+".to_vec();
+        let corners = [Vec3::new(1.0, 2.0, 3.0), Vec3::new(-4.0, 5.0, 6.0),
+                       Vec3::new(7.0, -8.0, 9.5)];
+        for corner in corners {
+            for coordinate in corner.to_array() {
+                ply.extend(coordinate.to_be_bytes());
+            }
+        }
+        ply.push(3);
+        for idx in [0i32, 1, 2] {
+            ply.extend(idx.to_be_bytes());
+        }
+
+        let triangles = parse_ply(&ply, 0).unwrap();
+        assert_eq!(triangles.len(), 1);
+        assert_eq!(triangles[0].vertices(), corners);
+    }
+
+    #[test]
+    fn ply_face_past_the_vertices_is_invalid() {
+        let ply = b"ply
+format ascii 1.0
+element vertex 3
+property float x
+property float y
+property float z
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0
+1 0 0
+0 1 0
+3 0 1 3
+";
+        let err = parse_ply(ply, 0).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn binary_stl_may_start_with_solid() {
+        let corners = [Vec3::ZERO, Vec3::X, Vec3::Y];
+        let mut stl = b"solid but binary all the same".to_vec();
+        stl.resize(80, b' ');
+        stl.extend(1u32.to_le_bytes());
+        for vector in [Vec3::Z, corners[0], corners[1], corners[2]] {
+            for coordinate in vector.to_array() {
+                stl.extend(coordinate.to_le_bytes());
+            }
+        }
+        stl.extend([0u8; 2]);
+        assert!(is_binary_stl(&stl));
+
+        let triangles = parse_stl(&stl, 0, NormalSmoothing::Flat).unwrap();
+        assert_eq!(triangles.len(), 1);
+        assert_eq!(triangles[0].vertices(), corners);
+
+        let ascii = b"solid triangle
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 0 1 0
+    endloop
+  endfacet
+endsolid triangle
+";
+        assert!(!is_binary_stl(ascii));
+        let triangles = parse_stl(ascii, 0, NormalSmoothing::Flat).unwrap();
+        assert_eq!(triangles.len(), 1);
+        assert_eq!(triangles[0].vertices(), corners);
+    }
+
+    #[test]
+    fn smoothing_keeps_edges_sharper_than_the_crease() {
+        // two triangles folded along the x axis at a right angle, facing +z and -y
+        let folded = || vec![
+            Triangle::new(Vec3::ZERO, Vec3::X, Vec3::Y, 0),
+            Triangle::new(Vec3::ZERO, Vec3::NEG_Z, Vec3::X, 0),
+        ];
+
+        let mut sharp = folded();
+        smooth_normals(&mut sharp, 30.0);
+        assert_eq!(sharp[0].normals(), [Vec3::Z; 3]);
+        assert_eq!(sharp[1].normals(), [Vec3::NEG_Y; 3]);
+
+        let mut smooth = folded();
+        smooth_normals(&mut smooth, 180.0);
+        let edge = Vec3::new(0.0, -1.0, 1.0).normalize();
+        for (normal, expected) in smooth[0].normals().into_iter().zip([edge, edge, Vec3::Z]) {
+            assert!(normal.abs_diff_eq(expected, 1e-6), "{normal} isn't {expected}");
+        }
+    }
+}
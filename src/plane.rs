@@ -0,0 +1,43 @@
+use glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
+
+// an infinite plane through point with the given normal; planes are unbounded
+// so they are not part of the BVH and the kernel tests them all directly
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct Plane {
+    pub point: Vec4,
+    pub normal: Vec4,
+    material_idx: u32,
+    _buffer: [u32; 3],
+}
+
+unsafe impl bytemuck::Pod for Plane {}
+unsafe impl bytemuck::Zeroable for Plane {}
+
+impl Plane {
+    pub fn new(point: Vec3, normal: Vec3, material_idx: u32) -> Self {
+        Self {
+            point: point.extend(0.0),
+            normal: normal.normalize().extend(0.0),
+            material_idx,
+            _buffer: [0u32; 3]
+        }
+    }
+
+    pub(crate) fn transformed(&self, transform: &Mat4) -> Self {
+        // normals follow the inverse transpose, to stay perpendicular under non-uniform scales
+        let normal = transform.inverse().transpose().transform_vector3(self.normal.xyz());
+        Self::new(transform.transform_point3(self.point.xyz()), normal, self.material_idx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::assert_layout;
+
+    #[test]
+    fn layout_matches_wgsl() {
+        assert_layout!(Plane, "Plane", point, normal, material_idx);
+    }
+}
@@ -0,0 +1,57 @@
+use glam::Vec3;
+
+// prim_type will be indexed as follows:
+// 0 Sphere; 1 Quad; 2 AABox; 3 Shape; 4 Triangle
+// infinite planes have no bounds and are kept out of the BVH
+
+pub enum PrimitiveType {
+    Sphere = 0,
+    Quad = 1,
+    AABox = 2,
+    Shape = 3,
+    Triangle = 4,
+}
+
+// a Primitive is what the BVH is built over; it records which typed buffer
+// (spheres, quads, boxes, shapes, triangles) holds the actual object, and caches its bounds
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct Primitive {
+    pub aabb_min: Vec3,
+    prim_type: u32,
+    pub aabb_max: Vec3,
+    prim_idx: u32,
+}
+
+unsafe impl bytemuck::Pod for Primitive {}
+unsafe impl bytemuck::Zeroable for Primitive {}
+
+impl Primitive {
+    pub fn new(prim_type: PrimitiveType, prim_idx: usize, aabb: (Vec3, Vec3)) -> Self {
+        Self {
+            aabb_min: aabb.0,
+            prim_type: prim_type as u32,
+            aabb_max: aabb.1,
+            prim_idx: prim_idx as u32,
+        }
+    }
+
+    pub fn get_aabb(&self) -> (Vec3, Vec3) {
+        (self.aabb_min, self.aabb_max)
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        0.5 * (self.aabb_min + self.aabb_max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::assert_layout;
+
+    #[test]
+    fn layout_matches_wgsl() {
+        assert_layout!(Primitive, "Primitive", aabb_min, prim_type, aabb_max, prim_idx);
+    }
+}
@@ -0,0 +1,82 @@
+use glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
+use crate::light::NO_LIGHT;
+
+// a parallelogram with corner q and edges u and v; the normal is u x v
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct Quad {
+    pub q: Vec4,
+    pub u: Vec4,
+    pub v: Vec4,
+    material_idx: u32,
+    // index into the scene's light list if the quad is emissive
+    light_idx: u32,
+    _buffer: [u32; 2],
+}
+
+unsafe impl bytemuck::Pod for Quad {}
+unsafe impl bytemuck::Zeroable for Quad {}
+
+impl Quad {
+    pub fn new(q: Vec3, u: Vec3, v: Vec3, material_idx: u32) -> Self {
+        Self {
+            q: q.extend(0.0),
+            u: u.extend(0.0),
+            v: v.extend(0.0),
+            material_idx,
+            light_idx: NO_LIGHT,
+            _buffer: [0u32; 2]
+        }
+    }
+
+    pub fn material_idx(&self) -> u32 {
+        self.material_idx
+    }
+
+    pub(crate) fn set_light_idx(&mut self, light_idx: u32) {
+        self.light_idx = light_idx;
+    }
+
+    // a mirroring transform would turn u x v to the other side, so the quad is then walked
+    // from the far end of u, which covers the same parallelogram with the normal kept
+    pub(crate) fn transformed(&self, transform: &Mat4) -> Self {
+        let (mut q, mut u) = (self.q.xyz(), self.u.xyz());
+        if transform.determinant() < 0.0 {
+            (q, u) = (q + u, -u);
+        }
+        Self {
+            q: transform.transform_point3(q).extend(0.0),
+            u: transform.transform_vector3(u).extend(0.0),
+            v: transform.transform_vector3(self.v.xyz()).extend(0.0),
+            ..*self
+        }
+    }
+
+    pub fn get_aabb(&self) -> (Vec3, Vec3) {
+        let q = self.q.xyz();
+        let u = self.u.xyz();
+        let v = self.v.xyz();
+        let corners = [q, q + u, q + v, q + u + v];
+        let mut aabb_min = Vec3::INFINITY;
+        let mut aabb_max = Vec3::NEG_INFINITY;
+        for corner in corners {
+            aabb_min = aabb_min.min(corner);
+            aabb_max = aabb_max.max(corner);
+        }
+        // an axis aligned quad has a flat box; pad it so the slab test still works
+        let pad = Vec3::select((aabb_max - aabb_min).cmplt(Vec3::splat(0.0001)),
+                               Vec3::splat(0.0001), Vec3::ZERO);
+        (aabb_min - pad, aabb_max + pad)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::assert_layout;
+
+    #[test]
+    fn layout_matches_wgsl() {
+        assert_layout!(Quad, "Quad", q, u, v, material_idx, light_idx);
+    }
+}
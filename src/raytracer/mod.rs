@@ -1,48 +1,66 @@
-use std::collections::HashMap;
 use wgpu::{BindGroupDescriptor, BindGroupEntry,
            BindGroupLayoutDescriptor, BindGroupLayoutEntry,
            BindingType, Buffer, BufferBindingType, BufferUsages,
-           ComputePassTimestampWrites, Device, PipelineCompilationOptions,
+           ComputePassTimestampWrites, Device,
            Queue, RenderPassTimestampWrites, RenderPipeline, ShaderStages,
-           StorageTextureAccess, Surface, SurfaceConfiguration, TextureDimension,
+           StorageTextureAccess, Surface, SurfaceConfiguration, Texture, TextureDimension,
            TextureFormat, TextureView, TextureViewDimension};
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use image::{imageops, RgbaImage};
 use winit::event::WindowEvent;
 use crate::app::{RenderParameters};
-use crate::{Scene};
+use crate::{blue_noise_mask, Eye, Light, Medium, Scene};
+use crate::filter::FILTER_TABLE_SIZE;
 use crate::bvh::{BVHTree};
+use crate::light_bvh::LightBVH;
 use crate::gpu_timing::{Queries, QueryResults};
-use crate::gpu_structs::{GPUCamera, get_gpu_sampling_params};
+use crate::gpu_structs::{GPUBackground, GPUCamera, GPUSamplingParameters,
+                         get_gpu_sampling_params};
+use crate::shader_preprocessor::{embedded_shader, preprocess, PreprocessedShader};
+
+// the features the kernel is built with; without USE_BVH every ray is tested against every
+// primitive, which is slow but a check on the BVH
+pub(crate) const KERNEL_DEFINES: &[&str] = &["USE_BVH"];
 
 pub struct RayTracer {
+    image_buffer: Texture,
     camera_buffer: Buffer,
     sampling_parameters_buffer: Buffer,
+    focus_pick_buffer: Buffer,
     image_bind_group: wgpu::BindGroup,
     scene_bind_group: wgpu::BindGroup,
     bvh_bind_group: wgpu::BindGroup,
     parameters_bind_group: wgpu::BindGroup,
+    light_bind_group: wgpu::BindGroup,
+    ray_tracer_pipeline_layout: wgpu::PipelineLayout,
     ray_tracer_pipeline: wgpu::ComputePipeline,
+    focus_pick_pipeline: wgpu::ComputePipeline,
     display_pipeline_bind_group: wgpu::BindGroup,
+    display_pipeline_layout: wgpu::PipelineLayout,
+    display_format: TextureFormat,
     display_pipeline: RenderPipeline,
 }
 
 impl RayTracer {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(device: &Device,
                queue: &Queue,
-               surface_config: &SurfaceConfiguration,
+               display_format: TextureFormat,
                render_parameters: &RenderParameters,
                scene: &Scene,
                max_image_size: (u32, u32),
-               bvh_tree: &BVHTree) -> Option<Self> {
+               bvh_tree: &BVHTree,
+               light_bvh: &LightBVH) -> Option<Self> {
 
         // create the image_buffer that the compute shader will use to store image
         let (image_bind_group,
             image_bind_group_layout,
+            image_buffer,
             image_buffer_view) = create_image_buffer(device, max_image_size);
 
         // create the scene bind group that holds objects and materials
         let (scene_bind_group, scene_bind_group_layout)
-            = create_scene_bind_group(device, scene);
+            = create_scene_bind_group(device, queue, scene);
 
         let (bvh_bind_group, bvh_bind_group_layout)
             = create_bvh_bind_group(device, bvh_tree);
@@ -51,9 +69,14 @@ impl RayTracer {
         let (parameters_bind_group,
             parameter_bind_group_layout,
             camera_buffer,
-            sampling_parameters_buffer)
+            sampling_parameters_buffer,
+            focus_pick_buffer)
             = create_parameters_bind_group(device, queue, render_parameters);
 
+        // the lights that are sampled directly, in a group of their own
+        let (light_bind_group, light_bind_group_layout)
+            = create_light_bind_group(device, scene, light_bvh);
+
         let ray_tracer_pipeline_layout = device.create_pipeline_layout(
             &wgpu::PipelineLayoutDescriptor {
                 label: Some("ray tracer pipeline layout"),
@@ -61,50 +84,68 @@ impl RayTracer {
                     &image_bind_group_layout,
                     &scene_bind_group_layout,
                     &bvh_bind_group_layout,
-                    &parameter_bind_group_layout
+                    &parameter_bind_group_layout,
+                    &light_bind_group_layout
                 ],
                 push_constant_ranges: &[],
             }
         );
 
-        let shader = device.create_shader_module(
-            wgpu::include_wgsl!("../shaders/raytracer_kernel.wgsl")
-        );
-        let mut id:HashMap<String, f64> = HashMap::new();
-        // id.insert("stackSize".to_string(), (bvh_tree.nodes.len() - 1) as f64);
-        let ray_tracer_pipeline = device.create_compute_pipeline(
-            &wgpu::ComputePipelineDescriptor {
-                label: Some("ray tracer pipeline"),
-                layout: Some(&ray_tracer_pipeline_layout),
-                module: &shader,
-                entry_point: "main",
-                compilation_options: Default::default(),
-                // PipelineCompilationOptions {
-                //     constants: None, //&id,
-                //     zero_initialize_workgroup_memory: false,
-                //     vertex_pulling_transform: false,
-                // }, // Default::default(),
-                cache: None,
-            }
-        );
+        let kernel = preprocess("raytracer_kernel.wgsl", KERNEL_DEFINES, embedded_shader)
+            .expect("the embedded kernel preprocesses");
+        let shader = create_shader_module(device, "raytracer_kernel.wgsl", &kernel);
+        let (ray_tracer_pipeline, focus_pick_pipeline) =
+            create_ray_tracer_pipelines(device, &ray_tracer_pipeline_layout, &shader);
 
-        let (display_pipeline_bind_group, display_pipeline) =
-            create_display_pipeline(device, surface_config.format, &image_buffer_view);
+        let (display_pipeline_bind_group, display_pipeline_layout) =
+            create_display_bind_group(device, &image_buffer_view);
+        let display = preprocess("screen_shader.wgsl", &[], embedded_shader)
+            .expect("the embedded display shader preprocesses");
+        let display_shader = create_shader_module(device, "screen_shader.wgsl", &display);
+        let display_pipeline = create_display_pipeline(
+            device, &display_pipeline_layout, display_format, &display_shader);
 
         Some(Self {
+            image_buffer,
             camera_buffer,
             sampling_parameters_buffer,
+            focus_pick_buffer,
             image_bind_group,
             scene_bind_group,
             bvh_bind_group,
             parameters_bind_group,
+            light_bind_group,
+            ray_tracer_pipeline_layout,
             ray_tracer_pipeline,
-            display_pipeline,
+            focus_pick_pipeline,
             display_pipeline_bind_group,
+            display_pipeline_layout,
+            display_format,
+            display_pipeline,
         })
 
     }
 
+    // rebuilds the pipelines from the given kernel and display shader; if either fails to
+    // compile, the pipelines already in use are kept and the errors returned
+    pub(crate) fn reload_shaders(&mut self, device: &Device, kernel: &PreprocessedShader,
+                                 display: &PreprocessedShader) -> Result<(), String> {
+        let (ray_tracer_pipeline, focus_pick_pipeline) = catch_shader_errors(device, || {
+            let shader = create_shader_module(device, "raytracer_kernel.wgsl", kernel);
+            create_ray_tracer_pipelines(device, &self.ray_tracer_pipeline_layout, &shader)
+        }).map_err(|err| kernel.map_error_lines(&err))?;
+        let display_pipeline = catch_shader_errors(device, || {
+            let shader = create_shader_module(device, "screen_shader.wgsl", display);
+            create_display_pipeline(device, &self.display_pipeline_layout, self.display_format,
+                                    &shader)
+        }).map_err(|err| display.map_error_lines(&err))?;
+
+        self.ray_tracer_pipeline = ray_tracer_pipeline;
+        self.focus_pick_pipeline = focus_pick_pipeline;
+        self.display_pipeline = display_pipeline;
+        Ok(())
+    }
+
     pub fn resize(&mut self,
                   device: &Device,
                   queue: &Queue,
@@ -119,6 +160,133 @@ impl RayTracer {
         let scene_parameters = GPUCamera::new(&render_parameters.camera,
                                               render_parameters.viewport);
         queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[scene_parameters]));
+
+        self.update_sampling_parameters(queue, render_parameters);
+    }
+
+    pub fn update_sampling_parameters(&self, queue: &Queue, render_parameters: &RenderParameters) {
+        let sampling_parameters = get_gpu_sampling_params(
+            &render_parameters.sampling_parameters);
+        queue.write_buffer(&self.sampling_parameters_buffer,
+                           0,
+                           bytemuck::cast_slice(&[sampling_parameters]));
+    }
+
+    pub fn update_camera(&self, queue: &Queue, render_parameters: &RenderParameters) {
+        self.update_eye_camera(queue, render_parameters, None);
+    }
+
+    // renders one eye of a stereo camera from now on, or the camera itself for None
+    pub fn update_eye_camera(&self, queue: &Queue, render_parameters: &RenderParameters,
+                             eye: Option<Eye>) {
+        let camera = GPUCamera::for_eye(&render_parameters.camera, render_parameters.viewport, eye);
+        queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[camera]));
+    }
+
+    // traces an image of the given size and reads it back, without presenting it
+    pub fn render_image(&self, device: &Device, queue: &Queue, size: (u32, u32)) -> RgbaImage {
+        // rows of a texture copy must be a multiple of 256 bytes apart
+        let unpadded_bytes_per_row = 4 * size.0;
+        let bytes_per_row = unpadded_bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) *
+            wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("image readback buffer"),
+            size: (bytes_per_row * size.1) as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(
+            &wgpu::CommandEncoderDescriptor {
+                label: Some("Image Encoder"),
+            });
+        {
+            let mut ray_tracing_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Image compute pass"),
+                timestamp_writes: None,
+            });
+            ray_tracing_pass.set_pipeline(&self.ray_tracer_pipeline);
+            ray_tracing_pass.set_bind_group(0, &self.image_bind_group, &[]);
+            ray_tracing_pass.set_bind_group(1, &self.scene_bind_group, &[]);
+            ray_tracing_pass.set_bind_group(2, &self.bvh_bind_group, &[]);
+            ray_tracing_pass.set_bind_group(3, &self.parameters_bind_group, &[]);
+            ray_tracing_pass.set_bind_group(4, &self.light_bind_group, &[]);
+            ray_tracing_pass.dispatch_workgroups(size.0, size.1, 1);
+        }
+        encoder.copy_texture_to_buffer(
+            self.image_buffer.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            });
+        queue.submit(Some(encoder.finish()));
+
+        readback_buffer.slice(..).map_async(wgpu::MapMode::Read, |_| ());
+        device.poll(wgpu::Maintain::wait()).panic_on_timeout();
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * size.1) as usize);
+        {
+            let view = readback_buffer.slice(..).get_mapped_range();
+            for row in view.chunks(bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+            }
+        }
+        readback_buffer.unmap();
+
+        RgbaImage::from_raw(size.0, size.1, pixels).expect("the readback holds every pixel")
+    }
+
+    // the distance along the camera's view to whatever the center of the pixel shows,
+    // or None if it shows the sky; waits for the GPU
+    pub fn pick_focus_distance(&self, device: &Device, queue: &Queue, pixel: (u32, u32))
+        -> Option<f32> {
+        queue.write_buffer(&self.focus_pick_buffer, 0, bytemuck::cast_slice(&[pixel.0, pixel.1]));
+
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("focus pick readback buffer"),
+            size: self.focus_pick_buffer.size(),
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(
+            &wgpu::CommandEncoderDescriptor {
+                label: Some("Focus Pick Encoder"),
+            });
+        {
+            let mut pick_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Focus pick pass"),
+                timestamp_writes: None,
+            });
+            pick_pass.set_pipeline(&self.focus_pick_pipeline);
+            pick_pass.set_bind_group(0, &self.image_bind_group, &[]);
+            pick_pass.set_bind_group(1, &self.scene_bind_group, &[]);
+            pick_pass.set_bind_group(2, &self.bvh_bind_group, &[]);
+            pick_pass.set_bind_group(3, &self.parameters_bind_group, &[]);
+            pick_pass.set_bind_group(4, &self.light_bind_group, &[]);
+            pick_pass.dispatch_workgroups(1, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&self.focus_pick_buffer, 0, &readback_buffer, 0,
+                                      self.focus_pick_buffer.size());
+        queue.submit(Some(encoder.finish()));
+
+        readback_buffer.slice(..).map_async(wgpu::MapMode::Read, |_| ());
+        device.poll(wgpu::Maintain::wait()).panic_on_timeout();
+        let distance = {
+            let view = readback_buffer.slice(..).get_mapped_range();
+            bytemuck::cast_slice::<u8, f32>(&view)[2]
+        };
+        readback_buffer.unmap();
+
+        (distance > 0.0).then_some(distance)
     }
 
     pub fn input(&mut self, _event: &WindowEvent) -> bool {
@@ -175,6 +343,7 @@ impl RayTracer {
             ray_tracing_pass.set_bind_group(1, &self.scene_bind_group, &[]);
             ray_tracing_pass.set_bind_group(2, &self.bvh_bind_group, &[]);
             ray_tracing_pass.set_bind_group(3, &self.parameters_bind_group, &[]);
+            ray_tracing_pass.set_bind_group(4, &self.light_bind_group, &[]);
             ray_tracing_pass.dispatch_workgroups(size.0, size.1, 1);
 
         }
@@ -216,8 +385,20 @@ impl RayTracer {
     }
 }
 
+// what the ray tracer needs beyond wgpu's default limits
+pub(crate) fn required_limits() -> wgpu::Limits {
+    wgpu::Limits {
+        max_storage_buffer_binding_size: 512_u32 << 20,
+        // the scene and BVH bind groups hold more storage buffers than the default 8
+        max_storage_buffers_per_shader_stage: 16,
+        // image, scene, bvh, parameters and lights
+        max_bind_groups: 5,
+        ..Default::default()
+    }
+}
+
 fn create_image_buffer(device: &Device, max_image_size: (u32, u32))
-                              -> (wgpu::BindGroup, wgpu::BindGroupLayout, TextureView) {
+                              -> (wgpu::BindGroup, wgpu::BindGroupLayout, Texture, TextureView) {
     let texture_size = wgpu::Extent3d {
         width: max_image_size.0,
         height: max_image_size.1,
@@ -232,6 +413,7 @@ fn create_image_buffer(device: &Device, max_image_size: (u32, u32))
         dimension: TextureDimension::D2,
         format: TextureFormat::Rgba8Unorm,
         usage: wgpu::TextureUsages::COPY_DST |
+            wgpu::TextureUsages::COPY_SRC |
             wgpu::TextureUsages::STORAGE_BINDING |
             wgpu::TextureUsages::TEXTURE_BINDING,
         view_formats: &[],
@@ -280,33 +462,26 @@ fn create_image_buffer(device: &Device, max_image_size: (u32, u32))
             ],
         }
     );
-    (image_bind_group, image_bind_group_layout, image_buffer_view)
+    (image_bind_group, image_bind_group_layout, image_buffer, image_buffer_view)
 }
 
 fn create_bvh_bind_group(device: &Device, bvh_tree: &BVHTree)
                            -> (wgpu::BindGroup, wgpu::BindGroupLayout) {
     // initialize the bvh_tree buffer
     let tree = &bvh_tree.nodes;
-    let bvh_buffer = device.create_buffer_init(&BufferInitDescriptor {
-        label: Some("BVH storage buffer"),
-        contents: bytemuck::cast_slice(tree.as_slice()),
-        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
-    });
+    let bvh_buffer = create_storage_buffer(device, "BVH storage buffer", tree);
+
+    // the primitive list the BVH leaves index into
+    let primitives = &bvh_tree.primitives;
+    let primitives_buffer = create_storage_buffer(
+        device, "Primitives storage buffer", primitives);
 
     let bvh_bind_group_layout = device.create_bind_group_layout(
         &BindGroupLayoutDescriptor {
-            label: Some("scene bind group layout"),
+            label: Some("bvh bind group layout"),
             entries: &[
-                BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: ShaderStages::COMPUTE,
-                    ty: BindingType::Buffer {
-                        ty: BufferBindingType::Storage { read_only: true },
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }
+                storage_buffer_layout_entry(0),
+                storage_buffer_layout_entry(1),
             ],
         }
     );
@@ -318,6 +493,10 @@ fn create_bvh_bind_group(device: &Device, bvh_tree: &BVHTree)
                 BindGroupEntry {
                     binding: 0,
                     resource: bvh_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: primitives_buffer.as_entire_binding(),
                 }
             ],
         }
@@ -325,46 +504,136 @@ fn create_bvh_bind_group(device: &Device, bvh_tree: &BVHTree)
     (bvh_bind_group, bvh_bind_group_layout)
 }
 
-fn create_scene_bind_group(device: &Device, scene: &Scene)
+fn create_light_bind_group(device: &Device, scene: &Scene, light_bvh: &LightBVH)
     -> (wgpu::BindGroup, wgpu::BindGroupLayout) {
-    let spheres = &scene.spheres;
-    let sphere_buffer = device.create_buffer_init(&BufferInitDescriptor {
-        label: Some("Sphere storage buffer"),
-        contents: bytemuck::cast_slice(spheres.as_slice()),
-        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+    // an empty light list gets a placeholder the kernel recognizes, rather than a zeroed light
+    let lights = if scene.lights.is_empty() { vec![Light::none()] } else { scene.lights.clone() };
+    let light_buffer = create_storage_buffer(
+        device, "Light storage buffer", &lights);
+
+    // the light BVH and the per light alias table entries and bit trails
+    let light_bvh_buffer = create_storage_buffer(
+        device, "Light BVH storage buffer", &light_bvh.nodes);
+    let light_selection_buffer = create_storage_buffer(
+        device, "Light selection storage buffer", &light_bvh.selection);
+
+    let light_bind_group_layout = device.create_bind_group_layout(
+        &BindGroupLayoutDescriptor {
+            label: Some("light bind group layout"),
+            entries: &[
+                storage_buffer_layout_entry(0),
+                storage_buffer_layout_entry(1),
+                storage_buffer_layout_entry(2),
+            ],
+        }
+    );
+    let light_bind_group = device.create_bind_group(
+        &BindGroupDescriptor {
+            label: Some("light bind group"),
+            layout: &light_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: light_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: light_bvh_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: light_selection_buffer.as_entire_binding(),
+                }
+            ],
+        }
+    );
+    (light_bind_group, light_bind_group_layout)
+}
+
+fn create_scene_bind_group(device: &Device, queue: &Queue, scene: &Scene)
+    -> (wgpu::BindGroup, wgpu::BindGroupLayout) {
+    let sphere_buffer = create_storage_buffer(
+        device, "Sphere storage buffer", &scene.spheres);
+    let materials_buffer = create_storage_buffer(
+        device, "Materials storage buffer", &scene.materials);
+    let quad_buffer = create_storage_buffer(
+        device, "Quad storage buffer", &scene.quads);
+    let plane_buffer = create_storage_buffer(
+        device, "Plane storage buffer", &scene.planes);
+    let box_buffer = create_storage_buffer(
+        device, "Box storage buffer", &scene.boxes);
+    let shape_buffer = create_storage_buffer(
+        device, "Shape storage buffer", &scene.shapes);
+    let texture_buffer = create_storage_buffer(
+        device, "Texture storage buffer", &scene.textures);
+    let triangle_buffer = create_storage_buffer(
+        device, "Triangle storage buffer", &scene.triangles);
+    let image_array_view = create_image_array(device, queue, &scene.images);
+    // the global fog goes first, and is an empty medium when the scene has none
+    let media: Vec<Medium> = std::iter::once(scene.fog.unwrap_or(Medium::vacuum()))
+        .chain(scene.media.iter().copied())
+        .collect();
+    let media_buffer = create_storage_buffer(
+        device, "Media storage buffer", &media);
+    let background_buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("background uniform buffer"),
+        contents: bytemuck::cast_slice(&[GPUBackground::new(scene.background)]),
+        usage: BufferUsages::UNIFORM,
     });
 
-    let materials = &scene.materials;
-    let materials_buffer = device.create_buffer_init(&BufferInitDescriptor {
-        label: Some("Materials storage buffer"),
-        contents: bytemuck::cast_slice(materials.as_slice()),
-        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+    let texture_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("texture sampler"),
+        address_mode_u: wgpu::AddressMode::Repeat,
+        address_mode_v: wgpu::AddressMode::Repeat,
+        address_mode_w: wgpu::AddressMode::Repeat,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        ..Default::default()
     });
 
     let scene_bind_group_layout = device.create_bind_group_layout(
         &BindGroupLayoutDescriptor {
             label: Some("scene bind group layout"),
             entries: &[
+                storage_buffer_layout_entry(0),
+                storage_buffer_layout_entry(1),
+                storage_buffer_layout_entry(2),
+                storage_buffer_layout_entry(3),
+                storage_buffer_layout_entry(4),
+                storage_buffer_layout_entry(5),
+                storage_buffer_layout_entry(6),
                 BindGroupLayoutEntry {
-                    binding: 0,
+                    binding: 7,
                     visibility: ShaderStages::COMPUTE,
-                    ty: BindingType::Buffer {
-                        ty: BufferBindingType::Storage { read_only: true },
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
+                    ty: BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float {
+                            filterable: true,
+                        },
+                        view_dimension: TextureViewDimension::D2Array,
+                        multisampled: false,
                     },
                     count: None,
                 },
                 BindGroupLayoutEntry {
-                    binding: 1,
+                    binding: 8,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Sampler(
+                        wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                storage_buffer_layout_entry(9),
+                storage_buffer_layout_entry(10),
+                BindGroupLayoutEntry {
+                    binding: 11,
                     visibility: ShaderStages::COMPUTE,
                     ty: BindingType::Buffer {
-                        ty: BufferBindingType::Storage { read_only: true },
+                        ty: BufferBindingType::Uniform,
                         has_dynamic_offset: false,
                         min_binding_size: None,
                     },
                     count: None,
-                }
+                },
             ],
         }
     );
@@ -381,6 +650,46 @@ fn create_scene_bind_group(device: &Device, scene: &Scene)
                 BindGroupEntry {
                     binding: 1,
                     resource: materials_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: quad_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: plane_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: box_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 5,
+                    resource: shape_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 6,
+                    resource: texture_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 7,
+                    resource: wgpu::BindingResource::TextureView(&image_array_view),
+                },
+                BindGroupEntry {
+                    binding: 8,
+                    resource: wgpu::BindingResource::Sampler(&texture_sampler),
+                },
+                BindGroupEntry {
+                    binding: 9,
+                    resource: media_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 10,
+                    resource: triangle_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 11,
+                    resource: background_buffer.as_entire_binding(),
                 }
             ],
         }
@@ -388,14 +697,111 @@ fn create_scene_bind_group(device: &Device, scene: &Scene)
     (scene_bind_group, scene_bind_group_layout)
 }
 
+// all image textures share one texture array, so every image is resized to the size of
+// the largest one; unused layers are white
+fn create_image_array(device: &Device, queue: &Queue, images: &[RgbaImage]) -> TextureView {
+    let (width, height) = images.iter()
+        .fold((1, 1), |(w, h), image| (w.max(image.width()), h.max(image.height())));
+    // the GL backend picks a texture's target when it is created and treats a single
+    // layer texture as plain 2D, so the array always gets at least two layers
+    let layer_count = images.len().max(2) as u32;
+
+    let mut texels = Vec::<u8>::with_capacity((width * height * 4 * layer_count) as usize);
+    for image in images {
+        if image.dimensions() == (width, height) {
+            texels.extend_from_slice(image.as_raw());
+        } else {
+            let resized = imageops::resize(image, width, height, imageops::FilterType::Triangle);
+            texels.extend_from_slice(resized.as_raw());
+        }
+    }
+    texels.resize((width * height * 4 * layer_count) as usize, 255u8);
+
+    let image_array = device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: Some("image texture array"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: layer_count,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        wgpu::util::TextureDataOrder::LayerMajor,
+        &texels,
+    );
+
+    // a single layer would default to a plain 2D view, so the dimension is explicit
+    image_array.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("image texture array view"),
+        dimension: Some(TextureViewDimension::D2Array),
+        ..Default::default()
+    })
+}
+
+// wgpu does not allow zero sized bindings, so an empty list is uploaded as a single
+// zeroed element; the kernel never reaches it (BVH) or it cannot be hit (planes)
+fn create_storage_buffer<T: bytemuck::Pod>(device: &Device, label: &str, data: &[T]) -> Buffer {
+    let placeholder = [T::zeroed()];
+    let contents = if data.is_empty() { &placeholder[..] } else { data };
+    device.create_buffer_init(&BufferInitDescriptor {
+        label: Some(label),
+        contents: bytemuck::cast_slice(contents),
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+    })
+}
+
+fn storage_buffer_layout_entry(binding: u32) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::COMPUTE,
+        ty: BindingType::Buffer {
+            ty: BufferBindingType::Storage { read_only: true },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+pub(crate) fn create_blue_noise_view(device: &Device, queue: &Queue) -> TextureView {
+    let blue_noise_mask = blue_noise_mask();
+    let blue_noise_texture = device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: Some("blue noise mask"),
+            size: wgpu::Extent3d {
+                width: blue_noise_mask.size(),
+                height: blue_noise_mask.size(),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        wgpu::util::TextureDataOrder::LayerMajor,
+        bytemuck::cast_slice(blue_noise_mask.values()),
+    );
+    blue_noise_texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
 fn create_parameters_bind_group(device: &Device,
                                 queue: &Queue,
                                 render_parameters: &RenderParameters)
-    -> (wgpu::BindGroup, wgpu::BindGroupLayout, Buffer, Buffer) {
+    -> (wgpu::BindGroup, wgpu::BindGroupLayout, Buffer, Buffer, Buffer) {
     // initialize the camera buffer
     let camera_desc = wgpu::BufferDescriptor {
         label: Some("camera uniform buffer"),
-        size: 128,
+        size: size_of::<GPUCamera>() as u64,
         usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
         mapped_at_creation: false,
     };
@@ -406,7 +812,7 @@ fn create_parameters_bind_group(device: &Device,
     // initialize the sampling_parameters buffer
     let sampling_param_desc = wgpu::BufferDescriptor {
         label: Some("sampling parameters uniform buffer"),
-        size: 32,
+        size: size_of::<GPUSamplingParameters>() as u64,
         usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
         mapped_at_creation: false,
     };
@@ -418,6 +824,40 @@ fn create_parameters_bind_group(device: &Device,
                        0,
                        bytemuck::cast_slice(&[sampling_parameters]));
 
+    // the blue noise sampler rotates its sequence by this mask, tiled over the image
+    let blue_noise_view = create_blue_noise_view(device, queue);
+
+    // pixel offsets are drawn from the filter's cumulative distribution, a single row
+    let filter_table = render_parameters.sampling_parameters.filter.sampling_table();
+    let filter_table_texture = device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: Some("filter sampling table"),
+            size: wgpu::Extent3d {
+                width: FILTER_TABLE_SIZE as u32 + 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        wgpu::util::TextureDataOrder::LayerMajor,
+        bytemuck::cast_slice(&filter_table),
+    );
+    let filter_table_view = filter_table_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    // the pixel to autofocus on goes in and the distance to it comes out
+    let focus_pick_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("focus pick storage buffer"),
+        size: 16,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+
     let parameters_bind_group_layout = device.create_bind_group_layout(
         &BindGroupLayoutDescriptor {
             label: Some("parameters bind group layout"),
@@ -441,6 +881,36 @@ fn create_parameters_bind_group(device: &Device,
                         min_binding_size: None,
                     },
                     count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
                 }
             ],
         }
@@ -458,19 +928,86 @@ fn create_parameters_bind_group(device: &Device,
                 BindGroupEntry {
                     binding: 1,
                     resource: sampling_parameters_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&blue_noise_view),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&filter_table_view),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: focus_pick_buffer.as_entire_binding(),
                 }
             ],
         }
     );
 
-    (parameters_bind_group, parameters_bind_group_layout, camera_buffer, sampling_parameters_buffer)
+    (parameters_bind_group, parameters_bind_group_layout, camera_buffer, sampling_parameters_buffer,
+     focus_pick_buffer)
 }
 
-fn create_display_pipeline(
+fn create_shader_module(device: &Device, label: &str, shader: &PreprocessedShader)
+    -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(shader.source.as_str().into()),
+    })
+}
+
+// runs create, which builds shader modules and pipelines, and returns the first error it raised
+fn catch_shader_errors<T>(device: &Device, create: impl FnOnce() -> T) -> Result<T, String> {
+    // GL reports some shader errors when it translates them, as internal errors
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    device.push_error_scope(wgpu::ErrorFilter::Internal);
+    let created = create();
+    let internal_error = pollster::block_on(device.pop_error_scope());
+    let validation_error = pollster::block_on(device.pop_error_scope());
+    match validation_error.or(internal_error) {
+        Some(error) => Err(error.to_string()),
+        None => Ok(created),
+    }
+}
+
+fn create_ray_tracer_pipelines(device: &Device,
+                               layout: &wgpu::PipelineLayout,
+                               shader: &wgpu::ShaderModule)
+    -> (wgpu::ComputePipeline, wgpu::ComputePipeline) {
+    let ray_tracer_pipeline = device.create_compute_pipeline(
+        &wgpu::ComputePipelineDescriptor {
+            label: Some("ray tracer pipeline"),
+            layout: Some(layout),
+            module: shader,
+            entry_point: "main",
+            compilation_options: Default::default(),
+            // PipelineCompilationOptions {
+            //     constants: None, //&id,
+            //     zero_initialize_workgroup_memory: false,
+            //     vertex_pulling_transform: false,
+            // }, // Default::default(),
+            cache: None,
+        }
+    );
+    // traces a single primary ray for autofocus
+    let focus_pick_pipeline = device.create_compute_pipeline(
+        &wgpu::ComputePipelineDescriptor {
+            label: Some("focus pick pipeline"),
+            layout: Some(layout),
+            module: shader,
+            entry_point: "pickFocus",
+            compilation_options: Default::default(),
+            cache: None,
+        }
+    );
+    (ray_tracer_pipeline, focus_pick_pipeline)
+}
+
+fn create_display_bind_group(
     device: &Device,
-    surface_config_format: TextureFormat,
     image_buffer_view: &TextureView)
-    -> (wgpu::BindGroup, RenderPipeline) {
+    -> (wgpu::BindGroup, wgpu::PipelineLayout) {
 
     let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
         label: Some("Sampler"),
@@ -527,10 +1064,6 @@ fn create_display_pipeline(
         }
     );
 
-    let shader = device.create_shader_module(
-        wgpu::include_wgsl!("../shaders/screen_shader.wgsl")
-    );
-
     let render_pipeline_layout =
         device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Render pipeline layout"),
@@ -538,17 +1071,26 @@ fn create_display_pipeline(
             push_constant_ranges: &[],
         });
 
-    let rp = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+    (render_bind_group, render_pipeline_layout)
+}
+
+fn create_display_pipeline(
+    device: &Device,
+    render_pipeline_layout: &wgpu::PipelineLayout,
+    surface_config_format: TextureFormat,
+    shader: &wgpu::ShaderModule)
+    -> RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: Some("Render Pipeline"),
-        layout: Some(&render_pipeline_layout),
+        layout: Some(render_pipeline_layout),
         vertex: wgpu::VertexState {
-            module: &shader,
+            module: shader,
             entry_point: "vs",
             compilation_options: Default::default(),
             buffers: &[],
         },
         fragment: Some(wgpu::FragmentState {
-            module: &shader,
+            module: shader,
             entry_point: "fs",
             compilation_options: Default::default(),
             targets: &[Some(wgpu::ColorTargetState {
@@ -574,9 +1116,7 @@ fn create_display_pipeline(
         },
         multiview: None,
         cache: None,
-    });
-
-    (render_bind_group, rp)
+    })
 }
 
 
@@ -0,0 +1,682 @@
+// the CPU side of the kernel's samplers, which produces exactly the numbers the kernel draws
+// so sequences can be inspected and tested without a GPU
+//
+// every sampler but Random hands out numbers by (sample index, dimension): each sample of a
+// pixel starts at dimension 0, the camera ray takes the first CAMERA_DIMENSIONS and every
+// bounce starts BOUNCE_DIMENSIONS further on, so the same decision at the same depth always
+// reads the same dimension of the sequence. a camera ray or bounce that draws more than its
+// share carries on with the PCG stream, which keeps it out of the next bounce's dimensions
+use std::sync::OnceLock;
+
+// how the kernel draws its random numbers
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Sampler {
+    // a PCG stream per pixel
+    Random = 0,
+    // Sobol points with hash based Owen scrambling, shuffled in groups of four dimensions
+    Sobol = 1,
+    // the R2 sequence, rotated per pixel by a tiled blue noise mask so errors look like
+    // high frequency noise
+    BlueNoise = 2,
+    // the R2 sequence, rotated per pixel at random
+    R2 = 3,
+    // the Halton sequence, scrambled per pixel
+    Halton = 4,
+}
+
+pub const CAMERA_DIMENSIONS: u32 = 8;
+pub const BOUNCE_DIMENSIONS: u32 = 32;
+pub const BLUE_NOISE_SIZE: u32 = 64;
+
+// the fractional parts of the inverse plastic number and its square, in 0.32 fixed point
+const R2_ALPHAS: [u32; 2] = [3242174889, 2447445414];
+const HALTON_PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53,
+    59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107, 109, 113, 127, 131,
+];
+
+// a square of values in [0, 1) where similar values lie far apart, made with the
+// void-and-cluster method; it tiles without seams
+pub struct BlueNoiseMask {
+    size: u32,
+    values: Vec<f32>,
+}
+
+impl BlueNoiseMask {
+    pub fn new(size: u32) -> Self {
+        let count = (size * size) as usize;
+        let mut energy = VoidAndCluster::new(size);
+
+        // start from a sparse random pattern and even it out by moving the point in the
+        // tightest cluster into the largest void, until that would put it straight back
+        let mut pattern: Vec<bool> = (0..count as u32)
+            .map(|idx| jenkins_hash(idx ^ 0x5bd1e995).is_multiple_of(10))
+            .collect();
+        for (idx, &set) in pattern.iter().enumerate() {
+            if set {
+                energy.splat(idx, 1.0);
+            }
+        }
+        loop {
+            let cluster = energy.tightest_cluster(&pattern);
+            pattern[cluster] = false;
+            energy.splat(cluster, -1.0);
+            let void = energy.largest_void(&pattern);
+            pattern[void] = true;
+            energy.splat(void, 1.0);
+            if void == cluster {
+                break;
+            }
+        }
+
+        // rank the initial points by taking clusters away, then the rest by filling voids
+        let mut ranks = vec![0u32; count];
+        let initial_count = pattern.iter().filter(|&&set| set).count();
+        let mut removal = energy.clone();
+        let mut remaining = pattern.clone();
+        for rank in (0..initial_count).rev() {
+            let cluster = removal.tightest_cluster(&remaining);
+            remaining[cluster] = false;
+            removal.splat(cluster, -1.0);
+            ranks[cluster] = rank as u32;
+        }
+        for rank in initial_count..count {
+            let void = energy.largest_void(&pattern);
+            pattern[void] = true;
+            energy.splat(void, 1.0);
+            ranks[void] = rank as u32;
+        }
+
+        Self {
+            size,
+            values: ranks.iter().map(|&rank| rank as f32 / count as f32).collect(),
+        }
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    // row by row, as the kernel's texture is laid out
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    pub fn value(&self, x: u32, y: u32) -> f32 {
+        self.values[((y % self.size) * self.size + x % self.size) as usize]
+    }
+}
+
+// void-and-cluster takes a while, so every renderer shares the one mask
+pub fn blue_noise_mask() -> &'static BlueNoiseMask {
+    static MASK: OnceLock<BlueNoiseMask> = OnceLock::new();
+    MASK.get_or_init(|| BlueNoiseMask::new(BLUE_NOISE_SIZE))
+}
+
+// the energy of every cell of the mask: a gaussian around each set point, wrapping at the edges
+#[derive(Clone)]
+struct VoidAndCluster {
+    size: u32,
+    kernel: Vec<f32>,
+    energy: Vec<f32>,
+}
+
+impl VoidAndCluster {
+    fn new(size: u32) -> Self {
+        let sigma = 1.5f32;
+        let wrapped = |d: u32| d.min(size - d) as f32;
+        let kernel = (0..size * size)
+            .map(|idx| {
+                let dx = wrapped(idx % size);
+                let dy = wrapped(idx / size);
+                (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp()
+            })
+            .collect();
+        Self { size, kernel, energy: vec![0.0; (size * size) as usize] }
+    }
+
+    fn splat(&mut self, idx: usize, sign: f32) {
+        let (x0, y0) = (idx as u32 % self.size, idx as u32 / self.size);
+        for (cell, energy) in self.energy.iter_mut().enumerate() {
+            let dx = (cell as u32 % self.size + self.size - x0) % self.size;
+            let dy = (cell as u32 / self.size + self.size - y0) % self.size;
+            *energy += sign * self.kernel[(dy * self.size + dx) as usize];
+        }
+    }
+
+    fn tightest_cluster(&self, pattern: &[bool]) -> usize {
+        self.extreme(pattern, true, |a, b| a > b)
+    }
+
+    fn largest_void(&self, pattern: &[bool]) -> usize {
+        self.extreme(pattern, false, |a, b| a < b)
+    }
+
+    fn extreme(&self, pattern: &[bool], set: bool, better: impl Fn(f32, f32) -> bool) -> usize {
+        let mut best = usize::MAX;
+        for (idx, &energy) in self.energy.iter().enumerate() {
+            if pattern[idx] == set && (best == usize::MAX || better(energy, self.energy[best])) {
+                best = idx;
+            }
+        }
+        best
+    }
+}
+
+// the numbers one pixel of the kernel draws, sample after sample
+pub struct PixelSampler<'a> {
+    sampler: Sampler,
+    pixel: (u32, u32),
+    seed: u32,
+    // where the blue noise mask is read for this frame
+    mask_shift: (u32, u32),
+    // the PCG state, which carries on from one sample to the next
+    state: u32,
+    sample_index: u32,
+    dimension: u32,
+    // the first dimension past the camera ray's or the current bounce's share
+    dimension_limit: u32,
+    mask: &'a BlueNoiseMask,
+}
+
+impl<'a> PixelSampler<'a> {
+    // frame_seed is the value frame_seed() gives for the render, as the kernel receives it
+    pub fn new(sampler: Sampler, pixel: (u32, u32), resolution: (u32, u32), frame_seed: u32,
+               mask: &'a BlueNoiseMask) -> Self {
+        let seed = init_rng(pixel, resolution, frame_seed);
+        let mask_shift = blue_noise_shift(frame_seed);
+        Self {
+            sampler,
+            pixel,
+            seed,
+            mask_shift,
+            state: seed,
+            sample_index: 0,
+            dimension: 0,
+            dimension_limit: 0,
+            mask,
+        }
+    }
+
+    pub fn start_sample(&mut self, sample_index: u32) {
+        self.sample_index = sample_index;
+        self.dimension = 0;
+        self.dimension_limit = CAMERA_DIMENSIONS;
+    }
+
+    pub fn start_bounce(&mut self, bounce: u32) {
+        self.dimension = CAMERA_DIMENSIONS + bounce * BOUNCE_DIMENSIONS;
+        self.dimension_limit = self.dimension + BOUNCE_DIMENSIONS;
+    }
+
+    pub fn next_float(&mut self) -> f32 {
+        let dimension = self.dimension;
+        if dimension >= self.dimension_limit {
+            return self.next_random();
+        }
+        self.dimension += 1;
+        match self.sampler {
+            Sampler::Random => self.next_random(),
+            Sampler::Sobol => sobol_sample(self.sample_index, dimension, self.seed),
+            Sampler::BlueNoise => {
+                let (offset_x, offset_y) = blue_noise_offset(dimension, self.mask.size());
+                let rotation = self.mask.value(self.pixel.0 + offset_x + self.mask_shift.0,
+                                               self.pixel.1 + offset_y + self.mask_shift.1);
+                r2_sample(self.sample_index, dimension, (rotation * 16777216.0) as u32 * 256)
+            }
+            Sampler::R2 => {
+                r2_sample(self.sample_index, dimension, hash_combine(self.seed, dimension))
+            }
+            Sampler::Halton => halton_sample(self.sample_index, dimension, self.seed),
+        }
+    }
+
+    fn next_random(&mut self) -> f32 {
+        self.state = pcg(self.state);
+        self.state as f32 / u32::MAX as f32
+    }
+}
+
+// the unscrambled Sobol sequence, in 0.32 fixed point; dimension 0 is the van der Corput
+// sequence and the next three follow Joe and Kuo's primitive polynomials
+pub fn sobol(index: u32, dimension: u32) -> u32 {
+    if dimension == 0 {
+        return index.reverse_bits();
+    }
+    // the degree, coefficients and initial direction numbers of each polynomial
+    let (degree, coefficients, initial) = match dimension {
+        1 => (1, 0, [1, 0, 0]),
+        2 => (2, 1, [1, 3, 0]),
+        _ => (3, 1, [1, 3, 1]),
+    };
+
+    // the direction numbers are built bit by bit, keeping the last three
+    let mut window = [0u32; 3];
+    let mut x = 0u32;
+    for bit in 0..32 {
+        if index >> bit == 0 {
+            break;
+        }
+        let m = if bit < degree {
+            initial[bit as usize]
+        } else {
+            let older = window[degree as usize - 1];
+            let mut m = older ^ (older << degree);
+            for k in 1..degree {
+                if (coefficients >> (degree - 1 - k)) & 1 == 1 {
+                    m ^= window[k as usize - 1] << k;
+                }
+            }
+            m
+        };
+        if (index >> bit) & 1 == 1 {
+            x ^= m << (31 - bit);
+        }
+        window = [m, window[0], window[1]];
+    }
+    x
+}
+
+// Burley's shuffled, Owen scrambled Sobol points: every group of four dimensions is its own
+// Sobol set with the sample order shuffled per pixel and group
+pub fn sobol_sample(index: u32, dimension: u32, seed: u32) -> f32 {
+    let group_seed = hash_combine(seed, dimension / 4);
+    let shuffled = nested_uniform_scramble(index, group_seed);
+    let component = dimension % 4;
+    let x = nested_uniform_scramble(sobol(shuffled, component), hash_combine(group_seed, component + 1));
+    to_unit_float(x)
+}
+
+// pairs of dimensions take the two components of the R2 sequence, rotated by a 0.32 fixed
+// point offset
+pub fn r2_sample(index: u32, dimension: u32, rotation: u32) -> f32 {
+    let x = 0x80000000u32
+        .wrapping_add(index.wrapping_mul(R2_ALPHAS[(dimension % 2) as usize]))
+        .wrapping_add(rotation);
+    to_unit_float(x)
+}
+
+// the radical inverse of index in a prime base, with each digit put through its own random
+// linear permutation, which keeps the first few samples of the large bases apart
+pub fn halton_sample(index: u32, dimension: u32, seed: u32) -> f32 {
+    let base = HALTON_PRIMES[(dimension % 32) as usize];
+    let dimension_seed = hash_combine(seed, dimension);
+    let inverse_base = 1.0 / base as f32;
+    let mut factor = inverse_base;
+    let mut radical_inverse = 0.0f32;
+    let mut i = index;
+    let mut level = 0;
+    while factor > 1e-7 {
+        let digit_seed = hash_combine(dimension_seed, level);
+        let scale = 1 + digit_seed % (base - 1);
+        let shift = jenkins_hash(digit_seed) % base;
+        radical_inverse += ((scale * (i % base) + shift) % base) as f32 * factor;
+        i /= base;
+        factor *= inverse_base;
+        level += 1;
+    }
+    radical_inverse.min(0.99999994)
+}
+
+// where the mask is read for a dimension, so each dimension sees it shifted
+pub fn blue_noise_offset(dimension: u32, size: u32) -> (u32, u32) {
+    let step = dimension + 1;
+    ((step.wrapping_mul(R2_ALPHAS[0]) >> 16) % size, (step.wrapping_mul(R2_ALPHAS[1]) >> 16) % size)
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    // Laine and Karras' permutation, which scrambles each bit by the bits below it; between the
+    // two reversals that is Owen scrambling
+    let mut x = x.reverse_bits().wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x.reverse_bits()
+}
+
+fn hash_combine(seed: u32, value: u32) -> u32 {
+    seed ^ jenkins_hash(value)
+        .wrapping_add(0x9e3779b9)
+        .wrapping_add(seed << 6)
+        .wrapping_add(seed >> 2)
+}
+
+fn to_unit_float(x: u32) -> f32 {
+    // the top 24 bits, which is all an f32 below one can hold
+    (x >> 8) as f32 / 16777216.0
+}
+
+// the seed every pixel of a frame starts from: the render seed folded into 32 bits and hashed
+// with the frame, which is what the kernel is given
+pub fn frame_seed(seed: u64, frame: u32) -> u32 {
+    hash_combine(jenkins_hash(seed as u32 ^ (seed >> 32) as u32), frame)
+}
+
+// the whole mask moves with the frame seed, so frames don't share their blue noise
+pub fn blue_noise_shift(frame_seed: u32) -> (u32, u32) {
+    (frame_seed & 0xffff, frame_seed >> 16)
+}
+
+fn init_rng(pixel: (u32, u32), resolution: (u32, u32), frame_seed: u32) -> u32 {
+    let pixel_index = pixel.0.wrapping_add(pixel.1.wrapping_mul(resolution.0));
+    jenkins_hash(hash_combine(frame_seed, pixel_index))
+}
+
+fn pcg(state: u32) -> u32 {
+    let old_state = state.wrapping_add(747796405).wrapping_add(2891336453);
+    let word = ((old_state >> ((old_state >> 28) + 4)) ^ old_state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+fn jenkins_hash(input: u32) -> u32 {
+    let mut x = input;
+    x = x.wrapping_add(x << 10);
+    x ^= x >> 6;
+    x = x.wrapping_add(x << 3);
+    x ^= x >> 11;
+    x = x.wrapping_add(x << 15);
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wgpu::util::DeviceExt;
+    use crate::app::SamplingParameters;
+    use crate::gpu_structs::get_gpu_sampling_params;
+    use crate::headless::request_device;
+    use crate::raytracer::create_blue_noise_view;
+    use crate::shader_preprocessor::{embedded_shader, preprocess};
+
+    const SAMPLERS: [Sampler; 5] =
+        [Sampler::Random, Sampler::Sobol, Sampler::BlueNoise, Sampler::R2, Sampler::Halton];
+
+    // every box of 2^-a by 2^-b with a + b = log2(points.len()) holds exactly one point
+    fn is_elementary_net(points: &[(f32, f32)]) -> bool {
+        let bits = points.len().trailing_zeros();
+        (0..=bits).all(|a| {
+            let mut counts = vec![0u32; points.len()];
+            for &(x, y) in points {
+                let column = (x * (1u32 << a) as f32) as usize;
+                let row = (y * (1u32 << (bits - a)) as f32) as usize;
+                counts[(row << a) + column] += 1;
+            }
+            counts.iter().all(|&count| count == 1)
+        })
+    }
+
+    #[test]
+    fn sobol_pairs_are_stratified_in_power_of_two_prefixes() {
+        for seed in [0, 1, 0xdeadbeef] {
+            for first_dimension in [0, 4] {
+                for bits in 0..=8 {
+                    let points: Vec<(f32, f32)> = (0..1u32 << bits)
+                        .map(|index| (sobol_sample(index, first_dimension, seed),
+                                      sobol_sample(index, first_dimension + 1, seed)))
+                        .collect();
+                    assert!(is_elementary_net(&points),
+                            "the first {} samples of dimensions {first_dimension} and {} with \
+                             seed {seed} are not stratified", points.len(), first_dimension + 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn r2_and_halton_stay_in_the_unit_interval() {
+        let indices = (0..1024).chain([u32::MAX / 3, u32::MAX - 1, u32::MAX]);
+        for index in indices {
+            for dimension in 0..64 {
+                for seed in [0, 1, u32::MAX] {
+                    let r2 = r2_sample(index, dimension, hash_combine(seed, dimension));
+                    let halton = halton_sample(index, dimension, seed);
+                    assert!((0.0..1.0).contains(&r2), "R2 gave {r2}");
+                    assert!((0.0..1.0).contains(&halton), "Halton gave {halton}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn blue_noise_mask_ranks_every_cell_once() {
+        let mask = blue_noise_mask();
+        let count = mask.values().len();
+        let mut ranks: Vec<usize> = mask.values().iter()
+            .map(|&value| (value * count as f32) as usize)
+            .collect();
+        ranks.sort();
+        assert!(ranks.iter().copied().eq(0..count));
+        for dimension in 0..1024 {
+            let (x, y) = blue_noise_offset(dimension, mask.size());
+            assert!(x < mask.size() && y < mask.size());
+        }
+    }
+
+    #[test]
+    fn long_bounces_leave_the_next_bounce_alone() {
+        for sampler in SAMPLERS.into_iter().filter(|&sampler| sampler != Sampler::Random) {
+            let mut long = PixelSampler::new(sampler, (3, 2), (8, 8), 5, blue_noise_mask());
+            let mut short = PixelSampler::new(sampler, (3, 2), (8, 8), 5, blue_noise_mask());
+            long.start_sample(1);
+            long.start_bounce(0);
+            for _ in 0..2 * BOUNCE_DIMENSIONS {
+                long.next_float();
+            }
+            long.start_bounce(1);
+            short.start_sample(1);
+            short.start_bounce(1);
+            assert_eq!(long.next_float(), short.next_float(), "{sampler:?}");
+        }
+    }
+
+    // the test kernel draws with the kernel's own sampling code for every pixel of a small
+    // image: the PCG seed, then for each sample a few more numbers than the camera ray and each
+    // bounce have dimensions, so the fall back to the PCG stream is covered too
+    const RESOLUTION: (u32, u32) = (5, 3);
+    const SAMPLES: u32 = 3;
+    const BOUNCES: u32 = 2;
+    const CAMERA_DRAWS: u32 = CAMERA_DIMENSIONS + 2;
+    const BOUNCE_DRAWS: u32 = BOUNCE_DIMENSIONS + 4;
+    const DRAWS_PER_PIXEL: u32 = 1 + SAMPLES * (CAMERA_DRAWS + BOUNCES * BOUNCE_DRAWS);
+
+    const SAMPLER_TEST_KERNEL: &str = r#"
+#include "sampling.wgsl"
+
+@group(3) @binding(5) var<storage, read_write> drawn: array<u32>;
+
+fn draw(count: u32, state: ptr<function, u32>, out: ptr<function, u32>) {
+    for (var i: u32 = 0u; i < count; i++) {
+        drawn[*out] = bitcast<u32>(rngNextFloat(state));
+        *out += 1u;
+    }
+}
+
+@compute @workgroup_size(1, 1, 1)
+fn drawSamples(@builtin(global_invocation_id) id: vec3u) {
+    var state: u32 = startPixel(id.xy, RESOLUTION);
+    var out: u32 = (id.y * RESOLUTION.x + id.x) * DRAWS_PER_PIXEL;
+    drawn[out] = state;
+    out += 1u;
+    for (var sample: u32 = 0u; sample < SAMPLES; sample++) {
+        startSample(sample);
+        draw(CAMERA_DRAWS, &state, &out);
+        for (var bounce: u32 = 0u; bounce < BOUNCES; bounce++) {
+            startBounce(bounce);
+            draw(BOUNCE_DRAWS, &state, &out);
+        }
+    }
+}
+"#;
+
+    fn cpu_draws(sampler: Sampler, frame_seed: u32) -> Vec<u32> {
+        let mut drawn = Vec::new();
+        for y in 0..RESOLUTION.1 {
+            for x in 0..RESOLUTION.0 {
+                drawn.push(init_rng((x, y), RESOLUTION, frame_seed));
+                let mut pixel = PixelSampler::new(sampler, (x, y), RESOLUTION, frame_seed,
+                                                  blue_noise_mask());
+                for sample in 0..SAMPLES {
+                    pixel.start_sample(sample);
+                    drawn.extend((0..CAMERA_DRAWS).map(|_| pixel.next_float().to_bits()));
+                    for bounce in 0..BOUNCES {
+                        pixel.start_bounce(bounce);
+                        drawn.extend((0..BOUNCE_DRAWS).map(|_| pixel.next_float().to_bits()));
+                    }
+                }
+            }
+        }
+        drawn
+    }
+
+    fn gpu_draws(device: &wgpu::Device, queue: &wgpu::Queue,
+                 sampling_parameters: &SamplingParameters) -> Vec<u32> {
+        let constants = format!(
+            "const RESOLUTION = vec2u({}u, {}u);\nconst SAMPLES = {SAMPLES}u;\n\
+             const BOUNCES = {BOUNCES}u;\nconst CAMERA_DRAWS = {CAMERA_DRAWS}u;\n\
+             const BOUNCE_DRAWS = {BOUNCE_DRAWS}u;\nconst DRAWS_PER_PIXEL = {DRAWS_PER_PIXEL}u;\n",
+            RESOLUTION.0, RESOLUTION.1);
+        let shader = preprocess("sampler_test.wgsl", &[], |name| match name {
+            "sampler_test.wgsl" => Ok(constants.clone() + SAMPLER_TEST_KERNEL),
+            _ => embedded_shader(name),
+        }).unwrap();
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("sampler test kernel"),
+            source: wgpu::ShaderSource::Wgsl(shader.source.into()),
+        });
+        // the test kernel only reads group 3, but the groups before it still need layouts;
+        // the layout is spelled out as a derived one would want a filterable mask
+        let compute_entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty,
+            count: None,
+        };
+        let empty_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[],
+        });
+        let sampling_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                compute_entry(1, wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                }),
+                compute_entry(2, wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                }),
+                compute_entry(5, wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                }),
+            ],
+        });
+        let layouts = [&empty_layout, &empty_layout, &empty_layout, &sampling_layout];
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &layouts,
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("sampler test pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &module,
+            entry_point: "drawSamples",
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let parameters = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("sampler test parameters"),
+            contents: bytemuck::cast_slice(&[get_gpu_sampling_params(sampling_parameters)]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let size = (RESOLUTION.0 * RESOLUTION.1 * DRAWS_PER_PIXEL) as u64 * 4;
+        let drawn = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("sampler test draws"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("sampler test readback"),
+            size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let blue_noise_view = create_blue_noise_view(device, queue);
+        let empty_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &empty_layout,
+            entries: &[],
+        });
+        let sampling_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &sampling_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 1, resource: parameters.as_entire_binding() },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&blue_noise_view),
+                },
+                wgpu::BindGroupEntry { binding: 5, resource: drawn.as_entire_binding() },
+            ],
+        });
+        let bind_groups = [&empty_group, &empty_group, &empty_group, &sampling_group];
+
+        let mut encoder = device.create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&Default::default());
+            pass.set_pipeline(&pipeline);
+            for (group, bind_group) in bind_groups.into_iter().enumerate() {
+                pass.set_bind_group(group as u32, bind_group, &[]);
+            }
+            pass.dispatch_workgroups(RESOLUTION.0, RESOLUTION.1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&drawn, 0, &readback, 0, size);
+        queue.submit(Some(encoder.finish()));
+
+        readback.slice(..).map_async(wgpu::MapMode::Read, |_| ());
+        device.poll(wgpu::Maintain::wait()).panic_on_timeout();
+        let values = bytemuck::cast_slice(&readback.slice(..).get_mapped_range()).to_vec();
+        readback.unmap();
+        values
+    }
+
+    #[test]
+    fn cpu_samplers_match_the_kernel() {
+        let Some((device, queue)) = pollster::block_on(request_device()) else {
+            assert!(std::env::var_os("WIW_SKIP_GPU_TESTS").is_some(),
+                    "no GPU adapter to run the kernel's samplers on; set WIW_SKIP_GPU_TESTS=1 \
+                     to skip the tests that need one");
+            return;
+        };
+        for sampler in SAMPLERS {
+            let sampling_parameters = SamplingParameters {
+                sampler,
+                seed: 0x1234_5678_9abc,
+                frame: 3,
+                ..Default::default()
+            };
+            let frame_seed = frame_seed(sampling_parameters.seed, sampling_parameters.frame);
+            let expected = cpu_draws(sampler, frame_seed);
+            let drawn = gpu_draws(&device, &queue, &sampling_parameters);
+            for (i, (&cpu, &gpu)) in expected.iter().zip(&drawn).enumerate() {
+                // the PCG seeds are integers and must agree exactly; the draws are floats, and
+                // the GPU may round a division or a sum differently
+                let agrees = if (i as u32).is_multiple_of(DRAWS_PER_PIXEL) {
+                    cpu == gpu
+                } else {
+                    (f32::from_bits(cpu) - f32::from_bits(gpu)).abs() <= 1e-6
+                };
+                assert!(agrees, "{sampler:?} draw {i} is {} on the CPU but {} in the kernel",
+                        f32::from_bits(cpu), f32::from_bits(gpu));
+            }
+        }
+    }
+}
@@ -1,27 +1,82 @@
+use std::path::Path;
 use glam::{Vec3};
+use image::RgbaImage;
 use crate::material::Material;
-use crate::Sphere;
-use crate::util_funcs::{random_f32, random_range_f32, random_vec3, random_vec3_range};
+use crate::texture::Texture;
+use crate::light::{Light, NO_LIGHT};
+use crate::medium::Medium;
+use crate::{AABox, Plane, Quad, Shape, Sphere, Triangle};
+use crate::primitive::{Primitive, PrimitiveType};
+use crate::util_funcs::{random_f32, random_range_f32, random_vec3, random_vec3_range, seeded_rng};
+
+// what rays that leave the scene see
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Background {
+    // a white to blue gradient from the horizon up
+    Sky,
+    // the same color in every direction; black for a scene lit only by its own lights
+    Color(Vec3),
+}
 
 pub struct Scene {
     pub spheres: Vec<Sphere>,
+    pub quads: Vec<Quad>,
+    pub planes: Vec<Plane>,
+    pub boxes: Vec<AABox>,
+    pub shapes: Vec<Shape>,
+    pub triangles: Vec<Triangle>,
     pub materials: Vec<Material>,
+    pub textures: Vec<Texture>,
+    // the pixels behind image textures; each becomes a layer of the GPU texture array
+    pub images: Vec<RgbaImage>,
+    // the lights sampled directly by shadow rays: point, spot and sun lights are declared
+    // here, while entries for emissive primitives are filled in by update_lights
+    pub lights: Vec<Light>,
+    // the media that spheres, boxes and shapes can be filled with, by index
+    pub media: Vec<Medium>,
+    // a medium filling all the space outside those, with the camera in it
+    pub fog: Option<Medium>,
+    pub background: Background,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Scene {
+    pub fn empty() -> Self {
+        Self {
+            spheres: vec![],
+            quads: vec![],
+            planes: vec![],
+            boxes: vec![],
+            shapes: vec![],
+            triangles: vec![],
+            materials: vec![],
+            textures: vec![],
+            images: vec![],
+            lights: vec![],
+            media: vec![],
+            fog: None,
+            background: Background::Sky,
+        }
+    }
+
     pub fn new() -> Self {
-        let mat_ground = Material::Lambertian(Vec3::new(0.8, 0.8, 0.0));
-        let mat_center = Material::Lambertian(Vec3::new(0.1, 0.2, 0.5));
-        let mat_left = Material::Dielectric(1.50);
-        // let mat_left = Material::Metal(Vec3::new(0.8, 0.8, 0.8), 0.3);
-        let mat_bubble = Material::Dielectric(1.00/1.50);
-        let mat_right = Material::Metal(Vec3::new(0.8, 0.6, 0.2), 1.0);
-
-        let mut materials = vec![mat_ground, mat_center, mat_left, mat_right, mat_bubble];
-
-        let ground = Sphere::new(
-            Vec3::new(0.0, -100.5, -1.0),
-            100.0,
+        let mat_ground = Material::lambertian(Vec3::new(0.8, 0.8, 0.0));
+        let mat_center = Material::lambertian(Vec3::new(0.1, 0.2, 0.5));
+        let mat_left = Material::dielectric(1.50);
+        // let mat_left = Material::metal(Vec3::new(0.8, 0.8, 0.8), 0.3);
+        let mat_bubble = Material::dielectric(1.00/1.50);
+        let mat_right = Material::metal(Vec3::new(0.8, 0.6, 0.2), 1.0);
+
+        let materials = vec![mat_ground, mat_center, mat_left, mat_right, mat_bubble];
+
+        let ground = Plane::new(
+            Vec3::new(0.0, -0.5, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
             0);
         let center = Sphere::new(
             Vec3::new(0.0, 0.0, -1.2),
@@ -40,49 +95,66 @@ impl Scene {
             0.5,
             3);
 
-        let mut spheres = vec![ground, center, right, left, bubble];
+        let spheres = vec![center, right, left, bubble];
+
+        Self { spheres, planes: vec![ground], materials, ..Self::empty() }
+    }
+
+    // the marbles are placed and colored from seed
+    pub fn book_one_final(seed: u64) -> Self {
+        Self::random_marbles(seed, false)
+    }
 
-        Self { spheres, materials }
+    // the book one final scene with the diffuse marbles bouncing upward while
+    // the shutter is open, as at the start of book two
+    pub fn bouncing_spheres(seed: u64) -> Self {
+        Self::random_marbles(seed, true)
     }
 
-    pub fn book_one_final() -> Self {
+    fn random_marbles(seed: u64, bouncing: bool) -> Self {
+        let mut rng = seeded_rng(seed);
         let mut spheres = Vec::<Sphere>::new();
         let mut materials = Vec::<Material>::new();
         // ground
-        let ground_mat = Material::Lambertian(Vec3::new(0.5, 0.5, 0.5));
+        let ground_mat = Material::lambertian(Vec3::new(0.5, 0.5, 0.5));
         materials.push(ground_mat);
 
-        let ground = Sphere::new(
-            Vec3::new(0.0, -1000.0, 0.0),
-            1000.0,
+        let ground = Plane::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
             0);
-        spheres.push(ground);
 
         // random marbles
         for a in  -11 .. 11 {
             for b in -11 .. 11 {
-                let choose_mat = random_f32();
-                let center = Vec3::new(a as f32 + 0.9 * random_f32(), 0.2,
-                                        b as f32 + 0.9 * random_f32());
+                let choose_mat = random_f32(&mut rng);
+                let center = Vec3::new(a as f32 + 0.9 * random_f32(&mut rng), 0.2,
+                                        b as f32 + 0.9 * random_f32(&mut rng));
 
                 if (center - Vec3::new(4.0, 0.2, 0.0)).length() > 0.9 {
 
                     if choose_mat < 0.8 {
                         // diffuse
-                        let albedo = random_vec3() * random_vec3();
-                        let sphere_material = Material::Lambertian(albedo);
+                        let albedo = random_vec3(&mut rng) * random_vec3(&mut rng);
+                        let sphere_material = Material::lambertian(albedo);
                         materials.push(sphere_material);
-                        spheres.push(Sphere::new(center, 0.2, (materials.len() - 1) as u32));
+                        let center1 = if bouncing {
+                            center + Vec3::new(0.0, random_range_f32(&mut rng, 0.0, 0.5), 0.0)
+                        } else {
+                            center
+                        };
+                        spheres.push(Sphere::new_moving(center, center1, 0.2,
+                                                        (materials.len() - 1) as u32));
                     } else if choose_mat < 0.95 {
                         // metal
-                        let albedo = random_vec3_range(0.5, 1.0);
-                        let fuzz = random_range_f32(0.0,0.5);
-                        let sphere_material = Material::Metal(albedo, fuzz);
+                        let albedo = random_vec3_range(&mut rng, 0.5, 1.0);
+                        let fuzz = random_range_f32(&mut rng, 0.0, 0.5);
+                        let sphere_material = Material::metal(albedo, fuzz);
                         materials.push(sphere_material);
                         spheres.push(Sphere::new(center, 0.2, (materials.len() - 1) as u32));
                     } else {
                         // glass
-                        let sphere_material = Material::Dielectric(1.5);
+                        let sphere_material = Material::dielectric(1.5);
                         materials.push(sphere_material);
                         spheres.push(Sphere::new(center, 0.2, (materials.len() - 1) as u32));
                     }
@@ -91,19 +163,125 @@ impl Scene {
         }
 
         // Big spheres
-        let dia_mat = Material::Dielectric(1.50);
+        let dia_mat = Material::dielectric(1.50);
         materials.push(dia_mat);
         spheres.push(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0, (materials.len() - 1) as u32));
 
-        let lamb_mat = Material::Lambertian(Vec3::new(0.4, 0.2, 0.1));
+        let lamb_mat = Material::lambertian(Vec3::new(0.4, 0.2, 0.1));
         materials.push(lamb_mat);
         spheres.push(Sphere::new(Vec3::new(-4.0, 1.0, 0.0), 1.0, (materials.len() - 1) as u32));
 
-        let met_mat = Material::Metal(Vec3::new(0.7, 0.6, 0.5), 0.0);
+        let met_mat = Material::metal(Vec3::new(0.7, 0.6, 0.5), 0.0);
         materials.push(met_mat);
         spheres.push(Sphere::new(Vec3::new(4.0, 1.0, 0.0), 1.0, (materials.len() - 1) as u32));
 
-        Self { spheres, materials }
+        Self { spheres, planes: vec![ground], materials, ..Self::empty() }
     }
 
+    // the textured spheres from book two
+    pub fn checkered_spheres() -> Self {
+        let checker = Texture::checker(0.32, Vec3::new(0.2, 0.3, 0.1), Vec3::splat(0.9));
+        let materials = vec![Material::lambertian(Vec3::ONE).with_albedo_texture(0)];
+        let spheres = vec![
+            Sphere::new(Vec3::new(0.0, -10.0, 0.0), 10.0, 0),
+            Sphere::new(Vec3::new(0.0, 10.0, 0.0), 10.0, 0),
+        ];
+
+        Self { spheres, materials, textures: vec![checker], ..Self::empty() }
+    }
+
+    pub fn perlin_spheres() -> Self {
+        let marble = Texture::noise(4.0, Vec3::ONE);
+        let materials = vec![Material::lambertian(Vec3::ONE).with_albedo_texture(0)];
+        let ground = Plane::new(Vec3::ZERO, Vec3::new(0.0, 1.0, 0.0), 0);
+        let spheres = vec![Sphere::new(Vec3::new(0.0, 2.0, 0.0), 2.0, 0)];
+
+        Self { spheres, planes: vec![ground], materials, textures: vec![marble], ..Self::empty() }
+    }
+
+    // a globe wrapped in the image at path, e.g. an equirectangular earth map
+    pub fn earth(path: &Path) -> image::ImageResult<Self> {
+        let mut scene = Self::empty();
+        let layer = scene.add_image(path)?;
+        scene.textures.push(Texture::image(layer, true));
+        scene.materials.push(Material::lambertian(Vec3::ONE).with_albedo_texture(0));
+        scene.spheres.push(Sphere::new(Vec3::ZERO, 2.0, 0));
+        Ok(scene)
+    }
+
+    // load a PNG or JPEG and return the layer it will occupy in the image array
+    pub fn add_image(&mut self, path: &Path) -> image::ImageResult<u32> {
+        let image = image::open(path)?.into_rgba8();
+        self.images.push(image);
+        Ok((self.images.len() - 1) as u32)
+    }
+
+    // collect every bounded object into the list the BVH is built over;
+    // planes are unbounded and are left out
+    pub fn primitives(&self) -> Vec<Primitive> {
+        let mut primitives = Vec::<Primitive>::with_capacity(
+            self.spheres.len() + self.quads.len() + self.boxes.len() + self.shapes.len() +
+                self.triangles.len());
+        for (idx, sphere) in self.spheres.iter().enumerate() {
+            primitives.push(Primitive::new(PrimitiveType::Sphere, idx, sphere.get_aabb()));
+        }
+        for (idx, quad) in self.quads.iter().enumerate() {
+            primitives.push(Primitive::new(PrimitiveType::Quad, idx, quad.get_aabb()));
+        }
+        for (idx, aabox) in self.boxes.iter().enumerate() {
+            primitives.push(Primitive::new(PrimitiveType::AABox, idx, aabox.get_aabb()));
+        }
+        for (idx, shape) in self.shapes.iter().enumerate() {
+            primitives.push(Primitive::new(PrimitiveType::Shape, idx, shape.get_aabb()));
+        }
+        for (idx, triangle) in self.triangles.iter().enumerate() {
+            primitives.push(Primitive::new(PrimitiveType::Triangle, idx, triangle.get_aabb()));
+        }
+        primitives
+    }
+
+    // rebuild the area lights from the emissive spheres, quads, disks and triangles, and tell
+    // each of them where it sits in the list; other emitters still light the scene, but only
+    // when a bounced ray happens to hit them. punctual lights are kept after the area
+    // lights, with the suns last as the light BVH expects
+    pub fn update_lights(&mut self) {
+        let materials = &self.materials;
+        let is_emissive = |material_idx: u32| materials[material_idx as usize].is_emissive();
+        let mut lights = Vec::<Light>::new();
+
+        for (idx, sphere) in self.spheres.iter_mut().enumerate() {
+            sphere.set_light_idx(NO_LIGHT);
+            if is_emissive(sphere.material_idx()) && !sphere.has_medium() {
+                sphere.set_light_idx(lights.len() as u32);
+                lights.push(Light::sphere(sphere, idx));
+            }
+        }
+        for (idx, quad) in self.quads.iter_mut().enumerate() {
+            quad.set_light_idx(NO_LIGHT);
+            if is_emissive(quad.material_idx()) {
+                quad.set_light_idx(lights.len() as u32);
+                lights.push(Light::quad(quad, idx));
+            }
+        }
+        for (idx, shape) in self.shapes.iter_mut().enumerate() {
+            shape.set_light_idx(NO_LIGHT);
+            if !is_emissive(shape.material_idx()) || shape.has_medium() {
+                continue;
+            }
+            if let Some(light) = Light::disk(shape, idx) {
+                shape.set_light_idx(lights.len() as u32);
+                lights.push(light);
+            }
+        }
+        for (idx, triangle) in self.triangles.iter_mut().enumerate() {
+            triangle.set_light_idx(NO_LIGHT);
+            if is_emissive(triangle.material_idx()) {
+                triangle.set_light_idx(lights.len() as u32);
+                lights.push(Light::triangle(triangle, idx));
+            }
+        }
+        lights.extend(self.lights.iter().filter(|light| light.is_punctual() && !light.is_infinite()));
+        lights.extend(self.lights.iter().filter(|light| light.is_infinite()));
+        self.lights = lights;
+    }
 }
\ No newline at end of file
@@ -0,0 +1,238 @@
+use glam::{Mat4, Vec3};
+use crate::util_funcs::{random_f32, random_range_f32, random_vec3, random_vec3_range, seeded_rng};
+use crate::{AABox, Background, Camera, Material, Medium, Plane, Quad, Scene, SceneGraph,
+            SceneObject, Sphere, Texture, Transform};
+
+// the built-in scenes, each with a camera framing it; whatever a scene draws at random comes
+// from its seed, so benchmarks and tests can name a scene and a seed and get the same image
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SceneGenerator {
+    // the random marbles on the cover of book one
+    BookOneFinal,
+    // the final scene of book two: a field of boxes, volumes, textures and a crowd of spheres
+    BookTwoFinal,
+    CornellBox,
+    // count spheres of random sizes and materials filling a cube, to stress the BVH
+    ManySpheres { count: u32 },
+    // principled spheres in rows of materials and columns of roughness
+    MaterialGrid,
+}
+
+impl SceneGenerator {
+    // the names from_name understands; many_spheres takes an optional count after a colon,
+    // as in many_spheres:100000
+    pub const NAMES: [&'static str; 5] =
+        ["book_one", "book_two", "cornell_box", "many_spheres", "material_grid"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let (name, count) = match name.split_once(':') {
+            Some((name, count)) => (name, Some(count.parse().ok()?)),
+            None => (name, None),
+        };
+        match name {
+            "book_one" => Some(Self::BookOneFinal),
+            "book_two" => Some(Self::BookTwoFinal),
+            "cornell_box" => Some(Self::CornellBox),
+            "many_spheres" => Some(Self::ManySpheres { count: count.unwrap_or(10_000) }),
+            "material_grid" => Some(Self::MaterialGrid),
+            _ => None,
+        }
+    }
+
+    pub fn generate(&self, seed: u64) -> Scene {
+        match *self {
+            Self::BookOneFinal => Scene::book_one_final(seed),
+            Self::BookTwoFinal => book_two_final(seed),
+            Self::CornellBox => cornell_box(),
+            Self::ManySpheres { count } => many_spheres(seed, count),
+            Self::MaterialGrid => material_grid(),
+        }
+    }
+
+    pub fn camera(&self) -> Camera {
+        let mut camera = Camera { defocus_angle: 0.0, ..Camera::default() };
+        match *self {
+            // the default camera is the book one camera
+            Self::BookOneFinal => return Camera::default(),
+            Self::BookTwoFinal => {
+                camera.look_at(Vec3::new(478.0, 278.0, -600.0), Vec3::new(278.0, 278.0, 0.0));
+                camera.vfov = 40.0;
+            }
+            Self::CornellBox => {
+                camera.look_at(Vec3::new(278.0, 278.0, -800.0), Vec3::new(278.0, 278.0, 0.0));
+                camera.vfov = 40.0;
+            }
+            Self::ManySpheres { count } => {
+                let half_side = many_spheres_half_side(count);
+                camera.look_at(Vec3::new(2.0, 1.2, 2.0) * half_side, Vec3::ZERO);
+                camera.vfov = 40.0;
+            }
+            Self::MaterialGrid => {
+                camera.look_at(Vec3::new(0.0, 4.5, 9.0), Vec3::new(0.0, 1.6, 0.0));
+                camera.vfov = 45.0;
+            }
+        }
+        camera
+    }
+}
+
+fn push_material(scene: &mut Scene, material: Material) -> u32 {
+    scene.materials.push(material);
+    (scene.materials.len() - 1) as u32
+}
+
+fn book_two_final(seed: u64) -> Scene {
+    let mut rng = seeded_rng(seed);
+    let mut scene = Scene { background: Background::Color(Vec3::ZERO), ..Scene::empty() };
+
+    // a floor of boxes of random heights
+    let ground = push_material(&mut scene, Material::lambertian(Vec3::new(0.48, 0.83, 0.53)));
+    let boxes_per_side = 20;
+    let width = 100.0;
+    for i in 0..boxes_per_side {
+        for j in 0..boxes_per_side {
+            let corner = Vec3::new(-1000.0 + i as f32 * width, 0.0, -1000.0 + j as f32 * width);
+            let height = random_range_f32(&mut rng, 1.0, 101.0);
+            scene.boxes.push(AABox::new(corner, corner + Vec3::new(width, height, width), ground));
+        }
+    }
+
+    let light = push_material(&mut scene, Material::emissive(Vec3::splat(7.0)));
+    scene.quads.push(Quad::new(Vec3::new(123.0, 554.0, 147.0), Vec3::new(300.0, 0.0, 0.0),
+                               Vec3::new(0.0, 0.0, 265.0), light));
+
+    let center = Vec3::new(400.0, 400.0, 200.0);
+    let moving = push_material(&mut scene, Material::lambertian(Vec3::new(0.7, 0.3, 0.1)));
+    scene.spheres.push(Sphere::new_moving(center, center + Vec3::new(30.0, 0.0, 0.0), 50.0,
+                                          moving));
+    let glass = push_material(&mut scene, Material::dielectric(1.5));
+    scene.spheres.push(Sphere::new(Vec3::new(260.0, 150.0, 45.0), 50.0, glass));
+    let metal = push_material(&mut scene, Material::metal(Vec3::new(0.8, 0.8, 0.9), 1.0));
+    scene.spheres.push(Sphere::new(Vec3::new(0.0, 150.0, 145.0), 50.0, metal));
+
+    // a glass ball holding blue smoke, with the smoke just inside the glass
+    scene.spheres.push(Sphere::new(Vec3::new(360.0, 150.0, 145.0), 70.0, glass));
+    scene.media.push(Medium::constant_density(0.2, Vec3::new(0.2, 0.4, 0.9)));
+    scene.spheres.push(Sphere::new(Vec3::new(360.0, 150.0, 145.0), 69.9, glass)
+        .with_medium((scene.media.len() - 1) as u32));
+    // a thin mist over everything
+    scene.fog = Some(Medium::constant_density(0.0001, Vec3::ONE));
+
+    // the book wraps the globe in an earth map; a checker keeps it textured without the image
+    scene.textures = vec![
+        Texture::checker(20.0, Vec3::new(0.1, 0.3, 0.6), Vec3::new(0.9, 0.9, 0.8)),
+        Texture::noise(0.2, Vec3::ONE),
+    ];
+    let globe = push_material(&mut scene, Material::lambertian(Vec3::ONE).with_albedo_texture(0));
+    scene.spheres.push(Sphere::new(Vec3::new(400.0, 200.0, 400.0), 100.0, globe));
+    let marble = push_material(&mut scene, Material::lambertian(Vec3::ONE).with_albedo_texture(1));
+    scene.spheres.push(Sphere::new(Vec3::new(220.0, 280.0, 300.0), 80.0, marble));
+
+    // a turned cube of small white spheres
+    let white = push_material(&mut scene, Material::lambertian(Vec3::splat(0.73)));
+    let cluster = Mat4::from_translation(Vec3::new(-100.0, 270.0, 395.0)) *
+        Mat4::from_rotation_y(15f32.to_radians());
+    for _ in 0..1000 {
+        let center = cluster.transform_point3(random_vec3_range(&mut rng, 0.0, 165.0));
+        scene.spheres.push(Sphere::new(center, 10.0, white));
+    }
+    scene
+}
+
+fn cornell_box() -> Scene {
+    let mut scene = Scene { background: Background::Color(Vec3::ZERO), ..Scene::empty() };
+    let red = push_material(&mut scene, Material::lambertian(Vec3::new(0.65, 0.05, 0.05)));
+    let white = push_material(&mut scene, Material::lambertian(Vec3::splat(0.73)));
+    let green = push_material(&mut scene, Material::lambertian(Vec3::new(0.12, 0.45, 0.15)));
+    let light = push_material(&mut scene, Material::emissive(Vec3::splat(15.0)));
+
+    let side = 555.0;
+    scene.quads.extend([
+        Quad::new(Vec3::new(side, 0.0, 0.0), Vec3::new(0.0, side, 0.0), Vec3::new(0.0, 0.0, side),
+                  green),
+        Quad::new(Vec3::ZERO, Vec3::new(0.0, side, 0.0), Vec3::new(0.0, 0.0, side), red),
+        Quad::new(Vec3::new(343.0, 554.0, 332.0), Vec3::new(-130.0, 0.0, 0.0),
+                  Vec3::new(0.0, 0.0, -105.0), light),
+        Quad::new(Vec3::ZERO, Vec3::new(side, 0.0, 0.0), Vec3::new(0.0, 0.0, side), white),
+        Quad::new(Vec3::splat(side), Vec3::new(-side, 0.0, 0.0), Vec3::new(0.0, 0.0, -side),
+                  white),
+        Quad::new(Vec3::new(0.0, 0.0, side), Vec3::new(side, 0.0, 0.0), Vec3::new(0.0, side, 0.0),
+                  white),
+    ]);
+
+    // the two boxes are turned, so they go in through a scene graph
+    let mut graph = SceneGraph::new();
+    let root = graph.root();
+    graph.add_object(root, "tall box",
+                     Transform::from_translation(Vec3::new(265.0, 0.0, 295.0))
+                         .with_rotation(Vec3::Y, 15.0),
+                     SceneObject::AABox(AABox::new(Vec3::ZERO, Vec3::new(165.0, 330.0, 165.0),
+                                                   white)));
+    graph.add_object(root, "short box",
+                     Transform::from_translation(Vec3::new(130.0, 0.0, 65.0))
+                         .with_rotation(Vec3::Y, -18.0),
+                     SceneObject::AABox(AABox::new(Vec3::ZERO, Vec3::splat(165.0), white)));
+    graph.flatten(&mut scene);
+    scene
+}
+
+// the spheres fill a cube that grows with their number, keeping them about as crowded
+fn many_spheres_half_side(count: u32) -> f32 {
+    (count.max(1) as f32).cbrt()
+}
+
+fn many_spheres(seed: u64, count: u32) -> Scene {
+    let mut rng = seeded_rng(seed);
+    let mut scene = Scene::empty();
+    let half_side = many_spheres_half_side(count);
+    let ground = push_material(&mut scene, Material::lambertian(Vec3::splat(0.5)));
+    scene.planes.push(Plane::new(Vec3::new(0.0, -half_side - 0.5, 0.0), Vec3::Y, ground));
+
+    for _ in 0..count {
+        let center = random_vec3_range(&mut rng, -half_side, half_side);
+        let radius = random_range_f32(&mut rng, 0.05, 0.25);
+        let choose_mat = random_f32(&mut rng);
+        let material = if choose_mat < 0.7 {
+            Material::lambertian(random_vec3(&mut rng) * random_vec3(&mut rng))
+        } else if choose_mat < 0.9 {
+            let albedo = random_vec3_range(&mut rng, 0.5, 1.0);
+            Material::metal(albedo, random_range_f32(&mut rng, 0.0, 0.5))
+        } else {
+            Material::dielectric(1.5)
+        };
+        let material_idx = push_material(&mut scene, material);
+        scene.spheres.push(Sphere::new(center, radius, material_idx));
+    }
+    scene
+}
+
+fn material_grid() -> Scene {
+    let mut scene = Scene::empty();
+    scene.textures.push(Texture::checker(0.5, Vec3::splat(0.2), Vec3::splat(0.8)));
+    let ground = push_material(&mut scene, Material::lambertian(Vec3::ONE).with_albedo_texture(0));
+    scene.planes.push(Plane::new(Vec3::ZERO, Vec3::Y, ground));
+    let light = push_material(&mut scene, Material::emissive(Vec3::splat(3.0)));
+    scene.quads.push(Quad::new(Vec3::new(-4.0, 7.0, -1.0), Vec3::new(8.0, 0.0, 0.0),
+                               Vec3::new(0.0, 0.0, 4.0), light));
+
+    const BASE_COLOR: Vec3 = Vec3::new(0.8, 0.2, 0.1);
+    let rows: [fn(f32) -> Material; 5] = [
+        |roughness| Material::principled(BASE_COLOR, 0.0, roughness),
+        |roughness| Material::principled(Vec3::new(0.95, 0.75, 0.4), 1.0, roughness),
+        |roughness| Material::principled(Vec3::ONE, 0.0, roughness).with_transmission(1.0, 1.5),
+        |roughness| Material::principled(BASE_COLOR, 0.0, roughness).with_clearcoat(1.0, 0.03),
+        |roughness| Material::principled(BASE_COLOR, 0.0, roughness).with_sheen(1.0, 0.5),
+    ];
+    let columns = 7;
+    for (row, material) in rows.iter().enumerate() {
+        for column in 0..columns {
+            let roughness = column as f32 / (columns - 1) as f32;
+            let material_idx = push_material(&mut scene, material(roughness));
+            // rows step back and up, so the ones behind show over the ones in front
+            let center = Vec3::new(1.1 * (column as f32 - 3.0), 0.5 + 0.7 * row as f32,
+                                   -1.2 * row as f32);
+            scene.spheres.push(Sphere::new(center, 0.5, material_idx));
+        }
+    }
+    scene
+}
@@ -0,0 +1,268 @@
+use glam::{Mat3, Mat4, Quat, Vec3};
+use crate::{AABox, Plane, Quad, Scene, Shape, Sphere, Triangle};
+
+// a scene graph places objects relative to the nodes they hang from, so a group can be
+// moved, turned or scaled as one; flatten takes everything to world space and appends it to
+// a scene's primitive lists, which is what the GPU sees. objects keep the material indices
+// they were made with, into the scene they are flattened into
+//
+// nothing is moved on the GPU by changing the graph: to animate a node, set its transform
+// and flatten again into a fresh scene
+
+// the local transform of a node: scaled first, then rotated, then translated
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    pub fn from_translation(translation: Vec3) -> Self {
+        Self { translation, ..Self::IDENTITY }
+    }
+
+    // angle in degrees, about the axis through the node's origin
+    pub fn from_rotation(axis: Vec3, angle: f32) -> Self {
+        Self::IDENTITY.with_rotation(axis, angle)
+    }
+
+    pub fn from_scale(scale: Vec3) -> Self {
+        Self { scale, ..Self::IDENTITY }
+    }
+
+    pub fn with_translation(mut self, translation: Vec3) -> Self {
+        self.translation = translation;
+        self
+    }
+
+    pub fn with_rotation(mut self, axis: Vec3, angle: f32) -> Self {
+        self.rotation = Quat::from_axis_angle(axis.normalize(), angle.to_radians());
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Vec3) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+// anything a node can hold, given in the node's own space
+#[derive(Copy, Clone, Debug)]
+pub enum SceneObject {
+    Sphere(Sphere),
+    Quad(Quad),
+    Plane(Plane),
+    AABox(AABox),
+    Shape(Shape),
+    Triangle(Triangle),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+#[derive(Clone, Debug)]
+pub struct SceneNode {
+    pub name: String,
+    pub transform: Transform,
+    pub objects: Vec<SceneObject>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+impl SceneNode {
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SceneGraph {
+    nodes: Vec<SceneNode>,
+}
+
+impl Default for SceneGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneGraph {
+    // a graph with only its root, named "root", at the world origin
+    pub fn new() -> Self {
+        let root = SceneNode {
+            name: "root".to_string(),
+            transform: Transform::IDENTITY,
+            objects: vec![],
+            parent: None,
+            children: vec![],
+        };
+        Self { nodes: vec![root] }
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    // an empty node under parent, to group others under
+    pub fn add_node(&mut self, parent: NodeId, name: &str, transform: Transform) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(SceneNode {
+            name: name.to_string(),
+            transform,
+            objects: vec![],
+            parent: Some(parent),
+            children: vec![],
+        });
+        self.nodes[parent.0].children.push(id);
+        id
+    }
+
+    // a node under parent holding a single object
+    pub fn add_object(&mut self, parent: NodeId, name: &str, transform: Transform,
+                      object: SceneObject) -> NodeId {
+        let id = self.add_node(parent, name, transform);
+        self.nodes[id.0].objects.push(object);
+        id
+    }
+
+    pub fn node(&self, id: NodeId) -> &SceneNode {
+        &self.nodes[id.0]
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> &mut SceneNode {
+        &mut self.nodes[id.0]
+    }
+
+    // the first node with the name, in the order they were added
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.nodes.iter().position(|node| node.name == name).map(NodeId)
+    }
+
+    // from the node's space to the world, through all its ancestors
+    pub fn world_transform(&self, id: NodeId) -> Mat4 {
+        let node = &self.nodes[id.0];
+        let local = node.transform.matrix();
+        match node.parent {
+            Some(parent) => self.world_transform(parent) * local,
+            None => local,
+        }
+    }
+
+    // appends every object in the graph to the scene, in world space
+    pub fn flatten(&self, scene: &mut Scene) {
+        self.flatten_node(self.root(), Mat4::IDENTITY, scene);
+    }
+
+    fn flatten_node(&self, id: NodeId, parent_transform: Mat4, scene: &mut Scene) {
+        let node = &self.nodes[id.0];
+        let transform = parent_transform * node.transform.matrix();
+        for object in &node.objects {
+            match object {
+                SceneObject::Sphere(sphere) => scene.spheres.push(sphere.transformed(&transform)),
+                SceneObject::Quad(quad) => scene.quads.push(quad.transformed(&transform)),
+                SceneObject::Plane(plane) => scene.planes.push(plane.transformed(&transform)),
+                SceneObject::AABox(aabox) => {
+                    // a box turned off the axes becomes a shape, which keeps its orientation
+                    if keeps_axes(&transform) {
+                        scene.boxes.push(aabox.transformed(&transform));
+                    } else {
+                        scene.shapes.push(aabox.turned(&transform));
+                    }
+                }
+                SceneObject::Shape(shape) => scene.shapes.push(shape.transformed(&transform)),
+                SceneObject::Triangle(triangle) =>
+                    scene.triangles.push(triangle.transformed(&transform)),
+            }
+        }
+        for &child in &node.children {
+            self.flatten_node(child, transform, scene);
+        }
+    }
+}
+
+// whether the transform maps each axis onto an axis
+fn keeps_axes(transform: &Mat4) -> bool {
+    let linear = Mat3::from_mat4(*transform);
+    [linear.x_axis, linear.y_axis, linear.z_axis].iter().all(|column| {
+        let magnitude = column.abs();
+        let largest = magnitude.max_element();
+        magnitude.cmplt(Vec3::splat(1e-5 * largest)).bitmask().count_ones() == 2
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn world_transform_applies_parents_after_children() {
+        let mut graph = SceneGraph::new();
+        let parent = graph.add_node(graph.root(), "parent",
+                                    Transform::from_translation(Vec3::new(0.0, 2.0, 0.0))
+                                        .with_scale(Vec3::splat(2.0)));
+        let child = graph.add_node(parent, "child",
+                                   Transform::from_rotation(Vec3::Y, 90.0)
+                                       .with_translation(Vec3::X));
+        let world = graph.world_transform(child);
+        // (1, 0, 0) turns to (0, 0, -1), moves to (1, 0, -1), then doubles and rises by 2
+        let point = world.transform_point3(Vec3::X);
+        assert!(point.abs_diff_eq(Vec3::new(2.0, 2.0, -2.0), 1e-5), "{point}");
+        assert_eq!(graph.world_transform(graph.root()), Mat4::IDENTITY);
+        assert_eq!(graph.find("child"), Some(child));
+    }
+
+    #[test]
+    fn keeps_axes_allows_quarter_turns_scales_and_mirrors() {
+        let quarter_turn = Transform::from_rotation(Vec3::Z, 90.0)
+            .with_scale(Vec3::new(1.0, 2.0, 3.0));
+        let mirror = Transform::from_scale(Vec3::new(-1.0, 1.0, 1.0));
+        assert!(keeps_axes(&Mat4::IDENTITY));
+        assert!(keeps_axes(&quarter_turn.matrix()));
+        assert!(keeps_axes(&mirror.matrix()));
+        assert!(!keeps_axes(&Transform::from_rotation(Vec3::Y, 45.0).matrix()));
+        assert!(!keeps_axes(&Transform::from_rotation(Vec3::ONE, 90.0).matrix()));
+    }
+
+    #[test]
+    fn turned_boxes_become_shapes() {
+        let aabox = AABox::new(Vec3::ZERO, Vec3::new(1.0, 2.0, 3.0), 0).with_medium(0);
+        let mut graph = SceneGraph::new();
+        graph.add_object(graph.root(), "kept", Transform::from_rotation(Vec3::Y, 90.0),
+                         SceneObject::AABox(aabox));
+        let turned = Transform::from_rotation(Vec3::Y, 30.0)
+            .with_translation(Vec3::new(5.0, 0.0, 0.0));
+        graph.add_object(graph.root(), "turned", turned, SceneObject::AABox(aabox));
+
+        let mut scene = Scene::empty();
+        graph.flatten(&mut scene);
+        assert_eq!(scene.boxes.len(), 1);
+        assert_eq!(scene.shapes.len(), 1);
+        assert!(scene.shapes[0].has_medium());
+        // the shape is bounded by the turned corners
+        let (shape_min, shape_max) = scene.shapes[0].get_aabb();
+        let (corners_min, corners_max) = aabox.transformed(&turned.matrix()).get_aabb();
+        assert!(shape_min.abs_diff_eq(corners_min, 1e-5), "{shape_min} isn't {corners_min}");
+        assert!(shape_max.abs_diff_eq(corners_max, 1e-5), "{shape_max} isn't {corners_max}");
+    }
+}
@@ -0,0 +1,243 @@
+// a small preprocessor for the shaders, as WGSL has none of its own. directives sit on lines of
+// their own:
+//   #include "file.wgsl"    pastes in another shader, the first time it is included only
+//   #define NAME            defines a feature for the rest of the shader
+//   #ifdef NAME, #ifndef NAME, #else, #endif
+//                           keep the lines between only while a feature is or isn't defined
+use std::collections::HashSet;
+use std::io;
+
+// the shaders built into the binary, by file name
+const EMBEDDED_SHADERS: &[(&str, &str)] = &[
+    ("bindings.wgsl", include_str!("shaders/bindings.wgsl")),
+    ("bvh.wgsl", include_str!("shaders/bvh.wgsl")),
+    ("camera.wgsl", include_str!("shaders/camera.wgsl")),
+    ("common.wgsl", include_str!("shaders/common.wgsl")),
+    ("intersect.wgsl", include_str!("shaders/intersect.wgsl")),
+    ("lights.wgsl", include_str!("shaders/lights.wgsl")),
+    ("materials.wgsl", include_str!("shaders/materials.wgsl")),
+    ("media.wgsl", include_str!("shaders/media.wgsl")),
+    ("raytracer_kernel.wgsl", include_str!("shaders/raytracer_kernel.wgsl")),
+    ("rng.wgsl", include_str!("shaders/rng.wgsl")),
+    ("sampling.wgsl", include_str!("shaders/sampling.wgsl")),
+    ("screen_shader.wgsl", include_str!("shaders/screen_shader.wgsl")),
+    ("textures.wgsl", include_str!("shaders/textures.wgsl")),
+];
+
+pub(crate) fn embedded_shader(name: &str) -> io::Result<String> {
+    EMBEDDED_SHADERS.iter()
+        .find(|(file, _)| *file == name)
+        .map(|(_, source)| source.to_string())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no shader {name}")))
+}
+
+// a shader with its includes pasted in and its conditional blocks resolved
+pub(crate) struct PreprocessedShader {
+    pub source: String,
+    // the file and line number each line of source came from
+    origins: Vec<(String, usize)>,
+}
+
+impl PreprocessedShader {
+    // naga gives the place of an error as wgsl:line:column in the preprocessed source; this
+    // points those at the file and line the code came from
+    pub fn map_error_lines(&self, message: &str) -> String {
+        let mut mapped = String::with_capacity(message.len());
+        let mut rest = message;
+        while let Some(start) = rest.find("wgsl:") {
+            mapped.push_str(&rest[..start]);
+            let after = &rest[start + "wgsl:".len()..];
+            let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            let origin = after[..digits].parse::<usize>().ok()
+                .and_then(|line| self.origins.get(line.wrapping_sub(1)));
+            match origin {
+                Some((file, line)) => mapped.push_str(&format!("{file}:{line}")),
+                None => mapped.push_str(&rest[start..start + "wgsl:".len() + digits]),
+            }
+            rest = &after[digits..];
+        }
+        mapped.push_str(rest);
+        mapped
+    }
+}
+
+// the shader called name, with the features in defines defined from its first line; load reads
+// a shader by file name, from the embedded ones or from disk
+pub(crate) fn preprocess(name: &str, defines: &[&str],
+                         load: impl FnMut(&str) -> io::Result<String>)
+    -> io::Result<PreprocessedShader> {
+    let mut preprocessor = Preprocessor {
+        load,
+        defines: defines.iter().map(|define| define.to_string()).collect(),
+        included: HashSet::new(),
+        shader: PreprocessedShader { source: String::new(), origins: Vec::new() },
+    };
+    preprocessor.include(name)?;
+    Ok(preprocessor.shader)
+}
+
+struct Preprocessor<F> {
+    load: F,
+    defines: HashSet<String>,
+    included: HashSet<String>,
+    shader: PreprocessedShader,
+}
+
+// an #ifdef or #ifndef that hasn't reached its #endif
+struct Condition {
+    // whether the lines around it are kept
+    outer: bool,
+    holds: bool,
+    in_else: bool,
+}
+
+impl Condition {
+    fn keeps_lines(&self) -> bool {
+        self.outer && self.holds != self.in_else
+    }
+}
+
+impl<F: FnMut(&str) -> io::Result<String>> Preprocessor<F> {
+    fn include(&mut self, file: &str) -> io::Result<()> {
+        if !self.included.insert(file.to_string()) {
+            return Ok(());
+        }
+        let text = (self.load)(file)
+            .map_err(|err| io::Error::new(err.kind(), format!("{file}: {err}")))?;
+
+        let mut conditions: Vec<Condition> = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let number = index + 1;
+            let keep = conditions.last().is_none_or(Condition::keeps_lines);
+            let Some(directive) = line.trim().strip_prefix('#') else {
+                if keep {
+                    self.shader.source.push_str(line);
+                    self.shader.source.push('\n');
+                    self.shader.origins.push((file.to_string(), number));
+                }
+                continue;
+            };
+
+            let (keyword, argument) = directive.split_once(char::is_whitespace)
+                .map_or((directive, ""), |(keyword, argument)| (keyword, argument.trim()));
+            let error = |message: String| directive_error(file, number, &message);
+            match keyword {
+                "include" => {
+                    let included = argument.strip_prefix('"')
+                        .and_then(|argument| argument.strip_suffix('"'))
+                        .ok_or_else(|| error(format!("#include needs a quoted file name, \
+                                                      not {argument}")))?;
+                    if keep {
+                        self.include(included)?;
+                    }
+                }
+                "define" | "ifdef" | "ifndef" if argument.is_empty() => {
+                    return Err(error(format!("#{keyword} needs a name")));
+                }
+                "define" => {
+                    if keep {
+                        self.defines.insert(argument.to_string());
+                    }
+                }
+                "ifdef" | "ifndef" => {
+                    let defined = self.defines.contains(argument);
+                    conditions.push(Condition {
+                        outer: keep,
+                        holds: defined == (keyword == "ifdef"),
+                        in_else: false,
+                    });
+                }
+                "else" => match conditions.last_mut() {
+                    Some(condition) if !condition.in_else => condition.in_else = true,
+                    Some(_) => return Err(error("a second #else".to_string())),
+                    None => return Err(error("#else without #ifdef".to_string())),
+                },
+                "endif" => {
+                    conditions.pop().ok_or_else(|| error("#endif without #ifdef".to_string()))?;
+                }
+                _ => return Err(error(format!("unknown directive #{keyword}"))),
+            }
+        }
+        if !conditions.is_empty() {
+            return Err(directive_error(file, text.lines().count(), "#ifdef without #endif"));
+        }
+        Ok(())
+    }
+}
+
+fn directive_error(file: &str, line: usize, message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{file}:{line}: {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_from<'a>(files: &'a [(&'a str, &'a str)])
+        -> impl FnMut(&str) -> io::Result<String> + 'a {
+        move |name| files.iter()
+            .find(|(file, _)| *file == name)
+            .map(|(_, source)| source.to_string())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "missing"))
+    }
+
+    #[test]
+    fn includes_are_pasted_once() {
+        let files = [
+            ("main.wgsl", "#include \"a.wgsl\"\n#include \"b.wgsl\"\nfn main() {}"),
+            ("a.wgsl", "#include \"b.wgsl\"\nconst A = 1;"),
+            ("b.wgsl", "const B = 2;"),
+        ];
+        let shader = preprocess("main.wgsl", &[], load_from(&files)).unwrap();
+        assert_eq!(shader.source, "const B = 2;\nconst A = 1;\nfn main() {}\n");
+        assert_eq!(shader.map_error_lines("┌─ wgsl:2:7"), "┌─ a.wgsl:2:7");
+        assert_eq!(shader.map_error_lines("wgsl:3:1 and wgsl:9:1"), "main.wgsl:3:1 and wgsl:9:1");
+    }
+
+    #[test]
+    fn conditions_follow_defines() {
+        let files = [("main.wgsl", "\
+#ifdef A
+a
+#ifndef B
+not b
+#else
+b
+#endif
+#else
+not a
+#endif
+#define B
+#ifdef B
+b later
+#endif")];
+        let shader = preprocess("main.wgsl", &["A"], load_from(&files)).unwrap();
+        assert_eq!(shader.source, "a\nnot b\nb later\n");
+        let shader = preprocess("main.wgsl", &[], load_from(&files)).unwrap();
+        assert_eq!(shader.source, "not a\nb later\n");
+    }
+
+    #[test]
+    fn bad_directives_are_errors() {
+        for source in ["#ifdef A\n", "#endif\n", "#ifdef A\n#else\n#else\n#endif\n", "#if A\n",
+                       "#include b.wgsl\n", "#include \"missing.wgsl\"\n"] {
+            let files = [("main.wgsl", source)];
+            assert!(preprocess("main.wgsl", &[], load_from(&files)).is_err(), "{source:?}");
+        }
+    }
+
+    #[test]
+    fn embedded_kernel_validates_with_and_without_the_bvh() {
+        for defines in [&["USE_BVH"][..], &[]] {
+            let kernel = preprocess("raytracer_kernel.wgsl", defines, embedded_shader).unwrap();
+            let module = naga::front::wgsl::parse_str(&kernel.source)
+                .unwrap_or_else(|err| panic!("{}", kernel.map_error_lines(
+                    &err.emit_to_string(&kernel.source))));
+            naga::valid::Validator::new(naga::valid::ValidationFlags::all(),
+                                        naga::valid::Capabilities::default())
+                .validate(&module)
+                .unwrap_or_else(|err| panic!("{}", kernel.map_error_lines(
+                    &err.emit_to_string(&kernel.source))));
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+// the shaders in the source tree, which the built binary has copies of
+pub(crate) const SHADER_DIRECTORY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders");
+
+// the event loop comes round far more often than anyone saves a shader
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// notices when any .wgsl file in a directory is saved, by polling the files' modification times
+pub(crate) struct ShaderWatcher {
+    directory: PathBuf,
+    last_modified: Option<SystemTime>,
+    last_poll: Instant,
+}
+
+impl ShaderWatcher {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        let directory = directory.into();
+        let last_modified = latest_modification(&directory);
+        Self { directory, last_modified, last_poll: Instant::now() }
+    }
+
+    // true the first time it is called after a save
+    pub fn poll(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        let modified = latest_modification(&self.directory);
+        if modified > self.last_modified {
+            self.last_modified = modified;
+            return true;
+        }
+        false
+    }
+
+    pub fn read(&self, name: &str) -> io::Result<String> {
+        std::fs::read_to_string(self.directory.join(name))
+    }
+}
+
+fn latest_modification(directory: &Path) -> Option<SystemTime> {
+    std::fs::read_dir(directory).ok()?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().extension().is_some_and(|extension| extension == "wgsl"))
+        .filter_map(|entry| entry.metadata().ok()?.modified().ok())
+        .max()
+}
@@ -0,0 +1,249 @@
+// what the kernels share with the Rust side: the structs, laid out to match their Rust
+// counterparts (layout.rs checks them), the values that stand for none, and the bind groups
+
+struct BVHNode {
+    aabbMin: vec3f,
+    leftFirst: u32,
+    aabbMax: vec3f,
+    primCount: u32,
+}
+
+struct Sphere {
+    center0: vec4f,
+    center1: vec4f,
+    radius: f32,
+    mat_idx: u32,
+    lightIdx: u32,
+    // a sphere filled with a medium is only a boundary, see Medium
+    mediumIdx: u32,
+}
+
+struct Quad {
+    q: vec4f,
+    u: vec4f,
+    v: vec4f,
+    mat_idx: u32,
+    lightIdx: u32,
+}
+
+struct Plane {
+    point: vec4f,
+    normal: vec4f,
+    mat_idx: u32,
+}
+
+struct AABox {
+    boxMin: vec3f,
+    mat_idx: u32,
+    boxMax: vec3f,
+    mediumIdx: u32,
+}
+
+// shapeType is indexed as follows:
+// 0 Cylinder; 1 Disk; 2 Cone; 3 Torus; 4 Box
+// shapes are intersected in object space, where their axis is +y
+struct Shape {
+    worldToObject: mat4x4f,
+    radius: f32,
+    height: f32,
+    minorRadius: f32,
+    shapeType: u32,
+    mat_idx: u32,
+    lightIdx: u32,
+    mediumIdx: u32,
+}
+
+// the geometric normal follows the winding, counter-clockwise seen from the front; zero
+// corner normals mean the triangle is flat
+struct Triangle {
+    p0: vec4f,
+    p1: vec4f,
+    p2: vec4f,
+    n0: vec4f,
+    n1: vec4f,
+    n2: vec4f,
+    uv01: vec4f,
+    uv2: vec2f,
+    mat_idx: u32,
+    lightIdx: u32,
+    // a color at each corner, packed as 8 bit sRGB, multiplying the material's albedo
+    colors: vec3u,
+}
+
+// what rays leaving the scene see: the sky gradient unless sky is zero, then color
+struct Background {
+    color: vec4f,
+    sky: u32,
+}
+
+// primType is indexed as follows:
+// 0 Sphere; 1 Quad; 2 AABox; 3 Shape; 4 Triangle
+struct Primitive {
+    aabbMin: vec3f,
+    primType: u32,
+    aabbMax: vec3f,
+    primIdx: u32,
+}
+
+struct Material {
+    albedo: vec4f,
+    emission: vec4f,
+    fuzz: f32,
+    refract_idx: f32,
+    mat_type: u32,
+    albedoTexture: u32,
+    roughnessTexture: u32,
+    emissionTexture: u32,
+    metallic: f32,
+    roughness: f32,
+    specular: f32,
+    clearcoat: f32,
+    clearcoatRoughness: f32,
+    sheen: f32,
+    sheenTint: f32,
+    transmission: f32,
+}
+
+// textureType is indexed as follows:
+// 0 Image; 1 Checker; 2 Noise
+struct Texture {
+    color0: vec4f,
+    color1: vec4f,
+    scale: f32,
+    imageLayer: u32,
+    textureType: u32,
+    srgb: u32,
+}
+
+const NO_TEXTURE: u32 = 0xffffffffu;
+
+// lightType is indexed as follows:
+// 0 Sphere; 1 Quad; 2 Disk; 3 Triangle; 4 Point; 5 Spot; 6 Sun
+// spheres read their geometry from the sphere buffer; quads keep their corner and edges in
+// position, u and v, disks their center and the two radii of the ellipse, triangles their
+// first corner and the edges to the other two
+// the punctual lights (point, spot, sun) have no geometry and carry their own intensity;
+// u is the direction spots and the sun shine in
+struct Light {
+    position: vec4f,
+    u: vec4f,
+    v: vec4f,
+    intensity: vec4f,
+    lightType: u32,
+    primIdx: u32,
+    mat_idx: u32,
+    area: f32,
+    cosInner: f32,
+    cosOuter: f32,
+}
+
+const NO_LIGHT: u32 = 0xffffffffu;
+
+// per light: an alias table entry, the light's pmf under power sampling and the path to
+// its leaf in the light BVH (bit k set means the right child at depth k)
+struct LightSelection {
+    aliasProbability: f32,
+    aliasIdx: u32,
+    pmf: f32,
+    bitTrail: u32,
+}
+
+// a leaf holds one light (lightCount == 1, leftFirst is the light); otherwise the children
+// are at leftFirst and leftFirst + 1
+struct LightBVHNode {
+    aabbMin: vec3f,
+    leftFirst: u32,
+    aabbMax: vec3f,
+    lightCount: u32,
+    power: f32,
+}
+
+// a homogeneous volume; media[0] is the global fog, which has no extinction when the scene
+// has none; the rest fill closed primitives, which the ray passes straight through
+struct Medium {
+    absorption: vec4f,
+    scattering: vec4f,
+    // Henyey-Greenstein asymmetry
+    g: f32,
+}
+
+const NO_MEDIUM: u32 = 0xffffffffu;
+
+struct CameraData {
+    pos: vec4f,
+    forwards: vec4f,
+    right: vec4f,
+    up: vec4f,
+    // the pixel grid, as angles for a fisheye or panorama; see gpu_structs.rs
+    pixel_00: vec4f,
+    du: vec4f,
+    dv: vec4f,
+    defocusRadius: f32,
+    shutterOpen: f32,
+    shutterClose: f32,
+    // what the rendered radiance is scaled by
+    exposure: f32,
+    // zero for a circular aperture, otherwise the sides of a regular polygon
+    apertureBlades: u32,
+    apertureRotation: f32,
+    // 0 Perspective; 1 Orthographic; 2 Fisheye; 3 Equirectangular
+    projection: u32,
+    // the fisheye's largest angle from forwards
+    maxAngle: f32,
+    // for one eye of a stereo pair, how far along the eye axis it is; zero otherwise
+    eyeOffset: f32,
+    // zero for parallel eyes
+    convergenceDistance: f32,
+}
+
+// a pixel whose primary ray is traced to find the distance to focus at, and that distance
+// along the camera's forward axis, or a negative one if the ray found nothing
+struct FocusPick {
+    pixel: vec2u,
+    distance: f32,
+}
+
+// lightSampling is indexed as follows:
+// 0 Uniform; 1 Power (alias table); 2 LightBVH
+// samplerType is indexed as follows:
+// 0 Random (PCG); 1 Sobol; 2 BlueNoise; 3 R2; 4 Halton
+// filterType is indexed as follows:
+// 0 Box; 1 Tent; 2 Gaussian; 3 BlackmanHarris; 4 Mitchell
+struct SamplingParameters {
+    samples_per_pixel: u32,
+    num_bounces: u32,
+    lightSampling: u32,
+    russianRouletteDepth: u32,
+    // zero means samples are not clamped
+    maxSampleRadiance: f32,
+    samplerType: u32,
+    filterType: u32,
+    // in pixels
+    filterRadius: f32,
+    // the render seed hashed with the frame; see frame_seed in sampler.rs
+    seed: u32,
+}
+
+@group(0) @binding(0) var color_buffer: texture_storage_2d<rgba8unorm, write>;
+@group(1) @binding(0) var<storage, read> spheres: array<Sphere>;
+@group(1) @binding(1) var<storage, read> materials: array<Material>;
+@group(1) @binding(2) var<storage, read> quads: array<Quad>;
+@group(1) @binding(3) var<storage, read> planes: array<Plane>;
+@group(1) @binding(4) var<storage, read> boxes: array<AABox>;
+@group(1) @binding(5) var<storage, read> shapes: array<Shape>;
+@group(1) @binding(6) var<storage, read> textures: array<Texture>;
+@group(1) @binding(7) var textureImages: texture_2d_array<f32>;
+@group(1) @binding(8) var textureSampler: sampler;
+@group(1) @binding(9) var<storage, read> media: array<Medium>;
+@group(1) @binding(10) var<storage, read> triangles: array<Triangle>;
+@group(1) @binding(11) var<uniform> background: Background;
+@group(2) @binding(0) var<storage, read> bvhTree: array<BVHNode>;
+@group(2) @binding(1) var<storage, read> primitives: array<Primitive>;
+@group(3) @binding(0) var<uniform> camera: CameraData;
+@group(3) @binding(1) var<uniform> sampling_parameters: SamplingParameters;
+@group(3) @binding(2) var blueNoiseMask: texture_2d<f32>;
+@group(3) @binding(3) var filterTable: texture_2d<f32>;
+@group(3) @binding(4) var<storage, read_write> focusPick: FocusPick;
+@group(4) @binding(0) var<storage, read> lights: array<Light>;
+@group(4) @binding(1) var<storage, read> lightTree: array<LightBVHNode>;
+@group(4) @binding(2) var<storage, read> lightSelection: array<LightSelection>;
@@ -0,0 +1,165 @@
+// tracing rays through the scene, down the BVH when USE_BVH is defined and through every
+// primitive otherwise
+
+#include "bindings.wgsl"
+#include "common.wgsl"
+#include "intersect.wgsl"
+
+// the nodes still to visit are kept by index; one is pushed for each level a traversal goes
+// down, so this must be at least MAX_DEPTH in bvh.rs, which the BVH is never built deeper than
+const STACKSIZE:u32 = 64;
+
+fn TraceRay(ray: Ray, hit: ptr<function, HitPayload>) -> bool {
+    // runs through objects in the scene and returns true if the ray hits one, and updates
+    // the hitPayload with the closest hit
+    return traverseScene(ray, 1e30, false, hit);
+}
+
+fn TraceShadowRay(ray: Ray, t_max: f32) -> bool {
+    // returns true if anything blocks the ray before t_max
+    var hit = HitPayload();
+    return traverseScene(ray, t_max, true, &hit);
+}
+
+fn traverseScene(ray: Ray, t_max: f32, anyHit: bool, hit: ptr<function, HitPayload>) -> bool {
+    // finds the closest hit before t_max; with anyHit set it stops at the first hit found,
+    // which is all a shadow ray needs to know
+
+    var nearest_hit: f32 = t_max;
+    var found = false;
+    var tempHitPayload = HitPayload();
+
+    // planes are unbounded so they live outside the BVH and are always tested
+    let plane_count = arrayLength(&planes);
+    for (var i: u32 = 0; i < plane_count; i++) {
+        var newHitPayload = HitPayload();
+        if intersectPlane(ray, i, 0.001, nearest_hit, &newHitPayload) {
+            if anyHit {
+                *hit = newHitPayload;
+                return true;
+            }
+            nearest_hit = newHitPayload.t;
+            tempHitPayload = newHitPayload;
+            found = true;
+        }
+    }
+
+#ifdef USE_BVH
+    // a root with no primitives and no children means there is nothing bounded in the scene
+    let emptyTree = bvhTree[0].primCount == 0 && bvhTree[0].leftFirst == 0;
+
+    if !emptyTree {
+        // this is where I will implement the BVH tree search rather than using a full primitive search
+        var stack = array<u32, STACKSIZE>();
+        var stackPointer:u32 = 0;
+        var node: BVHNode = bvhTree[0];
+        while true {
+            if node.primCount > 0 {
+                // this is a leaf and has primitives, so check to see if primitives are hit
+                for (var idx:u32 = 0; idx < node.primCount; idx++) {
+                    var newHitPayload = HitPayload();
+                    if hitPrimitive(ray, node.leftFirst + idx, 0.001, nearest_hit, &newHitPayload) {
+                        if anyHit {
+                            *hit = newHitPayload;
+                            return true;
+                        }
+                        nearest_hit = newHitPayload.t;
+                        tempHitPayload = newHitPayload;
+                        found = true;
+                    }
+                }
+                // we are now done with this node; if stack is empty, break; otherwise
+                // set node based on the stack
+                if stackPointer == 0 {
+                    break;
+                }
+                else {
+                    stackPointer--;
+                    node = bvhTree[stack[stackPointer]];
+                    continue;
+                }
+            } else {
+                // if not a leaf, check to see if this node's children have been hit
+                var leftIdx: u32 = node.leftFirst;
+                var rightIdx: u32 = node.leftFirst + 1;
+                var t_left:f32 = hit_bvh_node(bvhTree[leftIdx], ray, nearest_hit);
+                var t_right:f32 = hit_bvh_node(bvhTree[rightIdx], ray, nearest_hit);
+
+                // make sure the left node is always the closer node
+                if t_left > t_right {
+                    let temp_t:f32 = t_left;
+                    t_left = t_right;
+                    t_right = temp_t;
+
+                    let temp: u32 = leftIdx;
+                    leftIdx = rightIdx;
+                    rightIdx = temp;
+                }
+                // if the left hit is bigger than nearest hit, no need to do anything else here
+                if t_left > nearest_hit {
+                    if stackPointer == 0 {
+                        break;
+                    } else {
+                        stackPointer--;
+                        node = bvhTree[stack[stackPointer]];
+                    }
+                } else {
+                    node = bvhTree[leftIdx];
+                    // if the right child is also hit before nearest_hit, save it to the stack
+                    if t_right < nearest_hit {
+                        stack[stackPointer] = rightIdx;
+                        stackPointer++;
+                    }
+                }
+            }
+        }
+    }
+#else
+    // this is the old code with full primitive search
+    let primitive_count = arrayLength(&primitives);
+    for (var i: u32 = 0; i < primitive_count; i++) {
+        var newHitPayload = HitPayload();
+
+        // I could update this code so that hit only determines if a hit happened and, if it did,
+        // modifies the nearest_hit_t and stores the nearest_index
+        if hitPrimitive(ray, i, 0.001, nearest_hit, &newHitPayload) {
+            if anyHit {
+                *hit = newHitPayload;
+                return true;
+            }
+            nearest_hit = newHitPayload.t;
+            tempHitPayload = newHitPayload;
+            found = true;
+        }
+    }
+#endif
+
+    // then after looping through the objects, we will know the nearest_hit_t and the index; we could call
+    // for the payload then (as opposed to filling it out every time we hit a closer sphere)
+    if found {
+        *hit = tempHitPayload;
+        return true;
+    }
+    return false;
+}
+
+fn hit_bvh_node(node: BVHNode, ray: Ray, nearest_hit: f32) -> f32 {
+    let t_x_min = (node.aabbMin.x - ray.origin.x) * ray.invDirection.x;
+    let t_x_max = (node.aabbMax.x - ray.origin.x) * ray.invDirection.x;
+    var tmin = min(t_x_min, t_x_max);
+    var tmax = max(t_x_min, t_x_max);
+    let t_y_min = (node.aabbMin.y - ray.origin.y) * ray.invDirection.y;
+    let t_y_max = (node.aabbMax.y - ray.origin.y) * ray.invDirection.y;
+    tmin = max(min(t_y_min, t_y_max), tmin);
+    tmax = min(max(t_y_min, t_y_max), tmax);
+    let t_z_min = (node.aabbMin.z - ray.origin.z) * ray.invDirection.z;
+    let t_z_max = (node.aabbMax.z - ray.origin.z) * ray.invDirection.z;
+    tmin = max(min(t_z_min, t_z_max), tmin);
+    tmax = min(max(t_z_min, t_z_max), tmax);
+
+    if tmin > tmax || tmax <= 0.0 || tmin > nearest_hit {
+        return 1e30;
+    } else {
+        return tmin;
+    }
+}
@@ -0,0 +1,202 @@
+// camera rays: the projection, the lens and its aperture, and the pixel filter
+
+#include "bindings.wgsl"
+#include "common.wgsl"
+#include "sampling.wgsl"
+
+// the bins in filterTable, which holds the cumulative distribution of the filter's magnitude
+// over [0, filterRadius]; see filter.rs
+const FILTER_TABLE_SIZE: u32 = 64;
+
+fn getRay(x: u32, y: u32, state: ptr<function, u32>, weight: ptr<function, f32>) -> Ray {
+    // the offset from the pixel center is drawn from the reconstruction filter first, so it gets
+    // the sampler's best distributed dimensions; weight is what the sample counts for
+    var weightX: f32 = 0.0;
+    var weightY: f32 = 0.0;
+    let jitterX: f32 = sampleFilter(rngNextFloat(state), &weightX);
+    let jitterY: f32 = sampleFilter(rngNextFloat(state), &weightY);
+    *weight = weightX * weightY;
+    let offset: vec2f = sampleAperture(state);
+    var ray: Ray;
+    if !cameraRay(vec2f(f32(x) + jitterX, f32(y) + jitterY), offset, &ray) {
+        // outside a fisheye's image circle
+        *weight = 0.0;
+    }
+    // each ray sees the scene at a random instant while the shutter is open
+    ray.time = mix(camera.shutterOpen, camera.shutterClose, rngNextFloat(state));
+    return ray;
+}
+
+fn cameraRay(pixel: vec2f, lens: vec2f, ray: ptr<function, Ray>) -> bool {
+    // the ray through a point of the image, given in pixels from the center of the first,
+    // leaving from a point of the lens in the unit circle; false if the point shows nothing
+    var origin: vec3f = camera.pos.xyz;
+    var direction: vec3f = camera.forwards.xyz;
+    // the way a stereo eye is moved, across the view
+    var eyeAxis: vec3f = camera.right.xyz;
+    let onGrid: vec3f = camera.pixel_00.xyz + pixel.x * camera.du.xyz + pixel.y * camera.dv.xyz;
+    switch camera.projection {
+        case 1u: {
+            // orthographic: parallel rays from the grid, which passes through the camera
+            origin = onGrid;
+        }
+        case 2u: {
+            // equidistant fisheye: the angle from forwards is the distance from the center
+            let angle: f32 = length(onGrid.xy);
+            if angle > camera.maxAngle {
+                return false;
+            }
+            var across: vec2f = vec2f(0.0);
+            if angle > 0.0 {
+                across = onGrid.xy / angle;
+            }
+            direction = sin(angle) * (across.x * camera.right.xyz + across.y * camera.up.xyz) +
+                cos(angle) * camera.forwards.xyz;
+        }
+        case 3u: {
+            // equirectangular: longitude and latitude, with the eyes turning to stay level
+            // and across whichever way the ray is looking
+            let longitude: f32 = onGrid.x;
+            let latitude: f32 = onGrid.y;
+            direction = cos(latitude) * (sin(longitude) * camera.right.xyz +
+                cos(longitude) * camera.forwards.xyz) + sin(latitude) * camera.up.xyz;
+            eyeAxis = cos(longitude) * camera.right.xyz - sin(longitude) * camera.forwards.xyz;
+        }
+        default: {
+            // perspective: through the grid, which lies on the focus plane
+            direction = onGrid - origin;
+        }
+    }
+    direction = normalize(direction);
+
+    if camera.eyeOffset != 0.0 {
+        let eyeOrigin: vec3f = origin + camera.eyeOffset * eyeAxis;
+        if camera.convergenceDistance > 0.0 {
+            // aim at where the ray from between the eyes is at the convergence distance,
+            // measured along the view for the flat projections
+            var reach: f32 = camera.convergenceDistance;
+            if camera.projection <= 1u {
+                reach /= dot(direction, camera.forwards.xyz);
+            }
+            direction = normalize(origin + reach * direction - eyeOrigin);
+        }
+        origin = eyeOrigin;
+    }
+
+    if camera.projection == 0u && camera.defocusRadius > 0.0 {
+        // the thin lens: whatever part of the lens the ray leaves from, it passes through
+        // the same point of the focus plane, so only what is on that plane stays sharp
+        let focusDistance: f32 = dot(camera.pixel_00.xyz - camera.pos.xyz, camera.forwards.xyz);
+        let focus: vec3f = origin + (focusDistance / dot(direction, camera.forwards.xyz)) * direction;
+        origin += camera.defocusRadius * (lens.x * camera.right.xyz + lens.y * camera.up.xyz);
+        direction = normalize(focus - origin);
+    }
+
+    (*ray).origin = origin;
+    (*ray).direction = direction;
+    (*ray).invDirection = 1.0 / direction;
+    return true;
+}
+
+fn sampleAperture(state: ptr<function, u32>) -> vec2f {
+    // a point on the lens opening, within the unit circle; a polygonal aperture is split into
+    // equal triangles around its center, one picked at random and sampled uniformly
+    let blades: u32 = camera.apertureBlades;
+    if blades < 3u {
+        return rngNextVec3InUnitDisk(state).xy;
+    }
+    let side: f32 = min(floor(rngNextFloat(state) * f32(blades)), f32(blades - 1u));
+    let angle: f32 = 2.0 * PI / f32(blades);
+    let theta0: f32 = camera.apertureRotation + side * angle;
+    let corner0: vec2f = vec2f(cos(theta0), sin(theta0));
+    let corner1: vec2f = vec2f(cos(theta0 + angle), sin(theta0 + angle));
+    let su: f32 = sqrt(rngNextFloat(state));
+    let v: f32 = rngNextFloat(state);
+    return su * ((1.0 - v) * corner0 + v * corner1);
+}
+
+fn sampleFilter(u: f32, weight: ptr<function, f32>) -> f32 {
+    // filter importance sampling along one axis: returns an offset in pixels drawn in proportion
+    // to the filter's magnitude and sets weight to the filter over the pdf there; u is folded
+    // so each side of the filter gets one half of the range in order
+    let folded: f32 = abs(2.0 * u - 1.0);
+    var lo: u32 = 0u;
+    var hi: u32 = FILTER_TABLE_SIZE;
+    while hi - lo > 1u {
+        let mid: u32 = (lo + hi) / 2u;
+        if filterCdf(mid) <= folded {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+
+    let cdf0: f32 = filterCdf(lo);
+    let cdf1: f32 = filterCdf(lo + 1u);
+    var t: f32 = 0.5;
+    if cdf1 > cdf0 {
+        t = clamp((folded - cdf0) / (cdf1 - cdf0), 0.0, 1.0);
+    }
+    let binWidth: f32 = sampling_parameters.filterRadius / f32(FILTER_TABLE_SIZE);
+    var x: f32 = (f32(lo) + t) * binWidth;
+    if u < 0.5 {
+        x = -x;
+    }
+
+    // the pdf over the whole of [-filterRadius, filterRadius]
+    let pdf: f32 = 0.5 * (cdf1 - cdf0) / binWidth;
+    *weight = 0.0;
+    if pdf > 0.0 {
+        *weight = evalFilter(x) / pdf;
+    }
+    return x;
+}
+
+fn filterCdf(idx: u32) -> f32 {
+    return textureLoad(filterTable, vec2u(idx, 0u), 0).r;
+}
+
+fn evalFilter(offset: f32) -> f32 {
+    // the reconstruction filter along one axis, offset in pixels from the center
+    let r: f32 = sampling_parameters.filterRadius;
+    let x: f32 = abs(offset);
+    if x > r {
+        return 0.0;
+    }
+    switch (sampling_parameters.filterType) {
+        case 1u {
+            return r - x;
+        }
+        case 2u {
+            // a third of the radius is one standard deviation
+            let sigma: f32 = r / 3.0;
+            let edge: f32 = exp(-r * r / (2.0 * sigma * sigma));
+            return max(exp(-x * x / (2.0 * sigma * sigma)) - edge, 0.0);
+        }
+        case 3u {
+            let n: f32 = 0.5 + 0.5 * x / r;
+            return 0.35875 - 0.48829 * cos(2.0 * PI * n) + 0.14128 * cos(4.0 * PI * n) -
+                0.01168 * cos(6.0 * PI * n);
+        }
+        case 4u {
+            return mitchell(2.0 * x / r);
+        }
+        case 0u, default {
+            return 1.0;
+        }
+    }
+}
+
+fn mitchell(x: f32) -> f32 {
+    // the Mitchell-Netravali cubic with B = C = 1/3 over [0, 2]
+    let b: f32 = 1.0 / 3.0;
+    let c: f32 = 1.0 / 3.0;
+    if x < 1.0 {
+        return ((12.0 - 9.0 * b - 6.0 * c) * x * x * x + (-18.0 + 12.0 * b + 6.0 * c) * x * x +
+            (6.0 - 2.0 * b)) / 6.0;
+    } else if x <= 2.0 {
+        return ((-b - 6.0 * c) * x * x * x + (6.0 * b + 30.0 * c) * x * x +
+            (-12.0 * b - 48.0 * c) * x + (8.0 * b + 24.0 * c)) / 6.0;
+    }
+    return 0.0;
+}
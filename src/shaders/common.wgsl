@@ -0,0 +1,47 @@
+// the constants and the ray and hit records every module works with
+
+const EPSILON = 0.001f;
+
+const PI = 3.1415927f;
+const FRAC_1_PI = 0.31830987f;
+const FRAC_PI_2 = 1.5707964f;
+
+struct Ray {
+    origin: vec3f,
+    direction: vec3f,
+    invDirection: vec3f,
+    time: f32,
+}
+
+struct HitPayload {
+    t: f32,
+    p: vec3f,
+    n: vec3f,
+    uv: vec2f,
+    mat_idx: u32,
+    // the light list entry of the primitive that was hit, or NO_LIGHT
+    lightIdx: u32,
+    // the medium inside the primitive that was hit, or NO_MEDIUM for an ordinary surface
+    mediumIdx: u32,
+    // multiplies the material's albedo; white for everything but colored meshes
+    color: vec3f,
+}
+
+fn orthogonalTangent(n: vec3f) -> vec3f {
+    // any unit vector perpendicular to n
+    if abs(n.x) > 0.9 {
+        return normalize(cross(n, vec3f(0.0, 1.0, 0.0)));
+    }
+    return normalize(cross(n, vec3f(1.0, 0.0, 0.0)));
+}
+
+fn faceForward(n: vec3f, direction: vec3f) -> vec3f {
+    if dot(n, direction) > 0.0 {
+        return -n;
+    }
+    return n;
+}
+
+fn luminance(c: vec3f) -> f32 {
+    return dot(c, vec3f(0.2126, 0.7152, 0.0722));
+}
@@ -0,0 +1,557 @@
+// ray intersection tests for every kind of primitive
+
+#include "bindings.wgsl"
+#include "common.wgsl"
+#include "textures.wgsl"
+
+fn hitPrimitive(ray: Ray, primitiveIdx: u32, t_min: f32, t_nearest: f32, payload: ptr<function, HitPayload>) -> bool {
+    // looks up which kind of object the BVH primitive refers to and runs its intersection test
+    let primitive: Primitive = primitives[primitiveIdx];
+    switch (primitive.primType) {
+        case 1u {
+            return intersectQuad(ray, primitive.primIdx, t_min, t_nearest, payload);
+        }
+        case 2u {
+            return intersectBox(ray, primitive.primIdx, t_min, t_nearest, payload);
+        }
+        case 3u {
+            return intersectShape(ray, primitive.primIdx, t_min, t_nearest, payload);
+        }
+        case 4u {
+            return intersectTriangle(ray, primitive.primIdx, t_min, t_nearest, payload);
+        }
+        case 0u, default {
+            return intersectSphere(ray, primitive.primIdx, t_min, t_nearest, payload);
+        }
+    }
+}
+
+fn intersectSphere(ray: Ray, sphereIdx: u32, t_min: f32, t_nearest: f32, payload: ptr<function, HitPayload>) -> bool {
+    // checks if the ray intersects the sphere given by sphereIdx; if so, returns true and modifies
+    // a hitPayload to give the details of the hit
+    let sphere: Sphere = spheres[sphereIdx];
+    let sphere_center = sphereCenter(sphere, ray.time);
+    let a: f32 = dot(ray.direction, ray.direction);
+    let b: f32 = dot(ray.direction, ray.origin - sphere_center);
+    let c: f32 = dot(ray.origin - sphere_center, ray.origin - sphere_center) -
+        sphere.radius * sphere.radius;
+    let discrim: f32 = b * b - a * c;
+
+
+    if (discrim >= 0) {
+        var t: f32 = (-b - sqrt(discrim)) / a;
+        if (t > t_min && t < t_nearest) {
+            *payload = hitSphere(t, ray, sphere, sphere_center);
+            return true;
+        }
+
+        t = (-b + sqrt(discrim)) / a;
+        if (t > t_min && t < t_nearest) {
+            *payload = hitSphere(t, ray, sphere, sphere_center);
+            return true;
+        }
+    }
+    return false;
+}
+
+fn sphereCenter(sphere: Sphere, time: f32) -> vec3f {
+    // moving spheres travel linearly from center0 at time 0 to center1 at time 1
+    return mix(sphere.center0.xyz, sphere.center1.xyz, time);
+}
+
+fn hitSphere(t: f32, ray: Ray, sphere: Sphere, center: vec3f) -> HitPayload {
+    // make the hitPayload struct
+    // note that decision here is that normals ALWAYS point out of the sphere
+    // thus, to test whether a ray in intersecting the sphere from the inside vs the outside,
+    // the dot product of the ray direction and the normal is evaluated;  if negative, ray comes
+    // from outside; if positive, ray comes from within
+    let p: vec3f = ray.origin + t * ray.direction;
+    let n: vec3f = normalize(p - center);
+
+    // spherical mapping: u goes around the y axis starting at -x, v goes from the bottom pole up
+    let theta: f32 = acos(clamp(-n.y, -1.0, 1.0));
+    let phi: f32 = atan2(-n.z, n.x) + PI;
+    let uv: vec2f = vec2f(phi / (2.0 * PI), theta / PI);
+
+    return HitPayload(t, p, n, uv, sphere.mat_idx, sphere.lightIdx, sphere.mediumIdx,
+                      vec3f(1.0));
+}
+
+fn intersectQuad(ray: Ray, quadIdx: u32, t_min: f32, t_nearest: f32, payload: ptr<function, HitPayload>) -> bool {
+    // intersect the plane the quad lies in, then check the planar coordinates of the hit
+    // against the edges u and v
+    let quad: Quad = quads[quadIdx];
+    let cross_uv: vec3f = cross(quad.u.xyz, quad.v.xyz);
+    let n: vec3f = normalize(cross_uv);
+    let denom: f32 = dot(n, ray.direction);
+    if abs(denom) < 1e-8 {
+        return false;
+    }
+
+    let t: f32 = dot(n, quad.q.xyz - ray.origin) / denom;
+    if t <= t_min || t >= t_nearest {
+        return false;
+    }
+
+    let p: vec3f = ray.origin + t * ray.direction;
+    let w: vec3f = cross_uv / dot(cross_uv, cross_uv);
+    let planar: vec3f = p - quad.q.xyz;
+    let alpha: f32 = dot(w, cross(planar, quad.v.xyz));
+    let beta: f32 = dot(w, cross(quad.u.xyz, planar));
+    if alpha < 0.0 || alpha > 1.0 || beta < 0.0 || beta > 1.0 {
+        return false;
+    }
+
+    // a quad has no inside, so the normal always faces the incoming ray
+    *payload = HitPayload(t, p, faceForward(n, ray.direction), vec2f(alpha, beta), quad.mat_idx, quad.lightIdx,
+                          NO_MEDIUM, vec3f(1.0));
+    return true;
+}
+
+fn intersectTriangle(ray: Ray, triangleIdx: u32, t_min: f32, t_nearest: f32, payload: ptr<function, HitPayload>) -> bool {
+    // Moller-Trumbore: solve for the distance and the barycentric coordinates of the hit at once
+    let triangle: Triangle = triangles[triangleIdx];
+    let e1: vec3f = triangle.p1.xyz - triangle.p0.xyz;
+    let e2: vec3f = triangle.p2.xyz - triangle.p0.xyz;
+    let pvec: vec3f = cross(ray.direction, e2);
+    let det: f32 = dot(e1, pvec);
+    if abs(det) < 1e-12 {
+        return false;
+    }
+
+    let invDet: f32 = 1.0 / det;
+    let tvec: vec3f = ray.origin - triangle.p0.xyz;
+    let b1: f32 = dot(tvec, pvec) * invDet;
+    if b1 < 0.0 || b1 > 1.0 {
+        return false;
+    }
+    let qvec: vec3f = cross(tvec, e1);
+    let b2: f32 = dot(ray.direction, qvec) * invDet;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return false;
+    }
+    let t: f32 = dot(e2, qvec) * invDet;
+    if t <= t_min || t >= t_nearest {
+        return false;
+    }
+
+    // like spheres, the normal points out of the front so glass meshes know which side they
+    // are entered from; a smooth normal is kept on the same side as the geometric one
+    let b0: f32 = 1.0 - b1 - b2;
+    let geometricNormal: vec3f = normalize(cross(e1, e2));
+    var n: vec3f = geometricNormal;
+    let smoothNormal: vec3f = b0 * triangle.n0.xyz + b1 * triangle.n1.xyz + b2 * triangle.n2.xyz;
+    if dot(smoothNormal, smoothNormal) > 1e-12 {
+        n = normalize(smoothNormal);
+        if dot(n, geometricNormal) < 0.0 {
+            n = -n;
+        }
+    }
+    let uv: vec2f = b0 * triangle.uv01.xy + b1 * triangle.uv01.zw + b2 * triangle.uv2;
+    let color: vec3f = b0 * unpackColor(triangle.colors.x) + b1 * unpackColor(triangle.colors.y) +
+        b2 * unpackColor(triangle.colors.z);
+    *payload = HitPayload(t, ray.origin + t * ray.direction, n, uv, triangle.mat_idx,
+                          triangle.lightIdx, NO_MEDIUM, color);
+    return true;
+}
+
+fn unpackColor(packed: u32) -> vec3f {
+    return srgbToLinear(unpack4x8unorm(packed).xyz);
+}
+
+fn intersectPlane(ray: Ray, planeIdx: u32, t_min: f32, t_nearest: f32, payload: ptr<function, HitPayload>) -> bool {
+    let plane: Plane = planes[planeIdx];
+    let n: vec3f = plane.normal.xyz;
+    let denom: f32 = dot(n, ray.direction);
+    if abs(denom) < 1e-8 {
+        return false;
+    }
+
+    let t: f32 = dot(n, plane.point.xyz - ray.origin) / denom;
+    if t <= t_min || t >= t_nearest {
+        return false;
+    }
+
+    let p: vec3f = ray.origin + t * ray.direction;
+    // planar mapping with one texture repeat per world unit
+    let tangent: vec3f = orthogonalTangent(n);
+    let bitangent: vec3f = cross(n, tangent);
+    let uv: vec2f = vec2f(dot(p - plane.point.xyz, tangent), dot(p - plane.point.xyz, bitangent));
+    *payload = HitPayload(t, p, faceForward(n, ray.direction), uv, plane.mat_idx, NO_LIGHT, NO_MEDIUM,
+                          vec3f(1.0));
+    return true;
+}
+
+fn intersectBox(ray: Ray, boxIdx: u32, t_min: f32, t_nearest: f32, payload: ptr<function, HitPayload>) -> bool {
+    // slab test; like spheres, boxes are closed so the normal always points out of the box
+    let aabox: AABox = boxes[boxIdx];
+    let t0: vec3f = (aabox.boxMin - ray.origin) * ray.invDirection;
+    let t1: vec3f = (aabox.boxMax - ray.origin) * ray.invDirection;
+    let t_small: vec3f = min(t0, t1);
+    let t_large: vec3f = max(t0, t1);
+    let t_enter: f32 = max(max(t_small.x, t_small.y), t_small.z);
+    let t_exit: f32 = min(min(t_large.x, t_large.y), t_large.z);
+    if t_enter > t_exit {
+        return false;
+    }
+
+    // if the entry point is behind us, the ray started inside the box and hits the exit face
+    var t: f32 = t_enter;
+    if t <= t_min {
+        t = t_exit;
+    }
+    if t <= t_min || t >= t_nearest {
+        return false;
+    }
+
+    let p: vec3f = ray.origin + t * ray.direction;
+    let n: vec3f = boxNormal(aabox, p);
+    // each face is mapped to the unit square using the two axes that span it
+    let local: vec3f = (p - aabox.boxMin) / max(aabox.boxMax - aabox.boxMin, vec3f(1e-6));
+    var uv: vec2f = local.xy;
+    if n.x != 0.0 {
+        uv = local.zy;
+    } else if n.y != 0.0 {
+        uv = local.xz;
+    }
+    *payload = HitPayload(t, p, n, uv, aabox.mat_idx, NO_LIGHT, aabox.mediumIdx, vec3f(1.0));
+    return true;
+}
+
+fn intersectShape(ray: Ray, shapeIdx: u32, t_min: f32, t_nearest: f32, payload: ptr<function, HitPayload>) -> bool {
+    // move the ray into the shape's object space; the direction is not renormalized so
+    // the hit distance t is the same in both spaces
+    let shape: Shape = shapes[shapeIdx];
+    let o: vec3f = (shape.worldToObject * vec4f(ray.origin, 1.0)).xyz;
+    let d: vec3f = (shape.worldToObject * vec4f(ray.direction, 0.0)).xyz;
+
+    var t: f32 = t_nearest;
+    var n: vec3f = vec3f(0.0);
+    var hitFound = false;
+    switch (shape.shapeType) {
+        case 1u {
+            hitFound = intersectLocalDisk(o, d, shape.radius, 0.0, 1.0, t_min, &t, &n);
+        }
+        case 2u {
+            hitFound = intersectLocalCone(o, d, shape.radius, shape.height, t_min, &t, &n);
+        }
+        case 3u {
+            hitFound = intersectLocalTorus(o, d, shape.radius, shape.minorRadius, t_min, &t, &n);
+        }
+        case 4u {
+            hitFound = intersectLocalBox(o, d, t_min, &t, &n);
+        }
+        case 0u, default {
+            hitFound = intersectLocalCylinder(o, d, shape.radius, shape.height, t_min, &t, &n);
+        }
+    }
+    if !hitFound {
+        return false;
+    }
+
+    // normals transform with the transpose of the inverse, which is worldToObject
+    var worldNormal: vec3f = normalize((vec4f(n, 0.0) * shape.worldToObject).xyz);
+    if shape.shapeType == 1u {
+        // a disk has no inside, so like a quad its normal always faces the incoming ray
+        worldNormal = faceForward(worldNormal, ray.direction);
+    }
+    let p: vec3f = ray.origin + t * ray.direction;
+    let uv: vec2f = shapeUV(shape, o + t * d, n);
+    *payload = HitPayload(t, p, worldNormal, uv, shape.mat_idx, shape.lightIdx, shape.mediumIdx,
+                          vec3f(1.0));
+    return true;
+}
+
+fn shapeUV(shape: Shape, p: vec3f, n: vec3f) -> vec2f {
+    // u runs around the y axis for all the shapes; caps and disks are mapped
+    // onto the unit square from above
+    let u: f32 = atan2(p.z, p.x) / (2.0 * PI) + 0.5;
+    let capUV: vec2f = 0.5 * vec2f(p.x, p.z) / shape.radius + 0.5;
+    switch (shape.shapeType) {
+        case 1u {
+            return capUV;
+        }
+        case 3u {
+            // v runs around the tube
+            let tube: vec2f = vec2f(length(p.xz) - shape.radius, p.y);
+            return vec2f(u, atan2(tube.y, tube.x) / (2.0 * PI) + 0.5);
+        }
+        case 4u {
+            // each face is mapped to the unit square using the two axes that span it, as for
+            // axis aligned boxes
+            if n.x != 0.0 {
+                return p.zy;
+            } else if n.y != 0.0 {
+                return p.xz;
+            }
+            return p.xy;
+        }
+        case 0u, 2u, default {
+            if abs(n.y) > 0.999 && (p.y < 1e-4 || p.y > shape.height - 1e-4) {
+                return capUV;
+            }
+            return vec2f(u, p.y / shape.height);
+        }
+    }
+}
+
+fn intersectLocalDisk(o: vec3f, d: vec3f, radius: f32, height: f32, normalY: f32, t_min: f32,
+                      t: ptr<function, f32>, n: ptr<function, vec3f>) -> bool {
+    // a disk of the given radius in the plane y = height; also used for the caps of cylinders and cones
+    if abs(d.y) < 1e-8 {
+        return false;
+    }
+    let tDisk: f32 = (height - o.y) / d.y;
+    if tDisk <= t_min || tDisk >= *t {
+        return false;
+    }
+    let p: vec3f = o + tDisk * d;
+    if p.x * p.x + p.z * p.z > radius * radius {
+        return false;
+    }
+    *t = tDisk;
+    *n = vec3f(0.0, normalY, 0.0);
+    return true;
+}
+
+fn intersectLocalCylinder(o: vec3f, d: vec3f, radius: f32, height: f32, t_min: f32,
+                          t: ptr<function, f32>, n: ptr<function, vec3f>) -> bool {
+    var hitFound = false;
+    let a: f32 = d.x * d.x + d.z * d.z;
+    if a > 1e-12 {
+        let b: f32 = o.x * d.x + o.z * d.z;
+        let c: f32 = o.x * o.x + o.z * o.z - radius * radius;
+        let discrim: f32 = b * b - a * c;
+        if discrim >= 0.0 {
+            let sqrtDiscrim: f32 = sqrt(discrim);
+            for (var i: u32 = 0; i < 2; i++) {
+                let tSide: f32 = select((-b + sqrtDiscrim) / a, (-b - sqrtDiscrim) / a, i == 0u);
+                let y: f32 = o.y + tSide * d.y;
+                if tSide > t_min && tSide < *t && y >= 0.0 && y <= height {
+                    let p: vec3f = o + tSide * d;
+                    *t = tSide;
+                    *n = vec3f(p.x, 0.0, p.z) / radius;
+                    hitFound = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    // the caps only replace the side hit if they are closer
+    if intersectLocalDisk(o, d, radius, 0.0, -1.0, t_min, t, n) {
+        hitFound = true;
+    }
+    if intersectLocalDisk(o, d, radius, height, 1.0, t_min, t, n) {
+        hitFound = true;
+    }
+    return hitFound;
+}
+
+fn intersectLocalCone(o: vec3f, d: vec3f, radius: f32, height: f32, t_min: f32,
+                      t: ptr<function, f32>, n: ptr<function, vec3f>) -> bool {
+    // the side satisfies x^2 + z^2 = k^2 (height - y)^2 with k = radius / height
+    var hitFound = false;
+    let k2: f32 = (radius / height) * (radius / height);
+    let h: f32 = height - o.y;
+    let a: f32 = d.x * d.x + d.z * d.z - k2 * d.y * d.y;
+    let b: f32 = o.x * d.x + o.z * d.z + k2 * h * d.y;
+    let c: f32 = o.x * o.x + o.z * o.z - k2 * h * h;
+
+    var roots = array<f32, 2>(-1.0, -1.0);
+    if abs(a) < 1e-12 {
+        // the ray is parallel to the side, so there is a single root
+        if abs(b) > 1e-12 {
+            roots[0] = -c / (2.0 * b);
+        }
+    } else {
+        let discrim: f32 = b * b - a * c;
+        if discrim >= 0.0 {
+            let sqrtDiscrim: f32 = sqrt(discrim);
+            let t0: f32 = (-b - sqrtDiscrim) / a;
+            let t1: f32 = (-b + sqrtDiscrim) / a;
+            roots[0] = min(t0, t1);
+            roots[1] = max(t0, t1);
+        }
+    }
+    for (var i: u32 = 0; i < 2; i++) {
+        let tSide: f32 = roots[i];
+        let y: f32 = o.y + tSide * d.y;
+        if tSide > t_min && tSide < *t && y >= 0.0 && y <= height {
+            let p: vec3f = o + tSide * d;
+            *t = tSide;
+            *n = normalize(vec3f(p.x, k2 * (height - p.y), p.z));
+            hitFound = true;
+            break;
+        }
+    }
+
+    if intersectLocalDisk(o, d, radius, 0.0, -1.0, t_min, t, n) {
+        hitFound = true;
+    }
+    return hitFound;
+}
+
+fn intersectLocalBox(o: vec3f, d: vec3f, t_min: f32, t: ptr<function, f32>,
+                     n: ptr<function, vec3f>) -> bool {
+    // the unit cube from the origin to (1, 1, 1), by the slab test; like an axis aligned box,
+    // a ray that starts inside hits the face it leaves by
+    let t0: vec3f = -o / d;
+    let t1: vec3f = (vec3f(1.0) - o) / d;
+    let t_small: vec3f = min(t0, t1);
+    let t_large: vec3f = max(t0, t1);
+    let t_enter: f32 = max(max(t_small.x, t_small.y), t_small.z);
+    let t_exit: f32 = min(min(t_large.x, t_large.y), t_large.z);
+    if t_enter > t_exit {
+        return false;
+    }
+    var tBox: f32 = t_enter;
+    if tBox <= t_min {
+        tBox = t_exit;
+    }
+    if tBox <= t_min || tBox >= *t {
+        return false;
+    }
+
+    // the face that was hit is the axis along which p is furthest from the center
+    let fromCenter: vec3f = o + tBox * d - vec3f(0.5);
+    let away: vec3f = abs(fromCenter);
+    if away.x >= away.y && away.x >= away.z {
+        *n = vec3f(sign(fromCenter.x), 0.0, 0.0);
+    } else if away.y >= away.z {
+        *n = vec3f(0.0, sign(fromCenter.y), 0.0);
+    } else {
+        *n = vec3f(0.0, 0.0, sign(fromCenter.z));
+    }
+    *t = tBox;
+    return true;
+}
+
+fn intersectLocalTorus(o: vec3f, d: vec3f, majorRadius: f32, minorRadius: f32, t_min: f32,
+                       t: ptr<function, f32>, n: ptr<function, vec3f>) -> bool {
+    // analytic quartic solution following https://iquilezles.org/articles/intersectors/
+    // it expects a unit direction and a torus around the z axis, so the ray is normalized
+    // and swizzled here and the distance is scaled back at the end
+    let dirLength: f32 = length(d);
+    let ro: vec3f = o.xzy;
+    let rd: vec3f = d.xzy / dirLength;
+    let tMinLocal: f32 = t_min * dirLength;
+    let tMaxLocal: f32 = *t * dirLength;
+
+    var po: f32 = 1.0;
+    let Ra2: f32 = majorRadius * majorRadius;
+    let ra2: f32 = minorRadius * minorRadius;
+    let m: f32 = dot(ro, ro);
+    let nd: f32 = dot(ro, rd);
+
+    // reject rays that miss the bounding sphere
+    let hSphere: f32 = nd * nd - m + (majorRadius + minorRadius) * (majorRadius + minorRadius);
+    if hSphere < 0.0 {
+        return false;
+    }
+
+    let k: f32 = (m - ra2 - Ra2) / 2.0;
+    var k3: f32 = nd;
+    var k2: f32 = nd * nd + Ra2 * rd.z * rd.z + k;
+    var k1: f32 = k * nd + Ra2 * ro.z * rd.z;
+    var k0: f32 = k * k + Ra2 * ro.z * ro.z - Ra2 * ra2;
+
+    // prevent |c1| from being too close to zero
+    if abs(k3 * (k3 * k3 - k2) + k1) < 0.01 {
+        po = -1.0;
+        let tmp: f32 = k1;
+        k1 = k3;
+        k3 = tmp;
+        k0 = 1.0 / k0;
+        k1 = k1 * k0;
+        k2 = k2 * k0;
+        k3 = k3 * k0;
+    }
+
+    var c2: f32 = 2.0 * k2 - 3.0 * k3 * k3;
+    var c1: f32 = k3 * (k3 * k3 - k2) + k1;
+    var c0: f32 = k3 * (k3 * (-3.0 * k3 * k3 + 4.0 * k2) - 8.0 * k1) + 4.0 * k0;
+    c2 /= 3.0;
+    c1 *= 2.0;
+    c0 /= 3.0;
+
+    let Q: f32 = c2 * c2 + c0;
+    let R: f32 = 3.0 * c0 * c2 - c2 * c2 * c2 - c1 * c1;
+    var h: f32 = R * R - Q * Q * Q;
+    var z: f32 = 0.0;
+    if h < 0.0 {
+        // 4 intersections
+        let sQ: f32 = sqrt(Q);
+        z = 2.0 * sQ * cos(acos(clamp(R / (sQ * Q), -1.0, 1.0)) / 3.0);
+    } else {
+        // 2 intersections
+        let sQ: f32 = pow(sqrt(h) + abs(R), 1.0 / 3.0);
+        z = sign(R) * abs(sQ + Q / sQ);
+    }
+    z = c2 - z;
+
+    var d1: f32 = z - 3.0 * c2;
+    var d2: f32 = z * z - 3.0 * c0;
+    if abs(d1) < 1.0e-4 {
+        if d2 < 0.0 {
+            return false;
+        }
+        d2 = sqrt(d2);
+    } else {
+        if d1 < 0.0 {
+            return false;
+        }
+        d1 = sqrt(d1 / 2.0);
+        d2 = c1 / d1;
+    }
+
+    var result: f32 = tMaxLocal;
+    h = d1 * d1 - z + d2;
+    if h > 0.0 {
+        h = sqrt(h);
+        result = closerTorusRoot(-d1 - h - k3, po, tMinLocal, result);
+        result = closerTorusRoot(-d1 + h - k3, po, tMinLocal, result);
+    }
+    h = d1 * d1 + z - d2;
+    if h > 0.0 {
+        h = sqrt(h);
+        result = closerTorusRoot(d1 - h - k3, po, tMinLocal, result);
+        result = closerTorusRoot(d1 + h - k3, po, tMinLocal, result);
+    }
+    if result >= tMaxLocal {
+        return false;
+    }
+
+    let pos: vec3f = ro + result * rd;
+    let localNormal: vec3f = normalize(pos * (dot(pos, pos) - ra2 - Ra2 * vec3f(1.0, 1.0, -1.0)));
+    *t = result / dirLength;
+    *n = localNormal.xzy;
+    return true;
+}
+
+fn closerTorusRoot(root: f32, po: f32, t_min: f32, t_best: f32) -> f32 {
+    // undo the reciprocal substitution made when the quartic was badly conditioned
+    var t: f32 = root;
+    if po < 0.0 {
+        t = 2.0 / root;
+    }
+    if t > t_min && t < t_best {
+        return t;
+    }
+    return t_best;
+}
+
+fn boxNormal(aabox: AABox, p: vec3f) -> vec3f {
+    // the face that was hit is the axis along which p is furthest from the center,
+    // relative to the half extent of the box
+    let center: vec3f = 0.5 * (aabox.boxMin + aabox.boxMax);
+    let halfExtent: vec3f = max(0.5 * (aabox.boxMax - aabox.boxMin), vec3f(1e-6));
+    let d: vec3f = (p - center) / halfExtent;
+    let ad: vec3f = abs(d);
+    if ad.x >= ad.y && ad.x >= ad.z {
+        return vec3f(sign(d.x), 0.0, 0.0);
+    } else if ad.y >= ad.z {
+        return vec3f(0.0, sign(d.y), 0.0);
+    }
+    return vec3f(0.0, 0.0, sign(d.z));
+}
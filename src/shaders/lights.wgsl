@@ -0,0 +1,386 @@
+// next event estimation: picking a light, sampling a point on it and weighing the result
+
+#include "bindings.wgsl"
+#include "common.wgsl"
+#include "intersect.wgsl"
+#include "materials.wgsl"
+#include "media.wgsl"
+#include "sampling.wgsl"
+
+// a point picked on a light, as seen from the point being shaded
+struct LightSample {
+    wi: vec3f,
+    dist: f32,
+    emission: vec3f,
+    // solid angle pdf, including the probability of picking the light
+    pdf: f32,
+}
+
+fn sampleDirectLight(ray: Ray, hit: HitPayload, medium: u32, state: ptr<function, u32>) -> vec3f {
+    // next event estimation: pick a point on one of the lights and, if nothing blocks it,
+    // return the light it sends toward the ray, weighted against finding the same light
+    // by BSDF sampling; only diffuse and principled surfaces can be lit this way
+    let mat_type: u32 = materials[hit.mat_idx].mat_type;
+    if mat_type != 0u && mat_type != 3u {
+        return vec3f(0.0);
+    }
+
+    var lightIdx: u32 = NO_LIGHT;
+    var sample: LightSample;
+    if !pickLightSample(hit.p, ray.time, state, &lightIdx, &sample) {
+        return vec3f(0.0);
+    }
+
+    var bsdfPdf: f32 = 0.0;
+    let f: vec3f = evalBsdf(hit, -normalize(ray.direction), sample.wi, &bsdfPdf);
+    if all(f == vec3f(0.0)) {
+        return vec3f(0.0);
+    }
+    return f * directLight(hit.p, ray.time, lightIdx, sample, bsdfPdf, medium);
+}
+
+fn sampleMediumLight(ray: Ray, p: vec3f, medium: u32, state: ptr<function, u32>) -> vec3f {
+    // next event estimation from a scattering event inside a medium, where the phase function
+    // takes the place of the BSDF
+    var lightIdx: u32 = NO_LIGHT;
+    var sample: LightSample;
+    if !pickLightSample(p, ray.time, state, &lightIdx, &sample) {
+        return vec3f(0.0);
+    }
+
+    let phase: f32 = henyeyGreenstein(dot(ray.direction, sample.wi), media[medium].g);
+    return phase * directLight(p, ray.time, lightIdx, sample, phase, medium);
+}
+
+fn pickLightSample(p: vec3f, time: f32, state: ptr<function, u32>, lightIdx: ptr<function, u32>,
+                   sample: ptr<function, LightSample>) -> bool {
+    // selects a light and samples it from p; false if there is nothing to light p with
+    if lightCount() == 0u {
+        return false;
+    }
+
+    var pmf: f32 = 0.0;
+    *lightIdx = selectLight(p, state, &pmf);
+    if pmf <= 0.0 {
+        return false;
+    }
+    *sample = sampleLight(*lightIdx, pmf, p, time, state);
+    return (*sample).pdf > 0.0 && any((*sample).emission != vec3f(0.0));
+}
+
+fn directLight(p: vec3f, time: f32, lightIdx: u32, sample: LightSample, scatterPdf: f32, medium: u32) -> vec3f {
+    // the light arriving at p from the light sample, weighted against finding it by scattering
+    // with scatterPdf, before the BSDF or phase function is applied
+    // punctual lights can't be hit by BSDF samples, so light sampling gets all the weight
+    var weight: f32 = 1.0;
+    if isAreaLight(lights[lightIdx]) {
+        weight = powerHeuristic(sample.pdf, scatterPdf);
+    }
+
+    // stop short of the light so its own surface doesn't count as a blocker
+    let transmittance: vec3f = shadowTransmittance(p, sample.wi, sample.dist - EPSILON, time, medium);
+    return transmittance * sample.emission * weight / sample.pdf;
+}
+
+fn lightCount() -> u32 {
+    // a scene without lights uploads a single placeholder entry
+    if lights[0].lightType == NO_LIGHT {
+        return 0u;
+    }
+    return arrayLength(&lights);
+}
+
+fn selectLight(p: vec3f, state: ptr<function, u32>, pmf: ptr<function, f32>) -> u32 {
+    // picks the light to sample from p and sets pmf to the chance of picking it
+    let count: u32 = lightCount();
+    switch (sampling_parameters.lightSampling) {
+        case 1u {
+            var lightIdx: u32 = min(u32(rngNextFloat(state) * f32(count)), count - 1u);
+            if rngNextFloat(state) >= lightSelection[lightIdx].aliasProbability {
+                lightIdx = lightSelection[lightIdx].aliasIdx;
+            }
+            *pmf = lightSelection[lightIdx].pmf;
+            return lightIdx;
+        }
+        case 2u {
+            return selectLightFromTree(p, state, pmf);
+        }
+        case 0u, default {
+            *pmf = 1.0 / f32(count);
+            return min(u32(rngNextFloat(state) * f32(count)), count - 1u);
+        }
+    }
+}
+
+fn lightPmf(lightIdx: u32, p: vec3f) -> f32 {
+    // the chance selectLight picks the light from p
+    switch (sampling_parameters.lightSampling) {
+        case 1u {
+            return lightSelection[lightIdx].pmf;
+        }
+        case 2u {
+            return lightTreePmf(lightIdx, p);
+        }
+        case 0u, default {
+            return 1.0 / f32(lightCount());
+        }
+    }
+}
+
+fn selectLightFromTree(p: vec3f, state: ptr<function, u32>, pmf: ptr<function, f32>) -> u32 {
+    // suns can't be placed in the tree, so they get picked first, each as likely as the
+    // whole tree; otherwise walk down the tree choosing children by their importance
+    let treeCount: u32 = lightTree[0].lightCount;
+    let sunCount: u32 = lightCount() - treeCount;
+    let sunProbability: f32 = lightTreeSunProbability();
+    if rngNextFloat(state) < sunProbability {
+        *pmf = sunProbability / f32(sunCount);
+        return treeCount + min(u32(rngNextFloat(state) * f32(sunCount)), sunCount - 1u);
+    }
+
+    var node: LightBVHNode = lightTree[0];
+    var probability: f32 = 1.0 - sunProbability;
+    while node.lightCount > 1u {
+        let left: LightBVHNode = lightTree[node.leftFirst];
+        let right: LightBVHNode = lightTree[node.leftFirst + 1u];
+        let leftImportance: f32 = lightNodeImportance(left, p);
+        let totalImportance: f32 = leftImportance + lightNodeImportance(right, p);
+        if totalImportance <= 0.0 {
+            *pmf = 0.0;
+            return NO_LIGHT;
+        }
+        let leftProbability: f32 = leftImportance / totalImportance;
+        if rngNextFloat(state) < leftProbability {
+            node = left;
+            probability *= leftProbability;
+        } else {
+            node = right;
+            probability *= 1.0 - leftProbability;
+        }
+    }
+    *pmf = select(0.0, probability, node.lightCount == 1u);
+    return node.leftFirst;
+}
+
+fn lightTreePmf(lightIdx: u32, p: vec3f) -> f32 {
+    // retraces the choices selectLightFromTree makes by following the light's bit trail
+    let treeCount: u32 = lightTree[0].lightCount;
+    let sunProbability: f32 = lightTreeSunProbability();
+    if lightIdx >= treeCount {
+        return sunProbability / f32(lightCount() - treeCount);
+    }
+
+    let bitTrail: u32 = lightSelection[lightIdx].bitTrail;
+    var node: LightBVHNode = lightTree[0];
+    var probability: f32 = 1.0 - sunProbability;
+    var depth: u32 = 0u;
+    while node.lightCount > 1u {
+        let left: LightBVHNode = lightTree[node.leftFirst];
+        let right: LightBVHNode = lightTree[node.leftFirst + 1u];
+        let leftImportance: f32 = lightNodeImportance(left, p);
+        let totalImportance: f32 = leftImportance + lightNodeImportance(right, p);
+        if totalImportance <= 0.0 {
+            return 0.0;
+        }
+        let leftProbability: f32 = leftImportance / totalImportance;
+        if ((bitTrail >> depth) & 1u) == 1u {
+            node = right;
+            probability *= 1.0 - leftProbability;
+        } else {
+            node = left;
+            probability *= leftProbability;
+        }
+        depth++;
+    }
+    return probability;
+}
+
+fn lightTreeSunProbability() -> f32 {
+    // the suns sit after the tree's lights in the light list
+    let treeCount: u32 = lightTree[0].lightCount;
+    let sunCount: u32 = lightCount() - treeCount;
+    if sunCount == 0u {
+        return 0.0;
+    }
+    return f32(sunCount) / f32(sunCount + select(0u, 1u, treeCount > 0u));
+}
+
+fn lightNodeImportance(node: LightBVHNode, p: vec3f) -> f32 {
+    // power over squared distance, with the distance kept from shrinking below the size of
+    // the node so points inside or close to it don't favour it without bound
+    let extent: vec3f = node.aabbMax - node.aabbMin;
+    let toCenter: vec3f = 0.5 * (node.aabbMin + node.aabbMax) - p;
+    let dist2: f32 = max(max(dot(toCenter, toCenter), 0.25 * dot(extent, extent)), 1e-6);
+    return node.power / dist2;
+}
+
+fn isAreaLight(light: Light) -> bool {
+    return light.lightType <= 3u;
+}
+
+fn sampleLight(lightIdx: u32, pmf: f32, p: vec3f, time: f32, state: ptr<function, u32>) -> LightSample {
+    // picks a point on the light and returns the direction and distance to it from p,
+    // the light's emission there and the solid angle pdf of the choice, given the
+    // chance pmf of having picked this light
+    let light: Light = lights[lightIdx];
+    if !isAreaLight(light) {
+        return samplePunctualLight(light, pmf, p, state);
+    }
+
+    var lightHit = HitPayload();
+    switch (light.lightType) {
+        case 1u {
+            let u1: f32 = rngNextFloat(state);
+            let u2: f32 = rngNextFloat(state);
+            lightHit.p = light.position.xyz + u1 * light.u.xyz + u2 * light.v.xyz;
+            lightHit.n = normalize(cross(light.u.xyz, light.v.xyz));
+            lightHit.uv = vec2f(u1, u2);
+        }
+        case 2u {
+            let disk: vec3f = rngNextVec3InUnitDisk(state);
+            lightHit.p = light.position.xyz + disk.x * light.u.xyz + disk.y * light.v.xyz;
+            lightHit.n = normalize(cross(light.u.xyz, light.v.xyz));
+            lightHit.uv = 0.5 * disk.xy + 0.5;
+        }
+        case 3u {
+            // uniform barycentric coordinates, by folding the unit square onto the triangle
+            var b1: f32 = rngNextFloat(state);
+            var b2: f32 = rngNextFloat(state);
+            if b1 + b2 > 1.0 {
+                b1 = 1.0 - b1;
+                b2 = 1.0 - b2;
+            }
+            let triangle: Triangle = triangles[light.primIdx];
+            lightHit.p = light.position.xyz + b1 * light.u.xyz + b2 * light.v.xyz;
+            lightHit.n = normalize(cross(light.u.xyz, light.v.xyz));
+            lightHit.uv = (1.0 - b1 - b2) * triangle.uv01.xy + b1 * triangle.uv01.zw + b2 * triangle.uv2;
+        }
+        case 0u, default {
+            lightHit = sampleSphereLight(spheres[light.primIdx], p, time, state);
+        }
+    }
+    lightHit.mat_idx = light.mat_idx;
+    lightHit.lightIdx = lightIdx;
+
+    var sample: LightSample;
+    let toLight: vec3f = lightHit.p - p;
+    sample.dist = length(toLight);
+    sample.wi = toLight / sample.dist;
+    sample.emission = materialEmission(light.mat_idx, lightHit);
+    sample.pdf = pmf * lightSolidAnglePdf(light, p, lightHit, time);
+    return sample;
+}
+
+fn samplePunctualLight(light: Light, pmf: f32, p: vec3f, state: ptr<function, u32>) -> LightSample {
+    // there is a single direction to pick (or a tiny cone of them for the sun), so the pdf
+    // is just the chance of picking the light and the emission is what arrives at p
+    var sample: LightSample;
+    sample.pdf = pmf;
+    switch (light.lightType) {
+        case 6u {
+            // the sun is far away, and a finite angular size gives it soft shadows
+            let w: vec3f = -light.u.xyz;
+            let tangent: vec3f = orthogonalTangent(w);
+            let bitangent: vec3f = cross(w, tangent);
+            let oneMinusCosTheta: f32 = rngNextFloat(state) * (1.0 - light.cosOuter);
+            let cosTheta: f32 = 1.0 - oneMinusCosTheta;
+            let sinTheta: f32 = sqrt(max(oneMinusCosTheta * (1.0 + cosTheta), 0.0));
+            let phi: f32 = 2.0 * PI * rngNextFloat(state);
+            sample.wi = sinTheta * cos(phi) * tangent + sinTheta * sin(phi) * bitangent + cosTheta * w;
+            sample.dist = 1e30;
+            sample.emission = light.intensity.xyz;
+        }
+        case 4u, 5u, default {
+            let toLight: vec3f = light.position.xyz - p;
+            let dist2: f32 = dot(toLight, toLight);
+            sample.dist = sqrt(dist2);
+            sample.wi = toLight / sample.dist;
+            sample.emission = light.intensity.xyz / dist2;
+            if light.lightType == 5u {
+                let cosAngle: f32 = dot(-sample.wi, light.u.xyz);
+                if light.cosInner > light.cosOuter {
+                    sample.emission *= smoothstep(light.cosOuter, light.cosInner, cosAngle);
+                } else {
+                    sample.emission *= step(light.cosOuter, cosAngle);
+                }
+            }
+        }
+    }
+    return sample;
+}
+
+fn sampleSphereLight(sphere: Sphere, p: vec3f, time: f32, state: ptr<function, u32>) -> HitPayload {
+    // from outside, sample a direction uniformly in the cone the sphere subtends and find
+    // where it meets the sphere; from inside, fall back to a uniform point on the surface
+    let center: vec3f = sphereCenter(sphere, time);
+    let toCenter: vec3f = center - p;
+    let dist2: f32 = dot(toCenter, toCenter);
+    let radius2: f32 = sphere.radius * sphere.radius;
+
+    var ray: Ray;
+    ray.origin = p;
+    ray.time = time;
+    var t: f32;
+    if dist2 <= radius2 {
+        let onSphere: vec3f = center + sphere.radius * normalize(rngNextVec3InUnitSphere(state));
+        t = length(onSphere - p);
+        ray.direction = (onSphere - p) / t;
+    } else {
+        let w: vec3f = toCenter / sqrt(dist2);
+        let tangent: vec3f = orthogonalTangent(w);
+        let bitangent: vec3f = cross(w, tangent);
+        let oneMinusCosTheta: f32 = rngNextFloat(state) * coneOneMinusCos(radius2 / dist2);
+        let cosTheta: f32 = 1.0 - oneMinusCosTheta;
+        let sinTheta: f32 = sqrt(max(oneMinusCosTheta * (1.0 + cosTheta), 0.0));
+        let phi: f32 = 2.0 * PI * rngNextFloat(state);
+        ray.direction = sinTheta * cos(phi) * tangent + sinTheta * sin(phi) * bitangent + cosTheta * w;
+
+        // the nearer root, with grazing directions clamped onto the silhouette
+        let b: f32 = dot(ray.direction, toCenter);
+        t = b - sqrt(max(radius2 - (dist2 - b * b), 0.0));
+    }
+    return hitSphere(t, ray, sphere, center);
+}
+
+fn lightPdfFrom(lightIdx: u32, p: vec3f, lightHit: HitPayload, time: f32) -> f32 {
+    // the solid angle pdf with which selecting and sampling a light from p gives the point in lightHit
+    return lightPmf(lightIdx, p) * lightSolidAnglePdf(lights[lightIdx], p, lightHit, time);
+}
+
+fn lightSolidAnglePdf(light: Light, p: vec3f, lightHit: HitPayload, time: f32) -> f32 {
+    // the solid angle pdf with which sampleLight, once the light is picked, gives the point in lightHit
+    var pdf: f32;
+    if light.lightType == 0u {
+        let sphere: Sphere = spheres[light.primIdx];
+        let center: vec3f = sphereCenter(sphere, time);
+        let dist2: f32 = dot(center - p, center - p);
+        let radius2: f32 = sphere.radius * sphere.radius;
+        if dist2 <= radius2 {
+            pdf = areaToSolidAngle(1.0 / (4.0 * PI * radius2), p, lightHit.p, lightHit.n);
+        } else {
+            pdf = 1.0 / (2.0 * PI * coneOneMinusCos(radius2 / dist2));
+        }
+    } else {
+        pdf = areaToSolidAngle(1.0 / light.area, p, lightHit.p, lightHit.n);
+    }
+    return pdf;
+}
+
+fn coneOneMinusCos(sin2ThetaMax: f32) -> f32 {
+    // 1 - cos of a cone's half angle from its squared sine, without cancellation for small cones
+    if sin2ThetaMax < 1e-4 {
+        return 0.5 * sin2ThetaMax;
+    }
+    return 1.0 - sqrt(1.0 - sin2ThetaMax);
+}
+
+fn areaToSolidAngle(areaPdf: f32, p: vec3f, lightPoint: vec3f, lightNormal: vec3f) -> f32 {
+    // lights emit from both sides, so only the angle to the surface matters
+    let toLight: vec3f = lightPoint - p;
+    let dist2: f32 = dot(toLight, toLight);
+    let cosLight: f32 = abs(dot(lightNormal, toLight)) / sqrt(dist2);
+    if cosLight < 1e-6 {
+        return 0.0;
+    }
+    return areaPdf * dist2 / cosLight;
+}
@@ -0,0 +1,423 @@
+// scattering off surfaces: the BSDFs, how they are sampled and their parameters at a hit
+
+#include "bindings.wgsl"
+#include "common.wgsl"
+#include "sampling.wgsl"
+#include "textures.wgsl"
+
+// the parameters of the principled BSDF at a hit, after textures are applied
+struct PrincipledParams {
+    baseColor: vec3f,
+    metallic: f32,
+    roughness: f32,
+    specular: f32,
+    clearcoat: f32,
+    clearcoatRoughness: f32,
+    sheen: f32,
+    sheenTint: f32,
+    transmission: f32,
+    ior: f32,
+}
+
+// how likely each lobe of the principled BSDF is to be sampled
+struct LobeProbabilities {
+    diffuse: f32,
+    specular: f32,
+    clearcoat: f32,
+    transmission: f32,
+}
+
+fn getScatterRay(inRay: ptr<function, Ray>, mat_idx: u32, hit: ptr<function, HitPayload>, state: ptr<function, u32>,
+                 pdf: ptr<function, f32>) -> vec3f {
+    // replaces inRay with the scattered ray and returns the attenuation along it; pdf is set
+    // to the solid angle pdf of the new direction, or zero when it was picked specularly
+    // when we show up here, hit.n is necessarily the outward normal of the sphere
+    // we need to orient it correctly
+    let payLoad = *hit;
+    var ray = Ray();
+    ray.origin = payLoad.p;
+    ray.time = (*inRay).time;
+
+    let mat_type: u32 = materials[mat_idx].mat_type;
+    var attenuation: vec3f = materialAlbedo(mat_idx, payLoad);
+    *pdf = 0.0;
+
+    switch (mat_type) {
+        case 0u, default {
+            // bounce off the side the ray arrived from
+            let n: vec3f = faceForward(payLoad.n, (*inRay).direction);
+            var randomBounce: vec3f = normalize(rngNextVec3InUnitSphere(state));
+
+            ray.direction = n + randomBounce;
+            if length(ray.direction) < 0.001 {
+                ray.direction = n;
+            }
+            // the normal plus a random unit vector is cosine distributed about the normal
+            *pdf = max(dot(n, normalize(ray.direction)), 0.0) * FRAC_1_PI;
+        }
+        case 1u {
+            var randomBounce: vec3f = normalize(rngNextVec3InUnitSphere(state));
+            let fuzz: f32 = materials[mat_idx].fuzz *
+                textureValue(materials[mat_idx].roughnessTexture, payLoad).g;
+            ray.direction = reflect((*inRay).direction, payLoad.n) + fuzz * randomBounce;
+        }
+        case 2u {
+            let refract_idx: f32 = materials[mat_idx].refract_idx;
+            var norm: vec3f = payLoad.n;
+            let uv = normalize((*inRay).direction);
+            var cosTheta: f32 = min(dot(norm, -uv), 1.0); // as uv represents incoming, -uv is outgoing direction
+            var etaOverEtaPrime: f32 = 0.0;
+
+            // in old code, the normal vector was always determined at the time of hit and properly directioned
+            // i.e. I determined if the hit was on the outside/front face by taking dot product of imcoming ray with
+            // the normal; if it was negative front_face was false and norm *= -1, so normal pointed inward
+            // in the case of a ray from inside hitting, dot(-inDir, norm) would be positive
+            //
+            // now i'm not doing that; so first I need to see if dot(norm, -uv) > 0, ie the incoming ray is on the
+            // outside, as norm is ALWAYS facing outward; if so, use 1/refract_index
+            if cosTheta >= 0.0 {
+                etaOverEtaPrime = 1.0 / refract_idx;
+            } else {
+            // however, if dot(norm, -uv) < 0, the incoming ray is on the inside; now I need to flip the norm to face
+            // inside; my initial calc of cosTheta is also off by a sign as the norm wasn't pointing the right way
+                etaOverEtaPrime = refract_idx;
+                norm *= -1.0;
+                cosTheta *= -1.0;
+            }
+
+            let reflectance: f32 = schlick(cosTheta, etaOverEtaPrime);
+            var refractDirection: vec3f = vec3f(0.0);
+
+            if refract(uv, norm, etaOverEtaPrime, &refractDirection) {
+                if reflectance > rngNextFloat(state) {
+                    ray.direction = reflect(uv, norm);
+                } else {
+                    ray.direction = refractDirection;
+                }
+            } else {
+                ray.direction = reflect(uv, norm);
+            }
+        }
+        case 3u {
+            var direction: vec3f = vec3f(0.0);
+            attenuation = samplePrincipled(mat_idx, payLoad, -normalize((*inRay).direction), state,
+                                           &direction, pdf);
+            ray.direction = direction;
+        }
+    }
+    // keep directions unit length so hit distances are true distances through media
+    ray.direction = normalize(ray.direction);
+    ray.invDirection = 1.0 / ray.direction;
+    *inRay = ray;
+    return attenuation;
+}
+
+fn evalBsdf(hit: HitPayload, wo: vec3f, wi: vec3f, pdf: ptr<function, f32>) -> vec3f {
+    // returns f * cos(theta_i) for light arriving from wi and leaving toward wo, along with
+    // the pdf getScatterRay would have picked wi with; zero for the specular materials
+    *pdf = 0.0;
+    switch (materials[hit.mat_idx].mat_type) {
+        case 0u {
+            let n: vec3f = faceForward(hit.n, -wo);
+            let cosI: f32 = dot(n, wi);
+            if cosI <= 0.0 {
+                return vec3f(0.0);
+            }
+            *pdf = cosI * FRAC_1_PI;
+            return materialAlbedo(hit.mat_idx, hit) * cosI * FRAC_1_PI;
+        }
+        case 3u {
+            return evalPrincipled(hit.mat_idx, hit, wo, wi, pdf);
+        }
+        default {
+            return vec3f(0.0);
+        }
+    }
+}
+
+fn evalPrincipled(mat_idx: u32, hit: HitPayload, wo: vec3f, wi: vec3f, pdf: ptr<function, f32>) -> vec3f {
+    // the reflection lobes only, in the same shading frame samplePrincipled uses
+    let params: PrincipledParams = principledParams(mat_idx, hit);
+    if dot(hit.n, wo) < 0.0 && params.transmission > 0.0 {
+        return vec3f(0.0);
+    }
+    let n: vec3f = faceForward(hit.n, -wo);
+    let tangent: vec3f = orthogonalTangent(n);
+    let bitangent: vec3f = cross(n, tangent);
+    let woLocal: vec3f = vec3f(dot(wo, tangent), dot(wo, bitangent), dot(wo, n));
+    let wiLocal: vec3f = vec3f(dot(wi, tangent), dot(wi, bitangent), dot(wi, n));
+    if woLocal.z <= 0.0 || wiLocal.z <= 0.0 {
+        return vec3f(0.0);
+    }
+    return evalPrincipledLocal(params, principledLobes(params, woLocal), woLocal, wiLocal, pdf);
+}
+
+fn principledParams(mat_idx: u32, hit: HitPayload) -> PrincipledParams {
+    // following glTF, the roughness texture holds roughness in green and metallic in blue
+    let material: Material = materials[mat_idx];
+    let metallicRoughness: vec3f = textureValue(material.roughnessTexture, hit);
+    var params: PrincipledParams;
+    params.baseColor = materialAlbedo(mat_idx, hit);
+    params.metallic = clamp(material.metallic * metallicRoughness.b, 0.0, 1.0);
+    params.roughness = clamp(material.roughness * metallicRoughness.g, 0.0, 1.0);
+    params.specular = material.specular;
+    params.clearcoat = material.clearcoat;
+    params.clearcoatRoughness = material.clearcoatRoughness;
+    params.sheen = material.sheen;
+    params.sheenTint = material.sheenTint;
+    params.transmission = material.transmission;
+    params.ior = material.refract_idx;
+    return params;
+}
+
+fn principledLobes(params: PrincipledParams, woLocal: vec3f) -> LobeProbabilities {
+    // pick lobes roughly in proportion to how much light they will carry
+    let dielectric: f32 = 1.0 - params.metallic;
+    let coatFresnel: f32 = params.clearcoat * schlickWeight(woLocal.z, 0.04);
+    let baseWeight: f32 = 1.0 - coatFresnel;
+
+    var lobes: LobeProbabilities;
+    lobes.diffuse = baseWeight * dielectric * (1.0 - params.transmission) *
+        (luminance(params.baseColor) + params.sheen);
+    lobes.specular = baseWeight * (1.0 - dielectric * params.transmission) *
+        max(luminance(schlickFresnel(principledF0(params), woLocal.z)), 0.001);
+    lobes.clearcoat = coatFresnel;
+    lobes.transmission = baseWeight * dielectric * params.transmission;
+
+    let total: f32 = lobes.diffuse + lobes.specular + lobes.clearcoat + lobes.transmission;
+    if total > 0.0 {
+        lobes.diffuse /= total;
+        lobes.specular /= total;
+        lobes.clearcoat /= total;
+        lobes.transmission /= total;
+    }
+    return lobes;
+}
+
+fn principledF0(params: PrincipledParams) -> vec3f {
+    // specular = 0.5 gives the 4% reflectance of most dielectrics, as in glTF
+    return mix(vec3f(0.08 * params.specular), params.baseColor, params.metallic);
+}
+
+fn samplePrincipled(mat_idx: u32, hit: HitPayload, wo: vec3f, state: ptr<function, u32>,
+                    direction: ptr<function, vec3f>, samplePdf: ptr<function, f32>) -> vec3f {
+    // samples one lobe of the BSDF; reflection lobes are weighted with the combined pdf of
+    // all the reflection lobes, while transmission is a separate rough dielectric interface
+    // and is treated like a specular bounce (samplePdf stays zero)
+    let params: PrincipledParams = principledParams(mat_idx, hit);
+
+    // from inside a transmissive object only the interface matters; otherwise
+    // the shading frame is put on the side the ray arrives from
+    let inside: bool = dot(hit.n, wo) < 0.0;
+    let n: vec3f = faceForward(hit.n, -wo);
+    let tangent: vec3f = orthogonalTangent(n);
+    let bitangent: vec3f = cross(n, tangent);
+    let woLocal: vec3f = vec3f(dot(wo, tangent), dot(wo, bitangent), dot(wo, n));
+    if woLocal.z <= 0.0 {
+        return vec3f(0.0);
+    }
+
+    let lobes: LobeProbabilities = principledLobes(params, woLocal);
+    let u: f32 = rngNextFloat(state);
+    var wiLocal: vec3f;
+    var weight: vec3f;
+    if (inside && params.transmission > 0.0) || u < lobes.transmission {
+        let eta: f32 = select(1.0 / params.ior, params.ior, inside);
+        var probability: f32 = lobes.transmission;
+        if inside {
+            probability = 1.0;
+        }
+        weight = sampleRoughDielectric(params, woLocal, eta, state, &wiLocal) / probability;
+    } else {
+        let alpha: f32 = max(params.roughness * params.roughness, 0.001);
+        let coatAlpha: f32 = max(params.clearcoatRoughness * params.clearcoatRoughness, 0.001);
+        let v: f32 = u - lobes.transmission;
+        if v < lobes.diffuse {
+            wiLocal = rngNextCosineDirection(state);
+        } else {
+            var h: vec3f;
+            if v < lobes.diffuse + lobes.specular {
+                h = sampleGGXVNDF(woLocal, alpha, rngNextFloat(state), rngNextFloat(state));
+            } else {
+                h = sampleGGXVNDF(woLocal, coatAlpha, rngNextFloat(state), rngNextFloat(state));
+            }
+            wiLocal = reflect(-woLocal, h);
+        }
+        if wiLocal.z <= 0.0 {
+            return vec3f(0.0);
+        }
+        var pdf: f32 = 0.0;
+        let f: vec3f = evalPrincipledLocal(params, lobes, woLocal, wiLocal, &pdf);
+        if pdf <= 0.0 {
+            return vec3f(0.0);
+        }
+        weight = f / pdf;
+        *samplePdf = pdf;
+    }
+
+    *direction = wiLocal.x * tangent + wiLocal.y * bitangent + wiLocal.z * n;
+    return weight;
+}
+
+fn evalPrincipledLocal(params: PrincipledParams, lobes: LobeProbabilities, woLocal: vec3f, wiLocal: vec3f,
+                       pdf: ptr<function, f32>) -> vec3f {
+    // returns f * cos(theta_i) of the reflection lobes and their combined sampling pdf;
+    // both directions are in the shading frame and above the surface
+    let h: vec3f = normalize(woLocal + wiLocal);
+    let cosO: f32 = woLocal.z;
+    let cosI: f32 = wiLocal.z;
+    let vDotH: f32 = max(dot(woLocal, h), 0.0);
+    let alpha: f32 = max(params.roughness * params.roughness, 0.001);
+    let coatAlpha: f32 = max(params.clearcoatRoughness * params.clearcoatRoughness, 0.001);
+    let dielectric: f32 = 1.0 - params.metallic;
+
+    // specular reflection off the base layer
+    let F: vec3f = schlickFresnel(principledF0(params), vDotH);
+    let D: f32 = ggxD(h.z, alpha);
+    let specular: vec3f = (1.0 - dielectric * params.transmission) *
+        F * D * smithG2(woLocal, wiLocal, alpha) / (4.0 * cosO);
+
+    // the diffuse layer only gets the light that the specular layer did not reflect
+    let diffuseWeight: f32 = dielectric * (1.0 - params.transmission) *
+        (1.0 - schlickWeight(vDotH, 0.08 * params.specular));
+    let sheenColor: vec3f = mix(vec3f(1.0), params.baseColor / max(luminance(params.baseColor), 1e-4),
+                                params.sheenTint);
+    let sheen: vec3f = params.sheen * sheenColor * pow(1.0 - max(dot(wiLocal, h), 0.0), 5.0);
+    let diffuse: vec3f = diffuseWeight * (params.baseColor * FRAC_1_PI + sheen) * cosI;
+
+    // the clearcoat sits on top and dims everything beneath it
+    let coatF: f32 = params.clearcoat * schlickWeight(vDotH, 0.04);
+    let coatD: f32 = ggxD(h.z, coatAlpha);
+    let clearcoat: f32 = coatF * coatD * smithG2(woLocal, wiLocal, coatAlpha) / (4.0 * cosO);
+    let baseWeight: f32 = 1.0 - params.clearcoat * schlickWeight(cosO, 0.04);
+
+    *pdf = lobes.diffuse * cosI * FRAC_1_PI +
+        lobes.specular * ggxVNDFReflectionPdf(woLocal, h, alpha) +
+        lobes.clearcoat * ggxVNDFReflectionPdf(woLocal, h, coatAlpha);
+    return baseWeight * (diffuse + specular) + vec3f(clearcoat);
+}
+
+fn sampleRoughDielectric(params: PrincipledParams, woLocal: vec3f, eta: f32, state: ptr<function, u32>,
+                         wiLocal: ptr<function, vec3f>) -> vec3f {
+    // samples a visible microfacet normal and then reflects or refracts about it according to
+    // the exact Fresnel term; with this scheme the weight reduces to G2 / G1
+    let alpha: f32 = max(params.roughness * params.roughness, 0.001);
+    let h: vec3f = sampleGGXVNDF(woLocal, alpha, rngNextFloat(state), rngNextFloat(state));
+    let cosOH: f32 = dot(woLocal, h);
+    let F: f32 = fresnelDielectric(cosOH, eta);
+
+    var refracted: vec3f = vec3f(0.0);
+    var tint: vec3f = vec3f(1.0);
+    if rngNextFloat(state) < F || !refract(-woLocal, h, eta, &refracted) {
+        *wiLocal = reflect(-woLocal, h);
+        if (*wiLocal).z <= 0.0 {
+            return vec3f(0.0);
+        }
+    } else {
+        *wiLocal = refracted;
+        if (*wiLocal).z >= 0.0 {
+            return vec3f(0.0);
+        }
+        // as in glTF, light passing through is tinted by the base color
+        tint = params.baseColor;
+    }
+    let absWi: vec3f = vec3f((*wiLocal).xy, abs((*wiLocal).z));
+    return tint * smithG2(woLocal, absWi, alpha) / smithG1(woLocal, alpha);
+}
+
+fn sampleGGXVNDF(wo: vec3f, alpha: f32, u1: f32, u2: f32) -> vec3f {
+    // Heitz 2018, "Sampling the GGX Distribution of Visible Normals"
+    let vh: vec3f = normalize(vec3f(alpha * wo.x, alpha * wo.y, wo.z));
+    let lensq: f32 = vh.x * vh.x + vh.y * vh.y;
+    var t1: vec3f = vec3f(1.0, 0.0, 0.0);
+    if lensq > 0.0 {
+        t1 = vec3f(-vh.y, vh.x, 0.0) / sqrt(lensq);
+    }
+    let t2: vec3f = cross(vh, t1);
+    let r: f32 = sqrt(u1);
+    let phi: f32 = 2.0 * PI * u2;
+    let p1: f32 = r * cos(phi);
+    let s: f32 = 0.5 * (1.0 + vh.z);
+    let p2: f32 = (1.0 - s) * sqrt(1.0 - p1 * p1) + s * r * sin(phi);
+    let nh: vec3f = p1 * t1 + p2 * t2 + sqrt(max(0.0, 1.0 - p1 * p1 - p2 * p2)) * vh;
+    return normalize(vec3f(alpha * nh.x, alpha * nh.y, max(0.0, nh.z)));
+}
+
+fn ggxVNDFReflectionPdf(wo: vec3f, h: vec3f, alpha: f32) -> f32 {
+    // D * G1 * (wo.h) / wo.z for the normal, times 1 / (4 wo.h) for the reflection
+    return ggxD(h.z, alpha) * smithG1(wo, alpha) / (4.0 * wo.z);
+}
+
+fn ggxD(cosThetaH: f32, alpha: f32) -> f32 {
+    let a2: f32 = alpha * alpha;
+    let d: f32 = cosThetaH * cosThetaH * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+fn smithLambda(w: vec3f, alpha: f32) -> f32 {
+    let cos2: f32 = w.z * w.z;
+    let tan2: f32 = max(1.0 - cos2, 0.0) / max(cos2, 1e-8);
+    return 0.5 * (-1.0 + sqrt(1.0 + alpha * alpha * tan2));
+}
+
+fn smithG1(w: vec3f, alpha: f32) -> f32 {
+    return 1.0 / (1.0 + smithLambda(w, alpha));
+}
+
+fn smithG2(wo: vec3f, wi: vec3f, alpha: f32) -> f32 {
+    return 1.0 / (1.0 + smithLambda(wo, alpha) + smithLambda(wi, alpha));
+}
+
+fn schlickFresnel(f0: vec3f, cosTheta: f32) -> vec3f {
+    return f0 + (1.0 - f0) * pow(1.0 - clamp(cosTheta, 0.0, 1.0), 5.0);
+}
+
+fn schlickWeight(cosTheta: f32, f0: f32) -> f32 {
+    return f0 + (1.0 - f0) * pow(1.0 - clamp(cosTheta, 0.0, 1.0), 5.0);
+}
+
+fn fresnelDielectric(cosThetaI: f32, eta: f32) -> f32 {
+    // exact Fresnel reflectance for unpolarized light; eta is the ratio of the incident
+    // to the transmitted index of refraction
+    let sin2T: f32 = eta * eta * (1.0 - cosThetaI * cosThetaI);
+    if sin2T >= 1.0 {
+        return 1.0;
+    }
+    let cosT: f32 = sqrt(1.0 - sin2T);
+    let rs: f32 = (eta * cosThetaI - cosT) / (eta * cosThetaI + cosT);
+    let rp: f32 = (cosThetaI - eta * cosT) / (cosThetaI + eta * cosT);
+    return 0.5 * (rs * rs + rp * rp);
+}
+
+fn materialAlbedo(mat_idx: u32, hit: HitPayload) -> vec3f {
+    let material: Material = materials[mat_idx];
+    return material.albedo.xyz * hit.color * textureValue(material.albedoTexture, hit);
+}
+
+fn materialEmission(mat_idx: u32, hit: HitPayload) -> vec3f {
+    let material: Material = materials[mat_idx];
+    if all(material.emission.xyz == vec3f(0.0)) {
+        return vec3f(0.0);
+    }
+    return material.emission.xyz * textureValue(material.emissionTexture, hit);
+}
+
+fn schlick(cosine: f32, refractionIndex: f32) -> f32 {
+    var r0 = (1f - refractionIndex) / (1f + refractionIndex);
+    r0 = r0 * r0;
+    return r0 + (1f - r0) * pow((1f - cosine), 5f);
+}
+
+fn reflect(r: vec3f, n: vec3f) -> vec3f {
+    return r - 2.0 * dot(r,n) * n;
+}
+
+fn refract(uv: vec3f, n: vec3f, ri: f32, dir: ptr<function, vec3f>) -> bool {
+    let cosTheta: f32 = dot(uv, n);
+    let k: f32 = 1 - ri * ri * (1 - cosTheta * cosTheta);
+    if k >= 0.0 {
+        *dir = ri * uv - (ri * cosTheta + sqrt(k)) * n;
+        return true;
+    }
+    return false;
+}
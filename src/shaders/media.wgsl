@@ -0,0 +1,139 @@
+// homogeneous volumes: free flight sampling, transmittance and the phase function
+
+#include "bindings.wgsl"
+#include "bvh.wgsl"
+#include "common.wgsl"
+#include "sampling.wgsl"
+
+// how many volume boundaries a path or shadow ray may cross between two scattering events
+const MAX_CROSSINGS: u32 = 16;
+
+fn shadowTransmittance(origin: vec3f, direction: vec3f, dist: f32, time: f32, medium: u32) -> vec3f {
+    // the fraction of light that makes it along a shadow ray: zero if a surface is in the way,
+    // otherwise what the media it passes through let through
+    var shadowRay: Ray;
+    shadowRay.origin = origin;
+    shadowRay.direction = direction;
+    shadowRay.invDirection = 1.0 / direction;
+    shadowRay.time = time;
+    if !sceneHasMedia() {
+        if TraceShadowRay(shadowRay, dist) {
+            return vec3f(0.0);
+        }
+        return vec3f(1.0);
+    }
+
+    var transmittance: vec3f = vec3f(1.0);
+    var currentMedium: u32 = medium;
+    var remaining: f32 = dist;
+    for (var crossing: u32 = 0; crossing < MAX_CROSSINGS; crossing++) {
+        var hit = HitPayload();
+        let hitFound: bool = traverseScene(shadowRay, remaining, false, &hit);
+        var segment: f32 = remaining;
+        if hitFound {
+            segment = hit.t;
+        }
+        if currentMedium != NO_MEDIUM {
+            transmittance *= exp(-mediumExtinction(currentMedium) * segment);
+        }
+        if !hitFound {
+            return transmittance;
+        }
+        if hit.mediumIdx == NO_MEDIUM {
+            return vec3f(0.0);
+        }
+        currentMedium = mediumBeyond(hit, direction);
+        shadowRay.origin = hit.p;
+        remaining -= hit.t;
+    }
+    return vec3f(0.0);
+}
+
+fn globalMedium() -> u32 {
+    // the fog, or NO_MEDIUM if it is empty
+    if all(mediumExtinction(0u) == vec3f(0.0)) {
+        return NO_MEDIUM;
+    }
+    return 0u;
+}
+
+fn sceneHasMedia() -> bool {
+    return arrayLength(&media) > 1u || globalMedium() != NO_MEDIUM;
+}
+
+fn mediumBeyond(boundary: HitPayload, direction: vec3f) -> u32 {
+    // the medium a ray is in after crossing a volume boundary; normals point out of closed
+    // primitives, so going against the normal means going in
+    if dot(direction, boundary.n) < 0.0 {
+        return boundary.mediumIdx;
+    }
+    return globalMedium();
+}
+
+fn mediumExtinction(medium: u32) -> vec3f {
+    return media[medium].absorption.xyz + media[medium].scattering.xyz;
+}
+
+fn sampleMediumDistance(medium: u32, tMax: f32, state: ptr<function, u32>, throughput: ptr<function, vec3f>,
+                        dist: ptr<function, f32>) -> bool {
+    // free-flight sampling: draw the distance to the next collision from one color channel's
+    // extinction, picked at random, and weight by the average pdf over the channels; returns
+    // true and sets dist if the ray scatters before tMax, which is where it would hit a surface
+    let sigmaS: vec3f = media[medium].scattering.xyz;
+    let sigmaT: vec3f = mediumExtinction(medium);
+    let channel: u32 = min(u32(rngNextFloat(state) * 3.0), 2u);
+    *dist = 1e30;
+    if sigmaT[channel] > 0.0 {
+        *dist = -log(1.0 - rngNextFloat(state)) / sigmaT[channel];
+    }
+
+    let scattered: bool = *dist < tMax;
+    let transmittance: vec3f = exp(-sigmaT * min(*dist, tMax));
+    // the chance of scattering at dist, or of getting past tMax
+    var density: vec3f = transmittance;
+    var weight: vec3f = transmittance;
+    if scattered {
+        density = sigmaT * transmittance;
+        weight = sigmaS * transmittance;
+    }
+    let pdf: f32 = (density.x + density.y + density.z) / 3.0;
+    if pdf <= 0.0 {
+        *throughput = vec3f(0.0);
+    } else {
+        *throughput *= weight / pdf;
+    }
+    return scattered;
+}
+
+fn getPhaseScatterRay(inRay: Ray, p: vec3f, medium: u32, state: ptr<function, u32>, pdf: ptr<function, f32>) -> Ray {
+    // scatters the ray at p by the medium's phase function, which it samples exactly, so the
+    // weight is one and only the pdf is needed for MIS
+    let g: f32 = media[medium].g;
+    let u: f32 = rngNextFloat(state);
+    var cosTheta: f32 = 1.0 - 2.0 * u;
+    if abs(g) >= 1e-3 {
+        let sqrTerm: f32 = (1.0 - g * g) / (1.0 - g + 2.0 * g * u);
+        cosTheta = (1.0 + g * g - sqrTerm * sqrTerm) / (2.0 * g);
+    }
+    cosTheta = clamp(cosTheta, -1.0, 1.0);
+    let sinTheta: f32 = sqrt(max(1.0 - cosTheta * cosTheta, 0.0));
+    let phi: f32 = 2.0 * PI * rngNextFloat(state);
+
+    let w: vec3f = inRay.direction;
+    let tangent: vec3f = orthogonalTangent(w);
+    let bitangent: vec3f = cross(w, tangent);
+
+    var ray: Ray;
+    ray.origin = p;
+    ray.direction = normalize(sinTheta * cos(phi) * tangent + sinTheta * sin(phi) * bitangent + cosTheta * w);
+    ray.invDirection = 1.0 / ray.direction;
+    ray.time = inRay.time;
+    *pdf = henyeyGreenstein(cosTheta, g);
+    return ray;
+}
+
+fn henyeyGreenstein(cosTheta: f32, g: f32) -> f32 {
+    // the phase function, and its pdf, for light turning by the angle whose cosine is given
+    let denom: f32 = 1.0 + g * g - 2.0 * g * cosTheta;
+    return 0.25 * FRAC_1_PI * (1.0 - g * g) / (denom * sqrt(denom));
+}
@@ -0,0 +1,34 @@
+// the PCG generator and the hashes that seed it and the samplers
+
+fn hashCombine(seed: u32, value: u32) -> u32 {
+    return seed ^ (jenkinsHash(value) + 0x9e3779b9u + (seed << 6u) + (seed >> 2u));
+}
+
+fn toUnitFloat(x: u32) -> f32 {
+    // the top 24 bits, which is all an f32 below one can hold
+    return f32(x >> 8u) / 16777216.0;
+}
+
+fn initRng(pixel: vec2<u32>, resolution: vec2<u32>, frameSeed: u32) -> u32 {
+    let pixelIndex = dot(pixel, vec2<u32>(1u, resolution.x));
+    return jenkinsHash(hashCombine(frameSeed, pixelIndex));
+}
+
+fn rngNextInt(state: ptr<function, u32>) {
+    // PCG random number generator
+    // Based on https://www.shadertoy.com/view/XlGcRh
+
+    let oldState = *state + 747796405u + 2891336453u;
+    let word = ((oldState >> ((oldState >> 28u) + 4u)) ^ oldState) * 277803737u;
+    *state = (word >> 22u) ^ word;
+}
+
+fn jenkinsHash(input: u32) -> u32 {
+    var x = input;
+    x += x << 10u;
+    x ^= x >> 6u;
+    x += x << 3u;
+    x ^= x >> 11u;
+    x += x << 15u;
+    return x;
+}
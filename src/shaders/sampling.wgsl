@@ -0,0 +1,246 @@
+// the samplers, which draw each sample's numbers dimension by dimension, and the points and
+// directions made from them
+
+#include "bindings.wgsl"
+#include "common.wgsl"
+#include "rng.wgsl"
+
+// the low discrepancy samplers index their sequences by sample and dimension: each sample
+// starts at dimension 0, the camera ray takes the first CAMERA_DIMENSIONS and every bounce
+// starts BOUNCE_DIMENSIONS further on; draws past those go to the PCG stream instead, so a
+// bounce that crosses many volume boundaries can't read the next bounce's dimensions.
+// sampler.rs draws the same numbers on the CPU
+const CAMERA_DIMENSIONS: u32 = 8;
+const BOUNCE_DIMENSIONS: u32 = 32;
+// the fractional parts of the inverse plastic number and its square, in 0.32 fixed point
+const R2_ALPHAS = vec2u(3242174889u, 2447445414u);
+// a private variable rather than a constant, as naga only lets constant arrays be indexed by constants
+var<private> haltonPrimes: array<u32, 32> = array<u32, 32>(
+    2u, 3u, 5u, 7u, 11u, 13u, 17u, 19u, 23u, 29u, 31u, 37u, 41u, 43u, 47u, 53u,
+    59u, 61u, 67u, 71u, 73u, 79u, 83u, 89u, 97u, 101u, 103u, 107u, 109u, 113u, 127u, 131u,
+);
+
+// the pixel and sample the invocation is working on, for the samplers
+var<private> samplePixel: vec2u;
+var<private> pixelSeed: u32;
+var<private> blueNoiseShift: vec2u;
+var<private> sampleIndex: u32;
+var<private> sampleDimension: u32;
+// the first dimension past the camera ray's or the current bounce's share
+var<private> dimensionLimit: u32;
+
+fn powerHeuristic(pdf: f32, otherPdf: f32) -> f32 {
+    let a: f32 = pdf * pdf;
+    let b: f32 = otherPdf * otherPdf;
+    if a + b <= 0.0 {
+        return 0.0;
+    }
+    return a / (a + b);
+}
+
+fn rngNextInUnitHemisphere(state: ptr<function, u32>) -> vec3<f32> {
+    let r1 = rngNextFloat(state);
+    let r2 = rngNextFloat(state);
+
+    let phi = 2.0 * PI * r1;
+    let sinTheta = sqrt(1.0 - r2 * r2);
+
+    let x = cos(phi) * sinTheta;
+    let y = sin(phi) * sinTheta;
+    let z = r2;
+
+    return vec3(x, y, z);
+}
+
+fn rngNextCosineDirection(state: ptr<function, u32>) -> vec3<f32> {
+    // cosine weighted direction about +z, by projecting a uniform disk sample up
+    let disk = rngNextVec3InUnitDisk(state);
+    return vec3(disk.x, disk.y, sqrt(max(0.0, 1.0 - disk.x * disk.x - disk.y * disk.y)));
+}
+
+fn rngNextVec3InUnitDisk(state: ptr<function, u32>) -> vec3<f32> {
+    // Generate numbers uniformly in a disk:
+    // https://stats.stackexchange.com/a/481559
+
+    // r^2 is distributed as U(0, 1).
+    let r = sqrt(rngNextFloat(state));
+    let alpha = 2.0 * PI * rngNextFloat(state);
+
+    let x = r * cos(alpha);
+    let y = r * sin(alpha);
+
+    return vec3(x, y, 0.0);
+}
+
+fn rngNextVec3InUnitSphere(state: ptr<function, u32>) -> vec3<f32> {
+    // probability density is uniformly distributed over r^3
+    let r = pow(rngNextFloat(state), 0.33333f);
+    // and need to distribute theta according to arccos(U[-1,1])
+//    let theta = acos(2f * rngNextFloat(state) - 1.0);
+    let cosTheta = 2f * rngNextFloat(state) - 1f;
+    let sinTheta = sqrt(1 - cosTheta * cosTheta);
+    let phi = 2.0 * PI * rngNextFloat(state);
+
+    let x = r * sinTheta * cos(phi);
+    let y = r * sinTheta * sin(phi);
+    let z = r * cosTheta;
+
+    return vec3(x, y, z);
+}
+
+fn rngNextUintInRange(state: ptr<function, u32>, min: u32, max: u32) -> u32 {
+    rngNextInt(state);
+    return min + (*state) % (max - min);
+}
+
+fn rngNextFloat(state: ptr<function, u32>) -> f32 {
+    // the PCG stream in state, unless one of the low discrepancy samplers is picked and the
+    // camera ray or bounce still has dimensions left
+    if sampling_parameters.samplerType != 0u && sampleDimension < dimensionLimit {
+        return nextSample();
+    }
+    rngNextInt(state);
+    return f32(*state) / f32(0xffffffffu);
+}
+
+fn startPixel(pixel: vec2u, resolution: vec2u) -> u32 {
+    // sets the samplers up for a pixel, and returns the start of its PCG stream
+    let state: u32 = initRng(pixel, resolution, sampling_parameters.seed);
+    samplePixel = pixel;
+    blueNoiseShift = vec2u(sampling_parameters.seed & 0xffffu, sampling_parameters.seed >> 16u);
+    pixelSeed = state;
+    return state;
+}
+
+fn startSample(index: u32) {
+    sampleIndex = index;
+    sampleDimension = 0u;
+    dimensionLimit = CAMERA_DIMENSIONS;
+}
+
+fn startBounce(bounce: u32) {
+    sampleDimension = CAMERA_DIMENSIONS + bounce * BOUNCE_DIMENSIONS;
+    dimensionLimit = sampleDimension + BOUNCE_DIMENSIONS;
+}
+
+fn nextSample() -> f32 {
+    // the next dimension of the current sample from the chosen sequence
+    let dimension: u32 = sampleDimension;
+    sampleDimension++;
+    switch (sampling_parameters.samplerType) {
+        case 2u {
+            let size: vec2u = textureDimensions(blueNoiseMask);
+            let offset: vec2u = blueNoiseOffset(dimension, size.x);
+            let texel: vec2u = (samplePixel + offset + blueNoiseShift) % size;
+            let rotation: f32 = textureLoad(blueNoiseMask, texel, 0).r;
+            return r2Sample(sampleIndex, dimension, u32(rotation * 16777216.0) * 256u);
+        }
+        case 3u {
+            return r2Sample(sampleIndex, dimension, hashCombine(pixelSeed, dimension));
+        }
+        case 4u {
+            return haltonSample(sampleIndex, dimension, pixelSeed);
+        }
+        case 1u, default {
+            return sobolSample(sampleIndex, dimension, pixelSeed);
+        }
+    }
+}
+
+fn sobol(index: u32, dimension: u32) -> u32 {
+    // the unscrambled Sobol sequence, in 0.32 fixed point; dimension 0 is the van der Corput
+    // sequence and the next three follow Joe and Kuo's primitive polynomials
+    if dimension == 0u {
+        return reverseBits(index);
+    }
+    // the degree, coefficients and initial direction numbers of each polynomial
+    var degree: u32 = 3u;
+    var coefficients: u32 = 1u;
+    var initial = vec3u(1u, 3u, 1u);
+    if dimension == 1u {
+        degree = 1u;
+        coefficients = 0u;
+        initial = vec3u(1u, 0u, 0u);
+    } else if dimension == 2u {
+        degree = 2u;
+        initial = vec3u(1u, 3u, 0u);
+    }
+
+    // the direction numbers are built bit by bit, keeping the last three
+    var window = vec3u(0u);
+    var x: u32 = 0u;
+    for (var bit: u32 = 0u; bit < 32u; bit++) {
+        if (index >> bit) == 0u {
+            break;
+        }
+        var m: u32;
+        if bit < degree {
+            m = initial[bit];
+        } else {
+            let older: u32 = window[degree - 1u];
+            m = older ^ (older << degree);
+            for (var k: u32 = 1u; k < degree; k++) {
+                if ((coefficients >> (degree - 1u - k)) & 1u) == 1u {
+                    m ^= window[k - 1u] << k;
+                }
+            }
+        }
+        if ((index >> bit) & 1u) == 1u {
+            x ^= m << (31u - bit);
+        }
+        window = vec3u(m, window.x, window.y);
+    }
+    return x;
+}
+
+fn sobolSample(index: u32, dimension: u32, seed: u32) -> f32 {
+    // Burley's shuffled, Owen scrambled Sobol points: every group of four dimensions is its own
+    // Sobol set with the sample order shuffled per pixel and group
+    let groupSeed: u32 = hashCombine(seed, dimension / 4u);
+    let shuffled: u32 = nestedUniformScramble(index, groupSeed);
+    let component: u32 = dimension % 4u;
+    return toUnitFloat(nestedUniformScramble(sobol(shuffled, component), hashCombine(groupSeed, component + 1u)));
+}
+
+fn r2Sample(index: u32, dimension: u32, rotation: u32) -> f32 {
+    // pairs of dimensions take the two components of the R2 sequence, rotated by a 0.32 fixed
+    // point offset
+    return toUnitFloat(0x80000000u + index * R2_ALPHAS[dimension % 2u] + rotation);
+}
+
+fn haltonSample(index: u32, dimension: u32, seed: u32) -> f32 {
+    // the radical inverse of index in a prime base, with each digit put through its own random
+    // linear permutation, which keeps the first few samples of the large bases apart
+    let base: u32 = haltonPrimes[dimension % 32u];
+    let dimensionSeed: u32 = hashCombine(seed, dimension);
+    let inverseBase: f32 = 1.0 / f32(base);
+    var factor: f32 = inverseBase;
+    var radicalInverse: f32 = 0.0;
+    var i: u32 = index;
+    for (var level: u32 = 0u; factor > 1e-7; level++) {
+        let digitSeed: u32 = hashCombine(dimensionSeed, level);
+        let scale: u32 = 1u + digitSeed % (base - 1u);
+        let shift: u32 = jenkinsHash(digitSeed) % base;
+        radicalInverse += f32((scale * (i % base) + shift) % base) * factor;
+        i /= base;
+        factor *= inverseBase;
+    }
+    return min(radicalInverse, 0.99999994);
+}
+
+fn blueNoiseOffset(dimension: u32, size: u32) -> vec2u {
+    // where the mask is read for a dimension, so each dimension sees it shifted
+    let step: u32 = dimension + 1u;
+    return vec2u((step * R2_ALPHAS.x) >> 16u, (step * R2_ALPHAS.y) >> 16u) % size;
+}
+
+fn nestedUniformScramble(x: u32, seed: u32) -> u32 {
+    // Laine and Karras' permutation, which scrambles each bit by the bits below it; between the
+    // two reversals that is Owen scrambling
+    var v: u32 = reverseBits(x) + seed;
+    v ^= v * 0x6c50b47cu;
+    v ^= v * 0xb82f1e52u;
+    v ^= v * 0xc7afe638u;
+    v ^= v * 0x8d22f6e6u;
+    return reverseBits(v);
+}
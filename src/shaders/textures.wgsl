@@ -0,0 +1,87 @@
+// texture lookups: images, checkers and Perlin noise
+
+#include "bindings.wgsl"
+#include "common.wgsl"
+#include "rng.wgsl"
+
+fn textureValue(textureIdx: u32, hit: HitPayload) -> vec3f {
+    // an unused texture slot leaves the material's constant unchanged
+    if textureIdx == NO_TEXTURE {
+        return vec3f(1.0);
+    }
+    let texture: Texture = textures[textureIdx];
+    switch (texture.textureType) {
+        case 1u {
+            let cell: vec3i = vec3i(floor(hit.p / texture.scale));
+            if ((cell.x + cell.y + cell.z) & 1) == 0 {
+                return texture.color0.xyz;
+            }
+            return texture.color1.xyz;
+        }
+        case 2u {
+            // marble: a sine wave along z disturbed by turbulence
+            let marble: f32 = 0.5 * (1.0 + sin(texture.scale * hit.p.z + 10.0 * turbulence(hit.p, 7u)));
+            return texture.color0.xyz * marble;
+        }
+        case 0u, default {
+            // images are stored top row first, while v runs upward
+            let uv: vec2f = vec2f(hit.uv.x, 1.0 - hit.uv.y);
+            let texel: vec3f = textureSampleLevel(textureImages, textureSampler, uv,
+                                                  texture.imageLayer, 0.0).rgb;
+            if texture.srgb != 0u {
+                return srgbToLinear(texel);
+            }
+            return texel;
+        }
+    }
+}
+
+fn srgbToLinear(c: vec3f) -> vec3f {
+    let low: vec3f = c / 12.92;
+    let high: vec3f = pow((c + 0.055) / 1.055, vec3f(2.4));
+    return select(high, low, c <= vec3f(0.04045));
+}
+
+fn turbulence(p: vec3f, depth: u32) -> f32 {
+    var accum: f32 = 0.0;
+    var tempP: vec3f = p;
+    var weight: f32 = 1.0;
+    for (var i: u32 = 0; i < depth; i++) {
+        accum += weight * perlinNoise(tempP);
+        weight *= 0.5;
+        tempP *= 2.0;
+    }
+    return abs(accum);
+}
+
+fn perlinNoise(p: vec3f) -> f32 {
+    // gradient noise; rather than the permutation tables of the book, the gradient at each
+    // lattice point comes from hashing its coordinates, so no extra buffers are needed
+    let cell: vec3f = floor(p);
+    let f: vec3f = p - cell;
+    // Hermite cubic smoothing of the interpolation weights
+    let w: vec3f = f * f * (3.0 - 2.0 * f);
+
+    var accum: f32 = 0.0;
+    for (var i: u32 = 0; i < 2; i++) {
+        for (var j: u32 = 0; j < 2; j++) {
+            for (var k: u32 = 0; k < 2; k++) {
+                let corner: vec3f = vec3f(f32(i), f32(j), f32(k));
+                let gradient: vec3f = perlinGradient(vec3i(cell + corner));
+                let weight: vec3f = mix(1.0 - w, w, corner);
+                accum += weight.x * weight.y * weight.z * dot(gradient, f - corner);
+            }
+        }
+    }
+    return accum;
+}
+
+fn perlinGradient(lattice: vec3i) -> vec3f {
+    let h: u32 = jenkinsHash(bitcast<u32>(lattice.x) ^
+        jenkinsHash(bitcast<u32>(lattice.y) ^ jenkinsHash(bitcast<u32>(lattice.z))));
+    // a random unit vector from two hashed floats
+    let cosTheta: f32 = 2.0 * f32(h & 0xffffu) / 65535.0 - 1.0;
+    let sinTheta: f32 = sqrt(1.0 - cosTheta * cosTheta);
+    let phi: f32 = 2.0 * PI * f32(h >> 16u) / 65535.0;
+    return vec3f(sinTheta * cos(phi), sinTheta * sin(phi), cosTheta);
+}
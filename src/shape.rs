@@ -0,0 +1,171 @@
+use glam::{Mat4, Vec3};
+use crate::light::NO_LIGHT;
+use crate::medium::{gpu_medium_idx, NO_MEDIUM};
+
+// shape_type will be indexed as follows:
+// 0 Cylinder; 1 Disk; 2 Cone; 3 Torus; 4 Box
+//
+// every shape is defined in its own object space with its axis along +y:
+// - cylinder: radius, from y = 0 to y = height, capped at both ends
+// - disk: radius, lying in the y = 0 plane
+// - cone: base of the given radius at y = 0, apex at y = height, capped at the base
+// - torus: centered at the origin in the xz plane, with major and minor radius
+// - box: the unit cube from the origin to (1, 1, 1), stretched to size by the transform
+// the transform places the shape in the world; the GPU only needs its inverse
+
+enum ShapeType {
+    Cylinder = 0,
+    Disk = 1,
+    Cone = 2,
+    Torus = 3,
+    Box = 4,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct Shape {
+    world_to_object: Mat4,
+    radius: f32,
+    height: f32,
+    minor_radius: f32,
+    shape_type: u32,
+    material_idx: u32,
+    // index into the scene's light list if the shape is an emissive disk
+    light_idx: u32,
+    medium_idx: u32,
+    _buffer: u32,
+}
+
+unsafe impl bytemuck::Pod for Shape {}
+unsafe impl bytemuck::Zeroable for Shape {}
+
+impl Shape {
+    pub fn cylinder(radius: f32, height: f32, transform: Mat4, material_idx: u32) -> Self {
+        Self::new(ShapeType::Cylinder, radius, height, 0.0, transform, material_idx)
+    }
+
+    pub fn disk(radius: f32, transform: Mat4, material_idx: u32) -> Self {
+        Self::new(ShapeType::Disk, radius, 0.0, 0.0, transform, material_idx)
+    }
+
+    pub fn cone(radius: f32, height: f32, transform: Mat4, material_idx: u32) -> Self {
+        Self::new(ShapeType::Cone, radius, height, 0.0, transform, material_idx)
+    }
+
+    pub fn torus(major_radius: f32, minor_radius: f32, transform: Mat4, material_idx: u32) -> Self {
+        Self::new(ShapeType::Torus, major_radius, 0.0, minor_radius, transform, material_idx)
+    }
+
+    // the box with corners a and b, then placed by transform, which may turn it any way
+    pub fn cuboid(a: Vec3, b: Vec3, transform: Mat4, material_idx: u32) -> Self {
+        // a flat box would make the transform singular
+        let size = (a - b).abs().max(Vec3::splat(1e-6));
+        let unit_to_box = Mat4::from_translation(a.min(b)) * Mat4::from_scale(size);
+        Self::new(ShapeType::Box, 0.0, 0.0, 0.0, transform * unit_to_box, material_idx)
+    }
+
+    fn new(shape_type: ShapeType, radius: f32, height: f32, minor_radius: f32,
+           transform: Mat4, material_idx: u32) -> Self {
+        Self {
+            world_to_object: transform.inverse(),
+            radius,
+            height,
+            minor_radius,
+            shape_type: shape_type as u32,
+            material_idx,
+            light_idx: NO_LIGHT,
+            medium_idx: NO_MEDIUM,
+            _buffer: 0u32
+        }
+    }
+
+    // fill the shape with one of the scene's media, turning it into an invisible boundary;
+    // a disk has no inside and is left as it is
+    pub fn with_medium(mut self, medium_idx: u32) -> Self {
+        if self.shape_type != ShapeType::Disk as u32 {
+            self.medium_idx = gpu_medium_idx(medium_idx);
+        }
+        self
+    }
+
+    // a medium already given by its place in the GPU media list, as other primitives keep it
+    pub(crate) fn with_gpu_medium_idx(mut self, medium_idx: u32) -> Self {
+        self.medium_idx = medium_idx;
+        self
+    }
+
+    // volume boundaries are never lights, whatever their material
+    pub(crate) fn has_medium(&self) -> bool {
+        self.medium_idx != NO_MEDIUM
+    }
+
+    pub fn material_idx(&self) -> u32 {
+        self.material_idx
+    }
+
+    pub(crate) fn set_light_idx(&mut self, light_idx: u32) {
+        self.light_idx = light_idx;
+    }
+
+    // the shape with transform applied after its own
+    pub(crate) fn transformed(&self, transform: &Mat4) -> Self {
+        Self { world_to_object: self.world_to_object * transform.inverse(), ..*self }
+    }
+
+    // the world space center of a disk and the images of its x and z radii
+    pub(crate) fn disk_frame(&self) -> Option<(Vec3, Vec3, Vec3)> {
+        if self.shape_type != ShapeType::Disk as u32 {
+            return None;
+        }
+        let object_to_world = self.world_to_object.inverse();
+        Some((object_to_world.transform_point3(Vec3::ZERO),
+              object_to_world.transform_vector3(Vec3::new(self.radius, 0.0, 0.0)),
+              object_to_world.transform_vector3(Vec3::new(0.0, 0.0, self.radius))))
+    }
+
+    pub fn get_aabb(&self) -> (Vec3, Vec3) {
+        // bound the shape in object space, then take the world space box around
+        // the eight transformed corners
+        let (local_min, local_max) = match self.shape_type {
+            0 | 2 => (Vec3::new(-self.radius, 0.0, -self.radius),
+                      Vec3::new(self.radius, self.height, self.radius)),
+            1 => (Vec3::new(-self.radius, 0.0, -self.radius),
+                  Vec3::new(self.radius, 0.0, self.radius)),
+            4 => (Vec3::ZERO, Vec3::ONE),
+            _ => {
+                let extent = self.radius + self.minor_radius;
+                (Vec3::new(-extent, -self.minor_radius, -extent),
+                 Vec3::new(extent, self.minor_radius, extent))
+            }
+        };
+
+        let object_to_world = self.world_to_object.inverse();
+        let mut aabb_min = Vec3::INFINITY;
+        let mut aabb_max = Vec3::NEG_INFINITY;
+        for i in 0..8 {
+            let corner = Vec3::new(
+                if i & 1 == 0 { local_min.x } else { local_max.x },
+                if i & 2 == 0 { local_min.y } else { local_max.y },
+                if i & 4 == 0 { local_min.z } else { local_max.z });
+            let world_corner = object_to_world.transform_point3(corner);
+            aabb_min = aabb_min.min(world_corner);
+            aabb_max = aabb_max.max(world_corner);
+        }
+        // a disk seen edge on has a flat box; pad it so the slab test still works
+        let pad = Vec3::select((aabb_max - aabb_min).cmplt(Vec3::splat(0.0001)),
+                               Vec3::splat(0.0001), Vec3::ZERO);
+        (aabb_min - pad, aabb_max + pad)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::assert_layout;
+
+    #[test]
+    fn layout_matches_wgsl() {
+        assert_layout!(Shape, "Shape", world_to_object, radius, height, minor_radius, shape_type,
+                       material_idx, light_idx, medium_idx);
+    }
+}
@@ -1,13 +1,20 @@
-use glam::{Vec3, Vec4, Vec4Swizzles};
+use glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
+use crate::light::NO_LIGHT;
+use crate::medium::{gpu_medium_idx, NO_MEDIUM};
 
+// a sphere moves linearly from center0 at time 0 to center1 at time 1;
+// a stationary sphere has both centers equal
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 pub struct Sphere {
-    pub center: Vec4,
+    pub center0: Vec4,
+    pub center1: Vec4,
     // albedo: Vec3,
     radius: f32,
     material_idx: u32,
-    _buffer: [u32; 2],
+    // index into the scene's light list if the sphere is emissive
+    light_idx: u32,
+    medium_idx: u32,
 }
 
 unsafe impl bytemuck::Pod for Sphere {}
@@ -16,12 +23,72 @@ unsafe impl bytemuck::Zeroable for Sphere {}
 
 impl Sphere {
     pub fn new(center: Vec3, radius: f32, material_idx: u32) -> Self {
-        Self { center: center.extend(0.0), radius, material_idx, _buffer: [0u32;2] }
+        Self::new_moving(center, center, radius, material_idx)
     }
 
+    pub fn new_moving(center0: Vec3, center1: Vec3, radius: f32, material_idx: u32) -> Self {
+        Self {
+            center0: center0.extend(0.0),
+            center1: center1.extend(0.0),
+            radius,
+            material_idx,
+            light_idx: NO_LIGHT,
+            medium_idx: NO_MEDIUM,
+        }
+    }
+
+    // fill the sphere with one of the scene's media, turning it into an invisible boundary
+    pub fn with_medium(mut self, medium_idx: u32) -> Self {
+        self.medium_idx = gpu_medium_idx(medium_idx);
+        self
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    // volume boundaries are never lights, whatever their material
+    pub(crate) fn has_medium(&self) -> bool {
+        self.medium_idx != NO_MEDIUM
+    }
+
+    pub fn material_idx(&self) -> u32 {
+        self.material_idx
+    }
+
+    pub(crate) fn set_light_idx(&mut self, light_idx: u32) {
+        self.light_idx = light_idx;
+    }
+
+    // the sphere moved by transform; a sphere stays round, so it takes the largest scale
+    pub(crate) fn transformed(&self, transform: &Mat4) -> Self {
+        let scale = transform.x_axis.truncate().length()
+            .max(transform.y_axis.truncate().length())
+            .max(transform.z_axis.truncate().length());
+        Self {
+            center0: transform.transform_point3(self.center0.xyz()).extend(0.0),
+            center1: transform.transform_point3(self.center1.xyz()).extend(0.0),
+            radius: self.radius * scale,
+            ..*self
+        }
+    }
+
+    // the box covers the sphere over its whole motion, so the BVH is valid at any ray time
     pub fn get_aabb(&self) -> (Vec3, Vec3) {
-        let aabb_min = self.center.xyz() - Vec3::splat(self.radius);
-        let aabb_max = self.center.xyz() + Vec3::splat(self.radius);
+        let aabb_min = self.center0.xyz().min(self.center1.xyz()) - Vec3::splat(self.radius);
+        let aabb_max = self.center0.xyz().max(self.center1.xyz()) + Vec3::splat(self.radius);
         (aabb_min, aabb_max)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::assert_layout;
+
+    #[test]
+    fn layout_matches_wgsl() {
+        assert_layout!(Sphere, "Sphere", center0, center1, radius, material_idx, light_idx,
+                       medium_idx);
+    }
+}
@@ -0,0 +1,79 @@
+use glam::{Vec3, Vec4};
+
+// texture_type will be indexed as follows:
+// 0 Image; 1 Checker; 2 Noise
+//
+// materials refer to textures by index; NO_TEXTURE means the slot is unused
+// and the material's constant value is used as is
+
+pub const NO_TEXTURE: u32 = u32::MAX;
+
+enum TextureType {
+    Image = 0,
+    Checker = 1,
+    Noise = 2,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct Texture {
+    color0: Vec4,
+    color1: Vec4,
+    scale: f32,
+    image_layer: u32,
+    texture_type: u32,
+    srgb: u32,
+}
+
+unsafe impl bytemuck::Pod for Texture {}
+unsafe impl bytemuck::Zeroable for Texture {}
+
+impl Texture {
+    // samples layer image_layer of the scene's image array at the hit's uv;
+    // color data (albedo, emission) is stored in sRGB, data like roughness is linear
+    pub fn image(image_layer: u32, srgb: bool) -> Self {
+        Self {
+            color0: Vec4::ONE,
+            color1: Vec4::ONE,
+            scale: 1.0,
+            image_layer,
+            texture_type: TextureType::Image as u32,
+            srgb: srgb as u32,
+        }
+    }
+
+    // a solid 3D checkerboard with cells of size scale
+    pub fn checker(scale: f32, even: Vec3, odd: Vec3) -> Self {
+        Self {
+            color0: even.extend(1.0),
+            color1: odd.extend(1.0),
+            scale,
+            image_layer: 0,
+            texture_type: TextureType::Checker as u32,
+            srgb: 0,
+        }
+    }
+
+    // marble-like Perlin turbulence; scale sets the frequency of the veins
+    pub fn noise(scale: f32, color: Vec3) -> Self {
+        Self {
+            color0: color.extend(1.0),
+            color1: Vec4::ZERO,
+            scale,
+            image_layer: 0,
+            texture_type: TextureType::Noise as u32,
+            srgb: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::assert_layout;
+
+    #[test]
+    fn layout_matches_wgsl() {
+        assert_layout!(Texture, "Texture", color0, color1, scale, image_layer, texture_type, srgb);
+    }
+}
@@ -0,0 +1,172 @@
+use glam::{Mat4, Vec2, Vec3, Vec4, Vec4Swizzles};
+use crate::light::NO_LIGHT;
+
+// a triangle of a mesh; its geometric normal follows the winding, counter-clockwise seen
+// from the front, which is the outside for the closed meshes glass needs. a triangle may
+// carry a normal at each corner to be smoothly interpolated; all zero means it is flat.
+// a color at each corner, white unless given, is interpolated and multiplies the albedo
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct Triangle {
+    p0: Vec4,
+    p1: Vec4,
+    p2: Vec4,
+    n0: Vec4,
+    n1: Vec4,
+    n2: Vec4,
+    // the texture coordinates of the first two corners, then of the third
+    uv01: Vec4,
+    uv2: Vec2,
+    material_idx: u32,
+    light_idx: u32,
+    // 8 bit sRGB, packed as rgba
+    colors: [u32; 3],
+    _buffer: u32,
+}
+
+unsafe impl bytemuck::Pod for Triangle {}
+unsafe impl bytemuck::Zeroable for Triangle {}
+
+impl Triangle {
+    pub fn new(p0: Vec3, p1: Vec3, p2: Vec3, material_idx: u32) -> Self {
+        Self {
+            p0: p0.extend(0.0),
+            p1: p1.extend(0.0),
+            p2: p2.extend(0.0),
+            n0: Vec4::ZERO,
+            n1: Vec4::ZERO,
+            n2: Vec4::ZERO,
+            uv01: Vec4::new(0.0, 0.0, 1.0, 0.0),
+            uv2: Vec2::new(0.0, 1.0),
+            material_idx,
+            light_idx: NO_LIGHT,
+            colors: [u32::MAX; 3],
+            _buffer: 0,
+        }
+    }
+
+    pub fn with_normals(mut self, n0: Vec3, n1: Vec3, n2: Vec3) -> Self {
+        self.n0 = n0.normalize_or_zero().extend(0.0);
+        self.n1 = n1.normalize_or_zero().extend(0.0);
+        self.n2 = n2.normalize_or_zero().extend(0.0);
+        self
+    }
+
+    // image textures have v = 0 at the bottom of the image
+    pub fn with_uvs(mut self, uv0: Vec2, uv1: Vec2, uv2: Vec2) -> Self {
+        self.uv01 = Vec4::new(uv0.x, uv0.y, uv1.x, uv1.y);
+        self.uv2 = uv2;
+        self
+    }
+
+    // linear colors, stored as sRGB
+    pub fn with_colors(mut self, c0: Vec3, c1: Vec3, c2: Vec3) -> Self {
+        self.colors = [pack_srgb(c0), pack_srgb(c1), pack_srgb(c2)];
+        self
+    }
+
+    pub fn vertices(&self) -> [Vec3; 3] {
+        [self.p0.xyz(), self.p1.xyz(), self.p2.xyz()]
+    }
+
+    // all zero for a flat triangle
+    pub fn normals(&self) -> [Vec3; 3] {
+        [self.n0.xyz(), self.n1.xyz(), self.n2.xyz()]
+    }
+
+    pub fn material_idx(&self) -> u32 {
+        self.material_idx
+    }
+
+    pub(crate) fn set_light_idx(&mut self, light_idx: u32) {
+        self.light_idx = light_idx;
+    }
+
+    // a mirroring transform would reverse the winding and turn the triangle inside out, so
+    // then corners 1 and 2 trade places, with everything they carry
+    pub(crate) fn transformed(&self, transform: &Mat4) -> Self {
+        let normal_transform = transform.inverse().transpose();
+        let normal = |n: Vec4| {
+            normal_transform.transform_vector3(n.xyz()).normalize_or_zero().extend(0.0)
+        };
+        let moved = Self {
+            p0: transform.transform_point3(self.p0.xyz()).extend(0.0),
+            p1: transform.transform_point3(self.p1.xyz()).extend(0.0),
+            p2: transform.transform_point3(self.p2.xyz()).extend(0.0),
+            n0: normal(self.n0),
+            n1: normal(self.n1),
+            n2: normal(self.n2),
+            ..*self
+        };
+        if transform.determinant() < 0.0 {
+            moved.with_corners_swapped()
+        } else {
+            moved
+        }
+    }
+
+    fn with_corners_swapped(mut self) -> Self {
+        std::mem::swap(&mut self.p1, &mut self.p2);
+        std::mem::swap(&mut self.n1, &mut self.n2);
+        let uv1 = self.uv01.zw();
+        self.uv01 = self.uv01.xy().extend(self.uv2.x).extend(self.uv2.y);
+        self.uv2 = uv1;
+        self.colors.swap(1, 2);
+        self
+    }
+
+    pub fn get_aabb(&self) -> (Vec3, Vec3) {
+        let [p0, p1, p2] = self.vertices();
+        let aabb_min = p0.min(p1).min(p2);
+        let aabb_max = p0.max(p1).max(p2);
+        // a triangle lying in an axis plane has a flat box; pad it so the slab test still works
+        let pad = Vec3::select((aabb_max - aabb_min).cmplt(Vec3::splat(0.0001)),
+                               Vec3::splat(0.0001), Vec3::ZERO);
+        (aabb_min - pad, aabb_max + pad)
+    }
+}
+
+fn pack_srgb(color: Vec3) -> u32 {
+    let encode = |c: f32| {
+        let c = c.clamp(0.0, 1.0);
+        let srgb = if c <= 0.0031308 { 12.92 * c } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+        (srgb * 255.0).round() as u32
+    };
+    encode(color.x) | encode(color.y) << 8 | encode(color.z) << 16 | 0xff << 24
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::assert_layout;
+
+    #[test]
+    fn layout_matches_wgsl() {
+        assert_layout!(Triangle, "Triangle", p0, p1, p2, n0, n1, n2, uv01, uv2, material_idx,
+                       light_idx, colors);
+    }
+
+    #[test]
+    fn mirroring_keeps_the_winding_and_what_the_corners_carry() {
+        let triangle = Triangle::new(Vec3::ZERO, Vec3::X, Vec3::Y, 0)
+            .with_normals(Vec3::Z, Vec3::Z, Vec3::Z)
+            .with_uvs(Vec2::new(0.1, 0.2), Vec2::new(0.3, 0.4), Vec2::new(0.5, 0.6))
+            .with_colors(Vec3::X, Vec3::Y, Vec3::Z);
+        let mirror = Mat4::from_scale(Vec3::new(1.0, 1.0, -1.0));
+        let mirrored = triangle.transformed(&mirror);
+
+        let [p0, p1, p2] = mirrored.vertices();
+        assert_eq!([p0, p1, p2], [Vec3::ZERO, Vec3::Y, Vec3::X]);
+        // the geometric normal still agrees with the mirrored shading normals
+        let face_normal = (p1 - p0).cross(p2 - p0);
+        assert!(face_normal.dot(mirrored.n0.xyz()) > 0.0);
+        assert_eq!(mirrored.n1.xyz(), Vec3::NEG_Z);
+        assert_eq!(mirrored.uv01, Vec4::new(0.1, 0.2, 0.5, 0.6));
+        assert_eq!(mirrored.uv2, Vec2::new(0.3, 0.4));
+        assert_eq!(mirrored.colors, [triangle.colors[0], triangle.colors[2], triangle.colors[1]]);
+
+        let turned = triangle.transformed(&Mat4::from_rotation_x(1.0));
+        assert_eq!(turned.uv01, triangle.uv01);
+        assert_eq!(turned.colors, triangle.colors);
+    }
+}
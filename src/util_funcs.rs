@@ -1,37 +1,51 @@
-use rand::Rng;
+use rand::{Rng, SeedableRng};
 use rand::seq::SliceRandom;
+use rand_chacha::ChaCha12Rng;
 use glam::Vec3;
 
-#[allow(dead_code)]
-pub fn random_u32() -> u32 {
-    let mut rng = rand::thread_rng();
-    rng.gen::<u32>()
+// scenes are generated from a seeded generator, so the same seed always gives the same scene;
+// ChaCha12 by name rather than StdRng, which rand is free to change between versions and
+// platforms, as the golden references depend on the scenes staying put
+pub fn seeded_rng(seed: u64) -> ChaCha12Rng {
+    ChaCha12Rng::seed_from_u64(seed)
 }
 
 #[allow(dead_code)]
-pub fn random_f32() -> f32 {
-    let mut rng = rand::thread_rng();
-    rng.gen::<f32>()
+pub fn random_u32(rng: &mut impl Rng) -> u32 {
+    rng.random::<u32>()
 }
 
-#[allow(dead_code)]
-pub fn random_range_f32(min: f32, max: f32) -> f32 {
-    let mut rng = rand::thread_rng();
+pub fn random_f32(rng: &mut impl Rng) -> f32 {
+    rng.random::<f32>()
+}
+
+pub fn random_range_f32(rng: &mut impl Rng, min: f32, max: f32) -> f32 {
     rng.gen_range(min .. max)
 }
 
-pub fn random_vec3() -> Vec3 {
-    Vec3::new(random_f32(), random_f32(), random_f32())
+pub fn random_vec3(rng: &mut impl Rng) -> Vec3 {
+    Vec3::new(random_f32(rng), random_f32(rng), random_f32(rng))
 }
-pub fn random_vec3_range(min: f32, max: f32) -> Vec3 {
-    Vec3::new(random_range_f32(min, max),
-              random_range_f32(min, max),
-              random_range_f32(min, max))
+pub fn random_vec3_range(rng: &mut impl Rng, min: f32, max: f32) -> Vec3 {
+    Vec3::new(random_range_f32(rng, min, max),
+              random_range_f32(rng, min, max),
+              random_range_f32(rng, min, max))
 }
 
 #[allow(dead_code)]
-pub fn shuffle_array<T>(mut a: Vec<T>) -> Vec<T> {
-    let mut rng = rand::thread_rng();
-    a.shuffle(&mut rng);
+pub fn shuffle_array<T>(rng: &mut impl Rng, mut a: Vec<T>) -> Vec<T> {
+    a.shuffle(rng);
     a
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_rng_gives_the_same_numbers_everywhere() {
+        let mut rng = seeded_rng(1);
+        let numbers: Vec<u32> = (0..4).map(|_| random_u32(&mut rng)).collect();
+        assert_eq!(numbers, [3543144545, 4184349284, 3423430986, 2968834341]);
+    }
+}
@@ -0,0 +1,90 @@
+use image::{Rgba, RgbaImage};
+
+// SSIM is taken over windows of this many pixels a side, WINDOW_STEP apart
+const WINDOW_SIZE: u32 = 8;
+const WINDOW_STEP: u32 = 4;
+// the constants that keep SSIM stable over flat windows, for values in [0, 1]
+const C1: f64 = 0.01 * 0.01;
+const C2: f64 = 0.03 * 0.03;
+// how much the diff image brightens differences, so small ones show up
+const DIFF_GAIN: f32 = 4.0;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Comparison {
+    // over every color channel, in [0, 1]
+    pub rmse: f32,
+    // the mean structural similarity of the luma; one for identical images
+    pub ssim: f32,
+}
+
+// both images must be the same size
+pub fn compare(reference: &RgbaImage, actual: &RgbaImage) -> Comparison {
+    assert_eq!(reference.dimensions(), actual.dimensions());
+    Comparison { rmse: rmse(reference, actual), ssim: ssim(reference, actual) }
+}
+
+// the absolute difference of every channel, brightened by DIFF_GAIN
+pub fn diff_image(reference: &RgbaImage, actual: &RgbaImage) -> RgbaImage {
+    RgbaImage::from_fn(reference.width(), reference.height(), |x, y| {
+        let (a, b) = (reference.get_pixel(x, y), actual.get_pixel(x, y));
+        let channel = |c: usize| {
+            let difference = (a[c] as f32 - b[c] as f32).abs() * DIFF_GAIN;
+            difference.min(255.0) as u8
+        };
+        Rgba([channel(0), channel(1), channel(2), 255])
+    })
+}
+
+fn rmse(reference: &RgbaImage, actual: &RgbaImage) -> f32 {
+    let mut sum = 0.0_f64;
+    for (a, b) in reference.pixels().zip(actual.pixels()) {
+        for c in 0..3 {
+            let difference = (a[c] as f64 - b[c] as f64) / 255.0;
+            sum += difference * difference;
+        }
+    }
+    let count = 3 * reference.width() as u64 * reference.height() as u64;
+    (sum / count.max(1) as f64).sqrt() as f32
+}
+
+fn ssim(reference: &RgbaImage, actual: &RgbaImage) -> f32 {
+    let (width, height) = reference.dimensions();
+    let (a, b) = (luma(reference), luma(actual));
+    let window = WINDOW_SIZE.min(width).min(height);
+    let mut total = 0.0_f64;
+    let mut windows = 0;
+    for y0 in (0..=height - window).step_by(WINDOW_STEP as usize) {
+        for x0 in (0..=width - window).step_by(WINDOW_STEP as usize) {
+            let pixels = (y0..y0 + window)
+                .flat_map(|y| (x0..x0 + window).map(move |x| (y * width + x) as usize));
+            let n = (window * window) as f64;
+            let (mut mean_a, mut mean_b) = (0.0, 0.0);
+            for i in pixels.clone() {
+                mean_a += a[i];
+                mean_b += b[i];
+            }
+            mean_a /= n;
+            mean_b /= n;
+            let (mut var_a, mut var_b, mut covariance) = (0.0, 0.0, 0.0);
+            for i in pixels {
+                var_a += (a[i] - mean_a) * (a[i] - mean_a);
+                var_b += (b[i] - mean_b) * (b[i] - mean_b);
+                covariance += (a[i] - mean_a) * (b[i] - mean_b);
+            }
+            var_a /= n - 1.0;
+            var_b /= n - 1.0;
+            covariance /= n - 1.0;
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            windows += 1;
+        }
+    }
+    (total / windows as f64) as f32
+}
+
+// Rec. 709 luma of the stored values, in [0, 1]
+fn luma(image: &RgbaImage) -> Vec<f64> {
+    image.pixels()
+        .map(|p| (0.2126 * p[0] as f64 + 0.7152 * p[1] as f64 + 0.0722 * p[2] as f64) / 255.0)
+        .collect()
+}
@@ -0,0 +1,125 @@
+// golden image tests: each scene is rendered headless and compared with its reference in
+// tests/golden/references, passing if the two are close enough by both SSIM and RMSE. a failing
+// scene leaves its render and a diff in the target directory to look at; running with
+// WIW_BLESS_GOLDEN=1 writes the references from this adapter instead, after a change that is
+// meant to alter the images
+mod compare;
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use wiw::{HeadlessRenderer, LightSampling, RenderParameters, Sampler, SamplingParameters,
+          SceneGenerator};
+use crate::compare::{compare, diff_image};
+
+const VIEWPORT: (u32, u32) = (128, 72);
+const SEED: u64 = 1;
+// renders on the adapter the references came from match them exactly; the slack is for other
+// adapters, whose arithmetic differs enough to send the odd path another way
+const MIN_SSIM: f32 = 0.9;
+const MAX_RMSE: f32 = 0.04;
+
+// not every adapter copes with devices being made on several threads at once, so the scenes
+// render one at a time
+static RENDER: Mutex<()> = Mutex::new(());
+
+fn check_golden(name: &str, scene: &str, sampling_parameters: SamplingParameters) {
+    let generator = SceneGenerator::from_name(scene).unwrap();
+    let mut scene = generator.generate(SEED);
+    scene.update_lights();
+    let render_parameters = RenderParameters {
+        camera: generator.camera(),
+        sampling_parameters: SamplingParameters { seed: SEED, ..sampling_parameters },
+        viewport: VIEWPORT,
+    };
+
+    let image = {
+        let _guard = RENDER.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Some(renderer) = HeadlessRenderer::new(&scene, &render_parameters) else {
+            assert!(std::env::var_os("WIW_SKIP_GPU_TESTS").is_some(),
+                    "no GPU adapter to render {name} with; set WIW_SKIP_GPU_TESTS=1 to skip \
+                     the tests that need one");
+            return;
+        };
+        renderer.render(&render_parameters)
+    };
+
+    let reference_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden/references")
+        .join(format!("{name}.png"));
+    if std::env::var_os("WIW_BLESS_GOLDEN").is_some() {
+        image.save(&reference_path).unwrap();
+        return;
+    }
+    let reference = image::open(&reference_path)
+        .unwrap_or_else(|err| panic!("can't read the reference {}: {err}; run with \
+                                      WIW_BLESS_GOLDEN=1 to write it", reference_path.display()))
+        .to_rgba8();
+
+    let output = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    std::fs::create_dir_all(&output).unwrap();
+    let actual_path = output.join(format!("{name}.actual.png"));
+    if reference.dimensions() != image.dimensions() {
+        image.save(&actual_path).unwrap();
+        panic!("{name} rendered at {:?} but its reference is {:?}; the render is at {}",
+               image.dimensions(), reference.dimensions(), actual_path.display());
+    }
+    let comparison = compare(&reference, &image);
+    if comparison.ssim < MIN_SSIM || comparison.rmse > MAX_RMSE {
+        let diff_path = output.join(format!("{name}.diff.png"));
+        image.save(&actual_path).unwrap();
+        diff_image(&reference, &image).save(&diff_path).unwrap();
+        panic!("{name} differs from its reference with SSIM {} (at least {MIN_SSIM}) and RMSE {} \
+                (at most {MAX_RMSE}); the render is at {} and the diff at {}",
+               comparison.ssim, comparison.rmse, actual_path.display(), diff_path.display());
+    }
+}
+
+#[test]
+fn cornell_box() {
+    check_golden("cornell_box", "cornell_box", SamplingParameters {
+        samples_per_pixel: 16,
+        num_bounces: 8,
+        ..Default::default()
+    });
+}
+
+#[test]
+fn material_grid() {
+    check_golden("material_grid", "material_grid", SamplingParameters {
+        samples_per_pixel: 8,
+        num_bounces: 6,
+        light_sampling: LightSampling::Power,
+        ..Default::default()
+    });
+}
+
+#[test]
+fn book_one() {
+    check_golden("book_one", "book_one", SamplingParameters {
+        samples_per_pixel: 8,
+        num_bounces: 8,
+        sampler: Sampler::Random,
+        ..Default::default()
+    });
+}
+
+#[test]
+fn many_spheres() {
+    check_golden("many_spheres", "many_spheres:300", SamplingParameters {
+        samples_per_pixel: 8,
+        num_bounces: 6,
+        light_sampling: LightSampling::Uniform,
+        sampler: Sampler::BlueNoise,
+        ..Default::default()
+    });
+}
+
+#[test]
+fn identical_images_compare_exactly() {
+    let image = image::RgbaImage::from_fn(32, 24, |x, y| {
+        image::Rgba([x as u8 * 8, y as u8 * 10, 7, 255])
+    });
+    let comparison = compare(&image, &image);
+    assert_eq!(comparison.rmse, 0.0);
+    assert!((comparison.ssim - 1.0).abs() < 1e-6);
+}